@@ -1,33 +1,697 @@
-use std::time::{Duration, Instant};
+use cgmath::{EuclideanSpace, InnerSpace, Rad, Vector3};
+use std::{
+    f32::consts::PI,
+    fs, mem,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use wgpu::{Color, Face};
 use winit::{
-    event::{Event, WindowEvent},
+    event::{
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        VirtualKeyCode, WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
 
-use crate::render::Renderer;
+use super::{
+    benchmark::{BenchmarkReport, WARMUP_FRAMES},
+    builder::{EventHandler, LoadErrorHandler, LoadProgressHandler},
+    ApplicationBuilder,
+};
+use crate::loader::{self, Conversion, LoadError, LoadPhase, LoadProgress, LoadedModel};
+use crate::render::{
+    Axis, DisplayMode, GizmoMode, KeptScenes, MaterialChannel, NormalSpace, Plane, Renderer, Slice,
+    ViewPreset, CLAY_MATERIAL, MAX_CLIP_PLANES,
+};
+
+const CLIP_PLANE_STEP: f32 = 0.05;
+const SHADOW_RESOLUTION: u32 = 2048;
+const GROUND_SHADOW_OPACITY: f32 = 0.5;
+// how far one scroll wheel notch moves the camera
+const ZOOM_STEP: f32 = 0.9;
+const SMOOTHING_ITERATIONS: u32 = 10;
+const SMOOTHING_LAMBDA: f32 = 0.5;
+const SUBDIVISION_ITERATIONS: u32 = 1;
+// fractions of the scene's extent along the slice axis
+const SLICE_STEP: f32 = 0.02;
+const DEFAULT_SLICE_THICKNESS: f32 = 0.1;
+// how much one key press grows or shrinks the slice
+const SLICE_THICKNESS_FACTOR: f32 = 1.25;
+const AO_SAMPLES: u32 = 32;
+// radians of orbit per pixel dragged
+const ORBIT_SPEED: f32 = 0.01;
+// window opacity change per key press
+const OPACITY_STEP: f32 = 0.1;
+// back faces while culling is off, loud enough to spot holes and flipped triangles
+const BACKFACE_COLOR: Color = Color {
+    r: 1.0,
+    g: 0.0,
+    b: 0.5,
+    a: 1.0,
+};
+// how far the benchmark orbit swings above and below the starting pitch
+const BENCHMARK_PITCH_SWING: f32 = 0.3;
+// in the working directory, where F4 saves the render setup and shift+F4 reads it from
+const RENDER_SETUP_PATH: &str = "render_setup.json";
+
+// planes start out through the origin, cycling through the axes
+fn default_clip_plane(index: usize) -> Plane {
+    let normal = match index % 3 {
+        0 => Vector3::unit_x(),
+        1 => Vector3::unit_y(),
+        _ => Vector3::unit_z(),
+    };
+    Plane::new(normal, 0.0)
+}
+
+// how loaded files get prepared for the scene, from the builder
+struct LoadOptions {
+    conversion: Conversion,
+    // files at least this big get streamed in when their format allows it
+    stream_threshold: Option<u64>,
+    on_progress: Option<LoadProgressHandler>,
+    on_error: Option<LoadErrorHandler>,
+}
+
+impl LoadOptions {
+    // hands a load's progress on to the builder's handler, if there is one
+    fn progress<'a>(&'a mut self, path: &'a Path) -> impl FnMut(LoadProgress) + 'a {
+        let handler = &mut self.on_progress;
+        move |progress| {
+            if let Some(handler) = handler.as_mut() {
+                handler(path, progress);
+            }
+        }
+    }
+
+    fn streams(&self, path: &Path) -> bool {
+        let big = |threshold| fs::metadata(path).map_or(false, |meta| meta.len() >= threshold);
+        loader::streams_in_chunks(path) && self.stream_threshold.map_or(false, big)
+    }
+
+    // logs a failed load and passes it on to the builder's handler, if there is one
+    fn report_error(&mut self, path: &Path, err: &LoadError) {
+        log::error!("failed to load {}: {}", path.display(), err);
+        if let Some(handler) = self.on_error.as_mut() {
+            handler(path, err);
+        }
+    }
+}
+
+// the view preset saved next to a model, if there is one, returning whether it was applied
+fn apply_sidecar_preset(renderer: &mut Renderer, path: &Path) -> bool {
+    match ViewPreset::load(path) {
+        Ok(Some(preset)) => {
+            log::info!("applying view preset {}", path.display());
+            renderer.apply_view_preset(&preset);
+            true
+        }
+        Ok(None) => false,
+        Err(err) => {
+            log::error!("failed to read {}: {}", path.display(), err);
+            false
+        }
+    }
+}
+
+/*
+ * Converts `model` the way the builder asks and adds all of it to the scene, reporting
+ * UploadingGeometry by how many of its parts have gone to the renderer.
+ */
+fn add_model(
+    renderer: &mut Renderer,
+    path: &Path,
+    mut model: LoadedModel,
+    options: &mut LoadOptions,
+) {
+    model.prepare(&options.conversion, path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    log::info!(
+        "loaded {} ({} triangles, {} cameras)",
+        path.display(),
+        model.mesh.triangle_count() + model.strips.triangle_count(),
+        model.cameras.len()
+    );
+    let parts = 1
+        + !model.strips.indices.is_empty() as usize
+        + !model.points.indices.is_empty() as usize
+        + !model.lines.indices.is_empty() as usize;
+    let mut progress = options.progress(path);
+    progress(LoadProgress::new(LoadPhase::UploadingGeometry, 0.0));
+    let mut uploaded = 0;
+    let mut part_done = || {
+        uploaded += 1;
+        let fraction = uploaded as f32 / parts as f32;
+        progress(LoadProgress::new(LoadPhase::UploadingGeometry, fraction));
+    };
+    renderer.add_object(&name, model.mesh);
+    part_done();
+    if !model.strips.indices.is_empty() {
+        renderer.add_object(&format!("{} (strips)", name), model.strips);
+        part_done();
+    }
+    if !model.points.indices.is_empty() {
+        renderer.add_object(&format!("{} (points)", name), model.points);
+        part_done();
+    }
+    if !model.lines.indices.is_empty() {
+        renderer.add_lines(&format!("{} (lines)", name), model.lines);
+        part_done();
+    }
+    renderer.add_cameras(model.cameras);
+    renderer.add_skeletons(model.skeletons);
+}
 
 pub struct Application {
     window: Window,
     frame_rate: f64,
+    fixed_timestep: Option<Duration>,
+    max_frame_delta: Duration,
     event_loop: Option<EventLoop<()>>,
     renderer: Renderer,
+    active_clip_plane: usize,
+    show_stats: bool,
+    // last known cursor position in physical pixels
+    cursor: (f32, f32),
+    modifiers: ModifiersState,
+    // unsnapped yaw and pitch while the left button drags, see Camera::set_orbit_angles
+    orbit_drag: Option<(Rad<f32>, Rad<f32>)>,
+    // snap every orbit, not just the ones with shift held
+    snap_orbit: bool,
+    // the right button is down, dragging pans
+    pan_drag: bool,
+    // sidecar of the first file loaded, where F2 saves the view to
+    preset_path: Option<PathBuf>,
+    load_options: LoadOptions,
+    // the glTF file whose scenes Y steps through, when it's the only one loaded
+    scene_file: Option<PathBuf>,
+    title: String,
+    // file names of what's on screen and of what a dropped file replaced, A flips between them
+    model_name: Option<String>,
+    previous_model_name: Option<String>,
+    showing_previous: bool,
+    on_event: Option<EventHandler>,
 }
 
 impl Application {
     pub fn new(win_title: &str, frame_rate: f64) -> Self {
+        Self::builder()
+            .title(win_title)
+            .frame_rate(frame_rate)
+            .build()
+    }
+
+    pub fn builder() -> ApplicationBuilder {
+        ApplicationBuilder::new()
+    }
+
+    pub(super) fn from_builder(builder: ApplicationBuilder) -> Self {
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
-            .with_title(win_title)
+            .with_title(&builder.title)
+            .with_transparent(builder.transparent)
+            .with_decorations(builder.decorations)
             .build(&event_loop)
             .unwrap();
-        let renderer = Renderer::new(&window);
+        let mut renderer = Renderer::with_adapter_attempts(&window, builder.adapter_attempts);
+        renderer.init_pipeline();
+        renderer.set_transparent(builder.transparent);
+        renderer.set_max_frames_in_flight(builder.max_frames_in_flight);
+        renderer.set_memory_budget(builder.memory_budget);
+        let (orbit, pan, zoom) = builder.sensitivity;
+        renderer.camera.set_sensitivity(orbit, pan, zoom);
+        let mut load_options = LoadOptions {
+            conversion: Conversion {
+                coordinate_system: builder.coordinate_system,
+                deterministic_geometry: builder.deterministic_geometry,
+                repair_winding: builder.repair_winding,
+                optimize_mesh: builder.optimize_mesh,
+            },
+            stream_threshold: builder.stream_threshold,
+            on_progress: builder.on_load_progress,
+            on_error: builder.on_load_error,
+        };
+        // streamed files go through the same conversion and budget as the rest
+        renderer.set_load_conversion(load_options.conversion);
+        let mut preset_path = None;
+        let mut scene_file = None;
+        let mut streamed = Vec::new();
+        let model_name = builder.files.first().map(|path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        for path in &builder.files {
+            if load_options.streams(path) {
+                streamed.push(path);
+                if preset_path.is_none() {
+                    preset_path = Some(ViewPreset::sidecar_path(path));
+                }
+                continue;
+            }
+            let loaded = loader::load_with_progress(path, &mut load_options.progress(path))
+                .and_then(|model| {
+                    renderer
+                        .check_memory(&model, KeptScenes::Both)
+                        .map(|_| model)
+                });
+            match loaded {
+                Ok(model) => {
+                    // scenes can only be swapped when nothing else shares the view
+                    if builder.files.len() == 1 && model.scenes.len() > 1 {
+                        renderer.set_gltf_scenes(model.scenes.clone(), model.scene);
+                        scene_file = Some(path.clone());
+                    }
+                    add_model(&mut renderer, path, model, &mut load_options);
+                    if preset_path.is_none() {
+                        preset_path = Some(ViewPreset::sidecar_path(path));
+                    }
+                }
+                // one bad file shouldn't stop the rest of the scene from showing up
+                Err(err) => load_options.report_error(path, &err),
+            }
+        }
+        if renderer.objects.is_empty() && renderer.lines.is_empty() && streamed.is_empty() {
+            renderer.add_cube();
+        } else {
+            renderer.frame_scene();
+        }
+        if let Some(axis) = builder.up_axis {
+            renderer.set_up_axis(axis);
+        }
+        // applied last so the framing doesn't undo its camera
+        let mut view_set = match &preset_path {
+            Some(path) => apply_sidecar_preset(&mut renderer, path),
+            None => false,
+        };
+        if let Some(preset) = builder.preset {
+            renderer.apply_preset(preset);
+            view_set = true;
+        }
+        // streams frame what arrives unless the view has been set up already
+        for path in streamed {
+            let started = if view_set {
+                renderer.load_streaming_in_place(path)
+            } else {
+                renderer.load_streaming(path)
+            };
+            if let Err(err) = started {
+                load_options.report_error(path, &err);
+            }
+        }
 
         Self {
             window,
-            frame_rate,
+            frame_rate: builder.frame_rate,
+            fixed_timestep: builder.fixed_timestep,
+            max_frame_delta: builder.max_frame_delta,
             event_loop: Some(event_loop),
             renderer,
+            active_clip_plane: 0,
+            show_stats: false,
+            cursor: (0.0, 0.0),
+            modifiers: ModifiersState::empty(),
+            orbit_drag: None,
+            snap_orbit: false,
+            pan_drag: false,
+            preset_path,
+            load_options,
+            scene_file,
+            title: builder.title,
+            model_name,
+            previous_model_name: None,
+            showing_previous: false,
+            on_event: builder.on_event,
+        }
+    }
+
+    /*
+     * Replaces the scene with a dropped file, keeping the camera where it is so the two
+     * versions line up unless the file has a view preset saved next to it. What was showing
+     * becomes the previous version A flips back to.
+     */
+    fn load_dropped_file(&mut self, path: &Path) {
+        self.renderer.reset_taa_accumulation();
+        // the scene on show gets stashed as the previous one, the one stashed before goes
+        let loaded = if self.load_options.streams(path) {
+            // checked against the budget chunk by chunk as they arrive
+            Ok(None)
+        } else {
+            loader::load_with_progress(path, &mut self.load_options.progress(path)).and_then(
+                |model| {
+                    self.renderer
+                        .check_memory(&model, KeptScenes::Current)
+                        .map(|_| Some(model))
+                },
+            )
+        };
+        match loaded {
+            Ok(model) => {
+                self.renderer.clear_scene();
+                // its scenes would be swapped in over the comparison
+                self.renderer.set_gltf_scenes(Vec::new(), None);
+                self.scene_file = None;
+                match model {
+                    Some(model) => {
+                        add_model(&mut self.renderer, path, model, &mut self.load_options)
+                    }
+                    None => {
+                        if let Err(err) = self.renderer.load_streaming_in_place(path) {
+                            self.load_options.report_error(path, &err);
+                        }
+                    }
+                }
+                // F2 saves next to the file on show from now on
+                let preset_path = ViewPreset::sidecar_path(path);
+                apply_sidecar_preset(&mut self.renderer, &preset_path);
+                self.preset_path = Some(preset_path);
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                self.previous_model_name = self.model_name.replace(name.into_owned());
+                self.showing_previous = false;
+                self.update_title();
+            }
+            Err(err) => self.load_options.report_error(path, &err),
+        }
+    }
+
+    fn toggle_previous_model(&mut self) {
+        if !self.renderer.swap_previous_scene() {
+            log::info!("no previous model to compare against, drop a file on the window");
+            return;
+        }
+        mem::swap(&mut self.model_name, &mut self.previous_model_name);
+        self.showing_previous = !self.showing_previous;
+        self.update_title();
+    }
+
+    // the title says which of the two versions is up once there's something to compare
+    fn update_title(&self) {
+        let name = self.model_name.as_deref().unwrap_or("unnamed");
+        let title = if self.renderer.previous_scene.is_none() {
+            format!("{} - {}", self.title, name)
+        } else if self.showing_previous {
+            format!("{} - {} (previous)", self.title, name)
+        } else {
+            format!("{} - {} (current)", self.title, name)
+        };
+        self.window.set_title(&title);
+    }
+
+    // swaps in the file's next scene and frames it, wrapping around after the last
+    fn cycle_gltf_scene(&mut self) {
+        let path = match &self.scene_file {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let scenes = self.renderer.gltf_scenes();
+        let index = match self.renderer.active_gltf_scene {
+            Some(active) => scenes[(active + 1) % scenes.len()].index,
+            None => scenes[0].index,
+        };
+        // both the scene on show and any stashed one are dropped before the upload
+        let loaded = loader::load_gltf_scene(&path, index).and_then(|model| {
+            self.renderer
+                .check_memory(&model, KeptScenes::None)
+                .map(|_| model)
+        });
+        match loaded {
+            Ok(model) => {
+                let info = &self.renderer.gltf_scenes[index];
+                log::info!(
+                    "scene {}: {}",
+                    index,
+                    info.name.as_deref().unwrap_or("unnamed")
+                );
+                self.renderer.clear_scene();
+                // A compares against dropped files, not the file's other scenes
+                self.renderer.previous_scene = None;
+                self.renderer.active_gltf_scene = Some(index);
+                add_model(&mut self.renderer, &path, model, &mut self.load_options);
+                self.renderer.frame_scene();
+            }
+            Err(err) => {
+                log::error!("failed to load scene {}", index);
+                self.load_options.report_error(&path, &err);
+            }
+        }
+    }
+
+    // how far the scene reaches along an axis, and where it's centred on it
+    fn scene_extent(&self, axis: Axis) -> (f32, f32) {
+        match self.renderer.scene_bounds() {
+            Some(bounds) => {
+                let unit = axis.unit();
+                let (min, max) = (bounds.min.to_vec().dot(unit), bounds.max.to_vec().dot(unit));
+                ((max - min).max(f32::EPSILON), (min + max) / 2.0)
+            }
+            None => (1.0, 0.0),
+        }
+    }
+
+    // off, then a slice along each axis in turn, centred on the scene
+    fn cycle_slice(&mut self) {
+        let axis = match self.renderer.slice.map(|slice| slice.axis) {
+            None => Some(Axis::X),
+            Some(Axis::X) => Some(Axis::Y),
+            Some(Axis::Y) => Some(Axis::Z),
+            Some(Axis::Z) => None,
+        };
+        let slice = axis.map(|axis| {
+            let (extent, center) = self.scene_extent(axis);
+            Slice {
+                axis,
+                position: center,
+                thickness: extent * DEFAULT_SLICE_THICKNESS,
+            }
+        });
+        self.renderer.set_slice(slice);
+    }
+
+    fn handle_key(&mut self, key: VirtualKeyCode) {
+        // most keys change the picture without moving the view
+        self.renderer.reset_taa_accumulation();
+        match key {
+            VirtualKeyCode::F2 => match &self.preset_path {
+                Some(path) => match self.renderer.view_preset().save(path) {
+                    Ok(()) => log::info!("saved view preset {}", path.display()),
+                    Err(err) => log::error!("failed to save {}: {}", path.display(), err),
+                },
+                None => log::warn!("no model loaded to save a view preset for"),
+            },
+            VirtualKeyCode::F4 => {
+                let path = Path::new(RENDER_SETUP_PATH);
+                let (result, action, done) = if self.modifiers.shift() {
+                    (self.renderer.import_setup(path), "load", "loaded")
+                } else {
+                    (self.renderer.export_setup(path), "save", "saved")
+                };
+                match result {
+                    Ok(()) => log::info!("{} render setup {}", done, path.display()),
+                    Err(err) => log::error!("failed to {} {}: {}", action, path.display(), err),
+                }
+            }
+            // a report on stdout every 100 frames, there's no text renderer for an on-screen one
+            VirtualKeyCode::F3 => {
+                self.show_stats = !self.show_stats;
+                self.renderer.set_gpu_timing(self.show_stats);
+            }
+            VirtualKeyCode::M => {
+                let mode = self.renderer.display_mode.next();
+                self.renderer.set_display_mode(mode);
+            }
+            // steps through the material channels, starting there from the other modes
+            VirtualKeyCode::U => {
+                let channel = match self.renderer.display_mode {
+                    DisplayMode::Channel(channel) => channel.next(),
+                    _ => MaterialChannel::Albedo,
+                };
+                self.renderer
+                    .set_display_mode(DisplayMode::Channel(channel));
+            }
+            VirtualKeyCode::Q => {
+                let material = match self.renderer.material_override {
+                    Some(_) => None,
+                    None => Some(CLAY_MATERIAL),
+                };
+                self.renderer.set_material_override(material);
+            }
+            VirtualKeyCode::B => {
+                let space = match self.renderer.normal_space {
+                    NormalSpace::World => NormalSpace::View,
+                    NormalSpace::View => NormalSpace::World,
+                };
+                self.renderer.set_normal_space(space);
+            }
+            // the first press bakes, later ones toggle what was baked
+            VirtualKeyCode::K => {
+                if self
+                    .renderer
+                    .objects
+                    .iter()
+                    .any(|object| object.ao.is_some())
+                {
+                    let visible = !self.renderer.vertex_ao_visible;
+                    self.renderer.show_vertex_ao(visible);
+                } else {
+                    self.renderer.bake_vertex_ao(AO_SAMPLES);
+                }
+            }
+            VirtualKeyCode::Comma | VirtualKeyCode::Period => {
+                let step = if key == VirtualKeyCode::Period {
+                    OPACITY_STEP
+                } else {
+                    -OPACITY_STEP
+                };
+                let opacity = self.renderer.window_opacity + step;
+                self.renderer.set_window_opacity(opacity);
+            }
+            // both sides with the back ones flagged, or back to culling
+            VirtualKeyCode::F => {
+                if self.renderer.cull_mode.is_some() {
+                    self.renderer.set_cull_mode(None);
+                    self.renderer.set_backface_color(Some(BACKFACE_COLOR));
+                } else {
+                    self.renderer.set_cull_mode(Some(Face::Back));
+                    self.renderer.set_backface_color(None);
+                }
+            }
+            VirtualKeyCode::J => {
+                let visible = !self.renderer.skeleton_visible;
+                self.renderer.show_skeleton(visible);
+            }
+            VirtualKeyCode::H => {
+                let enabled = !self
+                    .renderer
+                    .ground_shadow
+                    .as_ref()
+                    .map_or(false, |ground| ground.enabled);
+                self.renderer
+                    .set_ground_shadow(enabled, GROUND_SHADOW_OPACITY);
+            }
+            VirtualKeyCode::G => {
+                let visible = !self.renderer.grid_visible;
+                self.renderer.show_grid(visible);
+            }
+            VirtualKeyCode::W => {
+                let visible = !self.renderer.wireframe_visible;
+                self.renderer.show_wireframe(visible);
+            }
+            VirtualKeyCode::E => {
+                let visible = !self.renderer.edge_diagnostics_visible;
+                self.renderer.show_edge_diagnostics(visible);
+            }
+            VirtualKeyCode::V => self.renderer.cycle_gltf_camera(),
+            VirtualKeyCode::Y => self.cycle_gltf_scene(),
+            VirtualKeyCode::A => self.toggle_previous_model(),
+            VirtualKeyCode::Z => {
+                let enabled = !self.renderer.opaque_sorting;
+                self.renderer.set_opaque_sorting(enabled);
+            }
+            VirtualKeyCode::T => {
+                let isolate = !self.renderer.isolate_selection;
+                self.renderer.set_isolate_selection(isolate);
+            }
+            VirtualKeyCode::Numpad5 => {
+                let enabled = self.renderer.camera.ortho_height.is_none();
+                self.renderer.camera.set_orthographic(enabled);
+            }
+            VirtualKeyCode::N => {
+                let enabled = !self.renderer.camera.adaptive_near;
+                self.renderer.camera.set_adaptive_near(enabled);
+            }
+            VirtualKeyCode::L => {
+                if self.renderer.is_smoothed() {
+                    self.renderer.restore_mesh();
+                } else {
+                    self.renderer
+                        .smooth_mesh(SMOOTHING_ITERATIONS, SMOOTHING_LAMBDA);
+                }
+            }
+            VirtualKeyCode::D => {
+                if self.renderer.is_smoothed() {
+                    self.renderer.restore_mesh();
+                } else {
+                    self.renderer.subdivide(SUBDIVISION_ITERATIONS);
+                }
+            }
+            VirtualKeyCode::S => {
+                let enabled = !self.renderer.shadow.enabled;
+                self.renderer.set_shadows(enabled, SHADOW_RESOLUTION);
+            }
+            VirtualKeyCode::C => {
+                self.active_clip_plane = (self.active_clip_plane + 1) % MAX_CLIP_PLANES;
+            }
+            VirtualKeyCode::P => {
+                let index = self.active_clip_plane;
+                let plane = match self.renderer.clip_planes[index] {
+                    Some(_) => None,
+                    None => Some(default_clip_plane(index)),
+                };
+                self.renderer.set_clip_plane(index, plane);
+            }
+            VirtualKeyCode::O => self.snap_orbit = !self.snap_orbit,
+            // off, then translating, rotating and scaling
+            VirtualKeyCode::R => {
+                let mode = match self.renderer.gizmo.map(|gizmo| gizmo.mode) {
+                    None => Some(GizmoMode::Translate),
+                    Some(GizmoMode::Translate) => Some(GizmoMode::Rotate),
+                    Some(GizmoMode::Rotate) => Some(GizmoMode::Scale),
+                    Some(GizmoMode::Scale) => None,
+                };
+                self.renderer.set_gizmo(mode);
+            }
+            // shift flips horizontal drags instead of vertical ones
+            VirtualKeyCode::I => {
+                let camera = &mut self.renderer.camera;
+                let (x, y) = (camera.invert_x, camera.invert_y);
+                if self.modifiers.shift() {
+                    camera.set_invert(!x, y);
+                } else {
+                    camera.set_invert(x, !y);
+                }
+            }
+            VirtualKeyCode::Left | VirtualKeyCode::Right => {
+                let steps = if key == VirtualKeyCode::Right { 1 } else { -1 };
+                self.renderer.camera.step_orbit(steps);
+            }
+            VirtualKeyCode::X => self.cycle_slice(),
+            VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+                if let Some(mut slice) = self.renderer.slice {
+                    let step = self.scene_extent(slice.axis).0 * SLICE_STEP;
+                    slice.position += if key == VirtualKeyCode::RBracket {
+                        step
+                    } else {
+                        -step
+                    };
+                    self.renderer.set_slice(Some(slice));
+                }
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::Equals => {
+                if let Some(mut slice) = self.renderer.slice {
+                    if key == VirtualKeyCode::Equals {
+                        slice.thickness *= SLICE_THICKNESS_FACTOR;
+                    } else {
+                        slice.thickness /= SLICE_THICKNESS_FACTOR;
+                    }
+                    self.renderer.set_slice(Some(slice));
+                }
+            }
+            VirtualKeyCode::PageUp | VirtualKeyCode::PageDown => {
+                let index = self.active_clip_plane;
+                if let Some(mut plane) = self.renderer.clip_planes[index] {
+                    let step = if key == VirtualKeyCode::PageUp {
+                        CLIP_PLANE_STEP
+                    } else {
+                        -CLIP_PLANE_STEP
+                    };
+                    plane.translate(step);
+                    self.renderer.set_clip_plane(index, Some(plane));
+                }
+            }
+            _ => {}
         }
     }
 
@@ -36,42 +700,198 @@ impl Application {
         let mut last_frame_inst = Instant::now();
         let (mut frame_count, mut accum_time) = (0, 0.0);
         let event_loop = self.event_loop.take().unwrap(); // avoid the self move problem
-        self.renderer.init_cube();
-        event_loop.run(move |event, _, control_flow| match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit;
+        event_loop.run(move |event, _, control_flow| {
+            if let Some(handler) = self.on_event.as_mut() {
+                if handler(&event, &mut self.renderer) {
+                    return;
+                }
+            }
+            match event {
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } => self.handle_key(key),
+                    WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+                    WindowEvent::DroppedFile(path) => self.load_dropped_file(&path),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let cursor = (position.x as f32, position.y as f32);
+                        let delta = (cursor.0 - self.cursor.0, cursor.1 - self.cursor.1);
+                        self.cursor = cursor;
+                        let camera = &self.renderer.camera;
+                        let (dx, dy) = camera.drag_delta(delta);
+                        let speed = ORBIT_SPEED * camera.orbit_sensitivity;
+                        if let Some((yaw, pitch)) = self.orbit_drag.as_mut() {
+                            *yaw -= Rad(dx * speed);
+                            *pitch += Rad(dy * speed);
+                            let snap = self.snap_orbit || self.modifiers.shift();
+                            self.renderer.camera.set_orbit_angles(*yaw, *pitch, snap);
+                        }
+                        if self.renderer.gizmo_dragging() {
+                            self.renderer.drag_gizmo(cursor, self.modifiers.shift());
+                        }
+                        if self.pan_drag {
+                            let height = self.window.inner_size().height as f32;
+                            self.renderer.camera.pan((dx, dy), height);
+                        }
+                    }
+                    // ctrl click selects the triangle under the cursor instead, or clears it
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if self.modifiers.ctrl() => {
+                        let selection = self.renderer.pick_triangle(self.cursor);
+                        if let Some((object, triangle)) = selection {
+                            let name = &self.renderer.objects[object].name;
+                            log::info!("selected triangle {} of {}", triangle, name);
+                        }
+                        self.renderer.select_triangle(selection);
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        // grabbing a gizmo handle drags it instead of orbiting
+                        self.orbit_drag = match state {
+                            ElementState::Pressed
+                                if self.renderer.begin_gizmo_drag(self.cursor) =>
+                            {
+                                None
+                            }
+                            ElementState::Pressed => Some(self.renderer.camera.orbit_angles()),
+                            ElementState::Released => {
+                                if self.renderer.gizmo_dragging() {
+                                    self.renderer.end_gizmo_drag();
+                                }
+                                None
+                            }
+                        };
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Right,
+                        ..
+                    } => self.pan_drag = state == ElementState::Pressed,
+                    // middle click pivots the view around the point under the cursor
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Middle,
+                        ..
+                    } => {
+                        if let Some(point) = self.renderer.pick(self.cursor) {
+                            self.renderer.set_pivot(point);
+                        }
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y,
+                            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 100.0,
+                        };
+                        let zoom = self.renderer.camera.zoom_sensitivity;
+                        self.renderer.camera.dolly(ZOOM_STEP.powf(lines * zoom));
+                    }
+                    _ => {}
+                },
+                Event::RedrawEventsCleared => {
+                    let target_frametime = Duration::from_secs_f64(1.0 / self.frame_rate);
+                    let time_since_last_frame = last_update_inst.elapsed();
+                    if time_since_last_frame >= target_frametime {
+                        self.window.request_redraw();
+                        last_update_inst = Instant::now();
+                    } else {
+                        *control_flow = ControlFlow::WaitUntil(
+                            Instant::now() + target_frametime - time_since_last_frame,
+                        );
+                    }
                 }
+                Event::RedrawRequested(_) => {
+                    let frame_time = last_frame_inst.elapsed();
+                    accum_time += frame_time.as_secs_f32();
+                    last_frame_inst = Instant::now();
+                    let dt = self
+                        .fixed_timestep
+                        .unwrap_or_else(|| frame_time.min(self.max_frame_delta));
+                    self.renderer.update(dt.as_secs_f32());
+                    // already logged by the renderer, streams have no caller to return them to
+                    for (path, err) in self.renderer.take_load_errors() {
+                        if let Some(handler) = self.load_options.on_error.as_mut() {
+                            handler(&path, &err);
+                        }
+                    }
+                    frame_count += 1;
+                    if frame_count == 100 {
+                        let avg_frame_time = accum_time * 1000.0 / frame_count as f32;
+                        if self.show_stats {
+                            println!("{}\n", self.renderer.stats(avg_frame_time));
+                        } else {
+                            println!("avg frame time {}ms.", avg_frame_time);
+                        }
+                        accum_time = 0.0;
+                        frame_count = 0;
+                    }
+                    Renderer::draw(&self.renderer);
+                }
+                Event::MainEventsCleared => {}
                 _ => {}
-            },
-            Event::RedrawEventsCleared => {
-                let target_frametime = Duration::from_secs_f64(1.0 / self.frame_rate);
-                let time_since_last_frame = last_update_inst.elapsed();
-                if time_since_last_frame >= target_frametime {
-                    self.window.request_redraw();
-                    last_update_inst = Instant::now();
-                } else {
-                    *control_flow = ControlFlow::WaitUntil(
-                        Instant::now() + target_frametime - time_since_last_frame,
-                    );
-                }
-            }
-            Event::RedrawRequested(_) => {
-                accum_time += last_frame_inst.elapsed().as_secs_f32();
-                last_frame_inst = Instant::now();
-                frame_count += 1;
-                if frame_count == 100 {
-                    println!(
-                        "avg frame time {}ms.",
-                        accum_time * 1000.0 / frame_count as f32
-                    );
-                    accum_time = 0.0;
-                    frame_count = 0;
-                }
-                Renderer::draw_cube(&self.renderer);
-            }
-            Event::MainEventsCleared => {}
-            _ => {}
+            }
+        });
+    }
+
+    /*
+     * Draws `frames` frames with vsync off while the camera makes one full turn around
+     * the scene, bobbing up and down once on the way, then prints a BenchmarkReport as
+     * JSON and exits. Input is ignored and updates always get the fixed timestep (or
+     * one frame at the configured rate), so every run renders the same frames.
+     */
+    pub fn run_benchmark(mut self, frames: u32) {
+        let frames = frames.max(1);
+        let dt = self
+            .fixed_timestep
+            .unwrap_or_else(|| Duration::from_secs_f64(1.0 / self.frame_rate))
+            .as_secs_f32();
+        let (start_yaw, start_pitch) = self.renderer.camera.orbit_angles();
+        self.renderer.set_vsync(false);
+        let mut frame_times = Vec::with_capacity(frames as usize);
+        let mut last_frame_inst = Instant::now();
+        let mut frame = 0;
+        let event_loop = self.event_loop.take().unwrap();
+        event_loop.run(move |event, _, control_flow| {
+            // winit polls by default, which is what a benchmark wants
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                Event::MainEventsCleared => self.window.request_redraw(),
+                Event::RedrawRequested(_) => {
+                    let t = frame.saturating_sub(WARMUP_FRAMES) as f32 / frames as f32;
+                    let yaw = start_yaw + Rad(2.0 * PI * t);
+                    let pitch = start_pitch + Rad(BENCHMARK_PITCH_SWING * (2.0 * PI * t).sin());
+                    self.renderer.camera.set_orbit_angles(yaw, pitch, false);
+                    self.renderer.update(dt);
+                    Renderer::draw(&self.renderer);
+                    if frame > WARMUP_FRAMES {
+                        frame_times.push(last_frame_inst.elapsed());
+                    }
+                    last_frame_inst = Instant::now();
+                    frame += 1;
+                    if frame_times.len() == frames as usize {
+                        println!("{}", BenchmarkReport::new(&frame_times).to_json());
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                _ => {}
+            }
         });
     }
 }
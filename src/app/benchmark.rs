@@ -0,0 +1,50 @@
+use serde::Serialize;
+use std::time::Duration;
+
+// frames drawn before timing starts, so pipeline and driver warm up doesn't count
+pub const WARMUP_FRAMES: u32 = 10;
+
+/*
+ * Frame time statistics from Application::run_benchmark, in milliseconds. Percentiles
+ * are nearest rank, so each one is a frame time that was actually measured.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct BenchmarkReport {
+    pub frames: usize,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl BenchmarkReport {
+    pub fn new(frame_times: &[Duration]) -> Self {
+        let mut ms: Vec<f64> = frame_times
+            .iter()
+            .map(|time| time.as_secs_f64() * 1000.0)
+            .collect();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| match ms.len() {
+            0 => 0.0,
+            n => ms[((p * n as f64).ceil() as usize).max(1) - 1],
+        };
+        let avg_ms = if ms.is_empty() {
+            0.0
+        } else {
+            ms.iter().sum::<f64>() / ms.len() as f64
+        };
+        Self {
+            frames: ms.len(),
+            min_ms: ms.first().copied().unwrap_or_default(),
+            avg_ms,
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: ms.last().copied().unwrap_or_default(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use winit::event::Event;
+
+use super::Application;
+use crate::{
+    loader::{CoordinateSystem, LoadError, LoadProgress},
+    render::{Axis, Preset, Renderer, DEFAULT_ADAPTER_ATTEMPTS, DEFAULT_MAX_FRAMES_IN_FLIGHT},
+};
+
+// returning true marks the event as consumed, skipping the viewer's own handling
+pub(super) type EventHandler = Box<dyn FnMut(&Event<'_, ()>, &mut Renderer) -> bool>;
+pub(super) type LoadProgressHandler = Box<dyn FnMut(&Path, LoadProgress)>;
+pub(super) type LoadErrorHandler = Box<dyn FnMut(&Path, &LoadError)>;
+
+pub struct ApplicationBuilder {
+    pub(super) title: String,
+    pub(super) frame_rate: f64,
+    pub(super) fixed_timestep: Option<Duration>,
+    pub(super) files: Vec<PathBuf>,
+    pub(super) max_frame_delta: Duration,
+    pub(super) on_event: Option<EventHandler>,
+    pub(super) on_load_progress: Option<LoadProgressHandler>,
+    pub(super) on_load_error: Option<LoadErrorHandler>,
+    pub(super) transparent: bool,
+    pub(super) decorations: bool,
+    pub(super) adapter_attempts: u32,
+    pub(super) deterministic_geometry: bool,
+    pub(super) max_frames_in_flight: u32,
+    pub(super) preset: Option<Preset>,
+    pub(super) coordinate_system: Option<CoordinateSystem>,
+    pub(super) optimize_mesh: bool,
+    pub(super) repair_winding: bool,
+    pub(super) up_axis: Option<Axis>,
+    pub(super) memory_budget: Option<u64>,
+    pub(super) stream_threshold: Option<u64>,
+    pub(super) sensitivity: (f32, f32, f32),
+}
+
+// OBJ files this big come in through Renderer::load_streaming unless set otherwise
+const DEFAULT_STREAM_THRESHOLD: u64 = 256 << 20;
+
+impl ApplicationBuilder {
+    pub fn new() -> Self {
+        Self {
+            title: String::from("Model Viewer"),
+            frame_rate: 60.0,
+            fixed_timestep: None,
+            files: Vec::new(),
+            max_frame_delta: Duration::from_millis(100),
+            on_event: None,
+            on_load_progress: None,
+            on_load_error: None,
+            transparent: false,
+            decorations: true,
+            adapter_attempts: DEFAULT_ADAPTER_ATTEMPTS,
+            deterministic_geometry: false,
+            max_frames_in_flight: DEFAULT_MAX_FRAMES_IN_FLIGHT,
+            preset: None,
+            coordinate_system: None,
+            optimize_mesh: false,
+            repair_winding: false,
+            up_axis: None,
+            memory_budget: None,
+            stream_threshold: Some(DEFAULT_STREAM_THRESHOLD),
+            sensitivity: (1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    pub fn frame_rate(mut self, frame_rate: f64) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    // advance updates by exactly `dt` every frame instead of the measured frame time,
+    // so the same number of frames always produces the same images
+    pub fn fixed_timestep(mut self, dt: Duration) -> Self {
+        self.fixed_timestep = Some(dt);
+        self
+    }
+
+    // upper bound on the measured delta fed to updates, so a stall doesn't cause a jump
+    pub fn max_frame_delta(mut self, max: Duration) -> Self {
+        self.max_frame_delta = max;
+        self
+    }
+
+    // models to load into the scene at startup, each as its own object
+    pub fn files<I: IntoIterator<Item = PathBuf>>(mut self, files: I) -> Self {
+        self.files.extend(files);
+        self
+    }
+
+    // clears to transparent black so the model can be composited over other windows
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    // how many times to ask for a graphics adapter before giving up
+    pub fn adapter_attempts(mut self, attempts: u32) -> Self {
+        self.adapter_attempts = attempts;
+        self
+    }
+
+    // sorts loaded geometry so the same file always produces identical buffers and exports
+    pub fn deterministic_geometry(mut self, enabled: bool) -> Self {
+        self.deterministic_geometry = enabled;
+        self
+    }
+
+    // fewer frames queued up means less input lag, at the cost of some GPU idle time
+    pub fn max_frames_in_flight(mut self, max: u32) -> Self {
+        self.max_frames_in_flight = max;
+        self
+    }
+
+    // applied once the files are loaded, over any view preset saved next to them
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    // what the files are in, for when a loader's guess is wrong. They're always converted
+    // into CoordinateSystem::CANONICAL
+    pub fn coordinate_system(mut self, system: CoordinateSystem) -> Self {
+        self.coordinate_system = Some(system);
+        self
+    }
+
+    // reorders the loaded triangles for the vertex cache, logging the ACMR before and after
+    pub fn optimize_mesh(mut self, enabled: bool) -> Self {
+        self.optimize_mesh = enabled;
+        self
+    }
+
+    // files that would take more than `bytes` of GPU memory fail to load, see
+    // Renderer::check_memory
+    pub fn memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    // multipliers on how fast drags orbit and pan and how far scrolling zooms, 1.0 each by
+    // default. Negative ones are clamped to 0, see Camera::set_sensitivity
+    pub fn sensitivity(mut self, orbit: f32, pan: f32, zoom: f32) -> Self {
+        self.sensitivity = (orbit, pan, zoom);
+        self
+    }
+
+    /*
+     * Files of at least `bytes` that the loader can hand over in chunks are streamed in,
+     * showing up progressively instead of holding the window up until they're parsed.
+     * None always loads files whole. See Renderer::load_streaming.
+     */
+    pub fn stream_threshold(mut self, bytes: Option<u64>) -> Self {
+        self.stream_threshold = bytes;
+        self
+    }
+
+    // makes the loaded triangles wind consistently with their neighbours, logging how many
+    // had to be flipped
+    pub fn repair_winding(mut self, enabled: bool) -> Self {
+        self.repair_winding = enabled;
+        self
+    }
+
+    // the axis the camera orbits around and keeps up, Z unless set. see Renderer::set_up_axis
+    pub fn up_axis(mut self, axis: Axis) -> Self {
+        self.up_axis = Some(axis);
+        self
+    }
+
+    // sees every winit event before the viewer does
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&Event<'_, ()>, &mut Renderer) -> bool + 'static,
+    {
+        self.on_event = Some(Box::new(handler));
+        self
+    }
+
+    // told how far along each file the viewer loads is, the startup files and dropped ones
+    pub fn on_load_progress<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&Path, LoadProgress) + 'static,
+    {
+        self.on_load_progress = Some(Box::new(handler));
+        self
+    }
+
+    /*
+     * Told about every file that fails to load: startup files, dropped ones, glTF scene
+     * switches and files streamed through Renderer::load_streaming. They're logged either way.
+     */
+    pub fn on_load_error<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&Path, &LoadError) + 'static,
+    {
+        self.on_load_error = Some(Box::new(handler));
+        self
+    }
+
+    pub fn build(self) -> Application {
+        Application::from_builder(self)
+    }
+}
+
+impl Default for ApplicationBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
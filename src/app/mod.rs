@@ -1,2 +1,6 @@
 mod application;
+mod benchmark;
+mod builder;
 pub use application::Application;
+pub use benchmark::BenchmarkReport;
+pub use builder::ApplicationBuilder;
@@ -0,0 +1,36 @@
+[[block]]
+struct Params {
+    // screen uv to image uv around the centre, see Background::uv_scale
+    uv_scale: vec2<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> r_params: Params;
+[[group(0), binding(1)]]
+var t_background: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_background: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// one triangle covering the screen, on the far plane so everything draws over it
+[[stage(vertex)]]
+fn vs_background([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(corner * 2.0 - vec2<f32>(1.0, 1.0), 1.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_background(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let uv = (in.uv - vec2<f32>(0.5, 0.5)) * r_params.uv_scale + vec2<f32>(0.5, 0.5);
+    // letterboxing leaves the clear colour around the image
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        discard;
+    }
+    return textureSample(t_background, s_background, uv);
+}
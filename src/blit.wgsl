@@ -0,0 +1,25 @@
+[[group(0), binding(0)]]
+var t_frame: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_frame: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// one triangle covering the viewport, which the letterboxing sets
+[[stage(vertex)]]
+fn vs_blit([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - vec2<f32>(1.0, 1.0), 0.0, 1.0);
+    // textures start at the top, clip space at the bottom
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_blit(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_frame, s_frame, in.uv);
+}
@@ -0,0 +1,61 @@
+[[block]]
+struct Params {
+    // average luminance the exposure brings the scene to
+    key: f32;
+    // per second, see AutoExposure::speed
+    speed: f32;
+    dt: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> r_params: Params;
+
+[[block]]
+struct State {
+    exposure: f32;
+};
+[[group(0), binding(1)]]
+var<storage, read_write> r_state: State;
+
+[[group(0), binding(2)]]
+var t_hdr: texture_2d<f32>;
+
+// the frame is measured on a GRID x GRID lattice of texels, 4 x 4 per invocation
+let GRID: u32 = 64u;
+let MIN_EXPOSURE: f32 = 0.01;
+let MAX_EXPOSURE: f32 = 100.0;
+
+var<workgroup> sums: array<f32, 256>;
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn cs_exposure(
+    [[builtin(local_invocation_id)]] id: vec3<u32>,
+    [[builtin(local_invocation_index)]] index: u32,
+) {
+    let size = vec2<f32>(textureDimensions(t_hdr));
+    var sum: f32 = 0.0;
+    for (var y: u32 = 0u; y < 4u; y = y + 1u) {
+        for (var x: u32 = 0u; x < 4u; x = x + 1u) {
+            let cell = vec2<f32>(f32(id.x * 4u + x), f32(id.y * 4u + y)) + vec2<f32>(0.5, 0.5);
+            let texel = vec2<i32>(cell / f32(GRID) * size);
+            let color = textureLoad(t_hdr, texel, 0).rgb;
+            let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+            // log average, so a few bright highlights don't drag the whole frame down
+            sum = sum + log(max(luminance, 0.0001));
+        }
+    }
+    sums[index] = sum;
+    workgroupBarrier();
+    for (var stride: u32 = 128u; stride > 0u; stride = stride >> 1u) {
+        if (index < stride) {
+            sums[index] = sums[index] + sums[index + stride];
+        }
+        workgroupBarrier();
+    }
+    if (index == 0u) {
+        let average = exp(sums[0] / f32(GRID * GRID));
+        let wanted = clamp(r_params.key / average, MIN_EXPOSURE, MAX_EXPOSURE);
+        // frame rate independent easing towards the wanted exposure
+        let blend = 1.0 - exp(-r_params.dt * r_params.speed);
+        r_state.exposure = mix(r_state.exposure, wanted, blend);
+    }
+}
@@ -0,0 +1,83 @@
+[[block]]
+struct Grid {
+    transform: mat4x4<f32>;
+    // clip space back to the grid's, for the rays through each pixel
+    inverse_transform: mat4x4<f32>;
+    eye: vec4<f32>;
+    color: vec4<f32>;
+    // x: ground height, y: fade distance, z: spacing of the finer lines, w: how far they've faded
+    params: vec4<f32>;
+    // x: 1 to draw the axes in their gizmo colours
+    flags: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> r_grid: Grid;
+
+struct GridOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+struct GridFragment {
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(frag_depth)]] depth: f32;
+};
+
+// one triangle covering the frame
+[[stage(vertex)]]
+fn vs_grid([[builtin(vertex_index)]] vertex_index: u32) -> GridOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: GridOutput;
+    out.ndc = uv * 2.0 - vec2<f32>(1.0, 1.0);
+    out.position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+// 1 on a line every `spacing`, falling off over about a pixel either side
+fn grid_lines(coord: vec2<f32>, spacing: f32) -> f32 {
+    let cell = coord / spacing;
+    let lines = abs(fract(cell - vec2<f32>(0.5, 0.5)) - vec2<f32>(0.5, 0.5)) / fwidth(cell);
+    return 1.0 - min(min(lines.x, lines.y), 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_grid(in: GridOutput) -> GridFragment {
+    let near = r_grid.inverse_transform * vec4<f32>(in.ndc, 0.0, 1.0);
+    let far = r_grid.inverse_transform * vec4<f32>(in.ndc, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+    let direction = far.xyz / far.w - origin;
+    let ground = r_grid.params.x;
+    // rays along the ground never meet it
+    let dz = select(direction.z, 1e-6, abs(direction.z) < 1e-6);
+    let hit = origin + direction * ((ground - origin.z) / dz);
+    let clip = r_grid.transform * vec4<f32>(hit, 1.0);
+
+    // derivatives have to be taken before any fragment in the quad can discard
+    let spacing = r_grid.params.z;
+    let fine = grid_lines(hit.xy, spacing) * (1.0 - r_grid.params.w);
+    let coarse = grid_lines(hit.xy, spacing * 10.0);
+    let axes = vec2<f32>(1.0, 1.0) - min(abs(hit.yx) / fwidth(hit.yx), vec2<f32>(1.0, 1.0));
+
+    var color = r_grid.color.rgb;
+    var line = max(fine, coarse);
+    // the gizmo's colours, the x axis runs along y = 0
+    if (r_grid.flags.x > 0.5) {
+        color = mix(color, vec3<f32>(0.2, 1.0, 0.2), axes.y);
+        color = mix(color, vec3<f32>(1.0, 0.2, 0.2), axes.x);
+        line = max(line, max(axes.x, axes.y));
+    }
+    // further away fades out, as does ground seen edge on where the lines would crowd
+    let distance = length(hit - r_grid.eye.xyz);
+    let fade = 1.0 - smoothstep(0.0, r_grid.params.y, distance);
+    let facing = smoothstep(0.0, 0.1, abs(normalize(direction).z));
+    let alpha = r_grid.color.a * line * fade * facing;
+
+    let depth = clip.z / clip.w;
+    if (clip.w <= 0.0 || depth < 0.0 || depth > 1.0 || alpha <= 0.0) {
+        discard;
+    }
+    var out: GridFragment;
+    out.color = vec4<f32>(color, alpha);
+    out.depth = depth;
+    return out;
+}
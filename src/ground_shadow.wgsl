@@ -0,0 +1,60 @@
+[[block]]
+struct GroundShadow {
+    transform: mat4x4<f32>;
+    // model space flattened onto the ground along the light, then into the mask
+    mask_transform: mat4x4<f32>;
+    // xy: min corner, zw: max corner of the patch of ground the mask covers
+    rect: vec4<f32>;
+    // x: ground height, y: opacity, z: mask texel size, w: blur radius in texels
+    params: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> r_ground: GroundShadow;
+[[group(0), binding(1)]]
+var t_mask: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_mask: sampler;
+
+[[stage(vertex)]]
+fn vs_mask([[location(0)]] position: vec3<f32>) -> [[builtin(position)]] vec4<f32> {
+    return r_ground.mask_transform * vec4<f32>(position, 1.0);
+}
+
+// overlapping triangles just write 1 again, so the shadow never gets darker than opacity
+[[stage(fragment)]]
+fn fs_mask() -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+}
+
+struct GroundOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// two triangles covering rect
+[[stage(vertex)]]
+fn vs_ground([[builtin(vertex_index)]] vertex_index: u32) -> GroundOutput {
+    let i = vertex_index;
+    let u = select(0.0, 1.0, i == 1u || i == 2u || i == 4u);
+    let v = select(0.0, 1.0, i == 2u || i == 4u || i == 5u);
+    let rect = r_ground.rect;
+    let position = vec3<f32>(mix(rect.x, rect.z, u), mix(rect.y, rect.w, v), r_ground.params.x);
+    var out: GroundOutput;
+    out.position = r_ground.transform * vec4<f32>(position, 1.0);
+    // the mask's rows run top down
+    out.uv = vec2<f32>(u, 1.0 - v);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_ground(in: GroundOutput) -> [[location(0)]] vec4<f32> {
+    let step = r_ground.params.z * r_ground.params.w * 0.5;
+    var coverage = 0.0;
+    for (var x: i32 = -2; x <= 2; x = x + 1) {
+        for (var y: i32 = -2; y <= 2; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * step;
+            coverage = coverage + textureSampleLevel(t_mask, s_mask, in.uv + offset, 0.0).r;
+        }
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, r_ground.params.y * coverage / 25.0);
+}
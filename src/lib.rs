@@ -0,0 +1,21 @@
+mod app;
+mod loader;
+mod render;
+pub use app::{Application, ApplicationBuilder, BenchmarkReport};
+pub use loader::{
+    load, load_gltf_scene, load_streaming, load_with_progress, streams_in_chunks, Conversion,
+    CoordinateSystem, Handedness, LoadError, LoadPhase, LoadProgress, LoadedModel,
+};
+pub use render::{
+    Aabb, Accumulation, AutoExposure, Axis, Background, BackgroundFit, BufferDump, Camera,
+    CameraDef, CameraProjection, CameraState, Capabilities, ColorRamp, ColorSpace, CustomUniforms,
+    DiffReport, DisplayMode, EdgeDiagnostics, FrameLimiter, FramePass, Gizmo, GizmoMode, GpuTimer,
+    GpuTimings, GroundShadow, HdrTarget, InfiniteGrid, Joint, KeptScenes, LineData, LineStyle,
+    LineVertex, Lines, MaterialChannel, MaterialFactors, Mesh, MeshData, Morph, NormalSpace,
+    OpacityPass, OutputTarget, Plane, PostContext, PostProcess, Preset, RenderSetup, Renderer,
+    RendererInitError, SceneInfo, SceneObject, ShadowMap, SharedEdge, Skeleton, Slice,
+    StashedScene, Stats, Topology, Vertex, ViewPreset, CLAY_MATERIAL, CUSTOM_UNIFORM_GROUP,
+    DEFAULT_ADAPTER_ATTEMPTS, DEFAULT_CREASE_ANGLE, DEFAULT_EXPOSURE_KEY, DEFAULT_FLAT_COLOR,
+    DEFAULT_MAX_FRAMES_IN_FLIGHT, DEFAULT_TIMEOUT_WARNING_THRESHOLD, HDR_FORMAT, MAX_CLIP_PLANES,
+    MAX_MORPH_TARGETS, MIN_WINDOW_OPACITY, RESTART_INDEX,
+};
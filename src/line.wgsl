@@ -0,0 +1,56 @@
+// leading fields of Locals in shader.wgsl, the rest of the buffer is ignored
+[[block]]
+struct Locals {
+    transform: mat4x4<f32>;
+    view: mat4x4<f32>;
+    clip_planes: array<vec4<f32>, 4>;
+    display_mode: u32;
+    time: f32;
+    color_space: u32;
+    surface_srgb: u32;
+    light_view_proj: mat4x4<f32>;
+    light_direction: vec4<f32>;
+    shadow_params: vec4<f32>;
+    color_ramp: u32;
+    slice_half_width: f32;
+    normal_space: u32;
+    vertex_ao: u32;
+    slice: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> r_locals: Locals;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_line(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = r_locals.transform * vec4<f32>(position, 1.0);
+    out.world_position = position;
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_line(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    for (var i: i32 = 0; i < 4; i = i + 1) {
+        let plane = r_locals.clip_planes[i];
+        if (dot(plane.xyz, in.world_position) > plane.w) {
+            discard;
+        }
+    }
+    if (r_locals.slice_half_width >= 0.0) {
+        let offset = dot(r_locals.slice.xyz, in.world_position) - r_locals.slice.w;
+        if (abs(offset) > r_locals.slice_half_width) {
+            discard;
+        }
+    }
+    return in.color;
+}
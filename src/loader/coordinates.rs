@@ -0,0 +1,96 @@
+use cgmath::{EuclideanSpace, Matrix, Matrix3, Point3, Vector3};
+
+use super::LoadedModel;
+use crate::render::Axis;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/*
+ * The conventions a file's coordinates follow. The renderer works in CANONICAL: Z up,
+ * right handed, the front of a model facing -Y towards the default camera. Loaders
+ * say what their format usually uses and `LoadedModel::convert_to` rotates and, for
+ * left handed sources, mirrors everything into it.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoordinateSystem {
+    pub up: Axis,
+    pub handedness: Handedness,
+}
+
+impl CoordinateSystem {
+    pub const CANONICAL: CoordinateSystem = CoordinateSystem::z_up();
+
+    // glTF, FBX, most DCC tools
+    pub const fn y_up() -> Self {
+        Self {
+            up: Axis::Y,
+            handedness: Handedness::Right,
+        }
+    }
+
+    // CAD and 3D printing, and what the viewer itself uses
+    pub const fn z_up() -> Self {
+        Self {
+            up: Axis::Z,
+            handedness: Handedness::Right,
+        }
+    }
+
+    // takes coordinates in this system to CANONICAL, always orthogonal
+    fn to_canonical(self) -> Matrix3<f32> {
+        let (x, y, z) = (Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z());
+        // the columns are where each source axis ends up
+        let (from_x, mut from_y, mut from_z) = match self.up {
+            Axis::X => (z, x, y),
+            // +Z, which faces the viewer in Y up files, goes to -Y
+            Axis::Y => (x, z, -y),
+            Axis::Z => (x, y, z),
+        };
+        // left handed is right handed with the axis towards the viewer flipped
+        if self.handedness == Handedness::Left {
+            match self.up {
+                Axis::Z => from_y = -from_y,
+                Axis::X | Axis::Y => from_z = -from_z,
+            }
+        }
+        Matrix3::from_cols(from_x, from_y, from_z)
+    }
+
+    // from this system into `target`
+    pub fn basis_to(self, target: CoordinateSystem) -> Matrix3<f32> {
+        target.to_canonical().transpose() * self.to_canonical()
+    }
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        CoordinateSystem::CANONICAL
+    }
+}
+
+impl LoadedModel {
+    // moves everything out of `coordinate_system` into `target`, a no-op when they match
+    pub fn convert_to(&mut self, target: CoordinateSystem) {
+        if self.coordinate_system == target {
+            return;
+        }
+        let basis = self.coordinate_system.basis_to(target);
+        self.mesh.change_basis(basis);
+        self.strips.change_basis(basis);
+        self.points.change_basis(basis);
+        self.lines.change_basis(basis);
+        for camera in self.cameras.iter_mut() {
+            camera.eye = Point3::from_vec(basis * camera.eye.to_vec());
+            camera.forward = basis * camera.forward;
+            camera.up = basis * camera.up;
+        }
+        for skeleton in self.skeletons.iter_mut() {
+            skeleton.change_basis(basis);
+        }
+        self.coordinate_system = target;
+    }
+}
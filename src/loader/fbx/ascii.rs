@@ -0,0 +1,167 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use super::{Node, Property};
+use crate::loader::LoadError;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    // an identifier followed by ':'
+    Name(String),
+    Str(String),
+    // a bare value, usually a number
+    Word(String),
+    // `*N`, the element count announcing an array
+    Count,
+    Comma,
+    Open,
+    Close,
+    Newline,
+}
+
+fn error(line: usize, message: &str) -> LoadError {
+    LoadError::Parse(format!("ASCII FBX line {}: {}", line, message))
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, LoadError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                tokens.push((Token::Newline, line));
+                line += 1;
+            }
+            ';' => {
+                while chars.peek().map_or(false, |&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => return Err(error(line, "unterminated string")),
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push((Token::Str(value), line));
+            }
+            ',' => tokens.push((Token::Comma, line)),
+            '{' => tokens.push((Token::Open, line)),
+            '}' => tokens.push((Token::Close, line)),
+            '*' => {
+                while chars.peek().map_or(false, char::is_ascii_digit) {
+                    chars.next();
+                }
+                tokens.push((Token::Count, line));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || ",{}:\";".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                if chars.peek() == Some(&':') {
+                    chars.next();
+                    tokens.push((Token::Name(word), line));
+                } else {
+                    tokens.push((Token::Word(word), line));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_word(word: &str) -> Property {
+    if let Ok(value) = word.parse::<i64>() {
+        return Property::Int(value);
+    }
+    match word.parse::<f64>() {
+        Ok(value) => Property::Float(value),
+        Err(_) => Property::String(word.to_owned()),
+    }
+}
+
+type Tokens = Peekable<IntoIter<(Token, usize)>>;
+
+pub fn parse(text: &str) -> Result<Vec<Node>, LoadError> {
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    parse_nodes(&mut tokens, false)
+}
+
+fn parse_nodes(tokens: &mut Tokens, nested: bool) -> Result<Vec<Node>, LoadError> {
+    let mut nodes = Vec::new();
+    while let Some((token, line)) = tokens.next() {
+        match token {
+            Token::Name(name) => nodes.push(parse_node(name, tokens)?),
+            Token::Close if nested => return Ok(nodes),
+            Token::Newline => {}
+            other => return Err(error(line, &format!("unexpected {:?}", other))),
+        }
+    }
+    if nested {
+        return Err(LoadError::Parse(
+            "ASCII FBX: unexpected end of file".to_owned(),
+        ));
+    }
+    Ok(nodes)
+}
+
+fn parse_node(name: String, tokens: &mut Tokens) -> Result<Node, LoadError> {
+    let mut properties = Vec::new();
+    let mut children = Vec::new();
+    loop {
+        match tokens.peek() {
+            None => break,
+            Some((Token::Newline, _)) => {
+                tokens.next();
+                break;
+            }
+            // `a: 1,2,3 }` closes the parent on the same line
+            Some((Token::Close, _)) => break,
+            _ => {}
+        }
+        let (token, line) = tokens.next().unwrap();
+        match token {
+            Token::Str(value) => properties.push(Property::String(value)),
+            Token::Word(word) => properties.push(parse_word(&word)),
+            Token::Count => {}
+            // long value lists wrap onto the next line after a comma
+            Token::Comma => {
+                while let Some((Token::Newline, _)) = tokens.peek() {
+                    tokens.next();
+                }
+            }
+            Token::Open => {
+                children = parse_nodes(tokens, true)?;
+                break;
+            }
+            other => return Err(error(line, &format!("unexpected {:?}", other))),
+        }
+    }
+    // arrays are written as `Name: *N { a: values }`, flatten them into one property
+    if properties.is_empty() && children.len() == 1 && children[0].name == "a" {
+        let values = children.pop().unwrap().properties;
+        properties.push(into_array(values));
+    }
+    Ok(Node {
+        name,
+        properties,
+        children,
+    })
+}
+
+fn into_array(values: Vec<Property>) -> Property {
+    if values.iter().all(|value| matches!(value, Property::Int(_))) {
+        Property::IntArray(values.iter().filter_map(Property::as_i64).collect())
+    } else {
+        Property::FloatArray(values.iter().filter_map(Property::as_f64).collect())
+    }
+}
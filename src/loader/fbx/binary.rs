@@ -0,0 +1,227 @@
+use std::{borrow::Cow, convert::TryInto};
+
+use super::{Node, Property};
+use crate::loader::LoadError;
+
+pub const MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+// magic, two unknown bytes, then the version
+const HEADER_SIZE: usize = 27;
+
+fn error(message: &str) -> LoadError {
+    LoadError::Parse(format!("binary FBX: {}", message))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| error("unexpected end of file"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, LoadError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // 7.5 widened the record header fields to 64 bits
+    fn offset(&mut self, wide: bool) -> Result<u64, LoadError> {
+        if wide {
+            self.u64()
+        } else {
+            self.u32().map(u64::from)
+        }
+    }
+}
+
+pub fn version(bytes: &[u8]) -> Option<u32> {
+    bytes
+        .get(HEADER_SIZE - 4..HEADER_SIZE)
+        .map(|version| u32::from_le_bytes(version.try_into().unwrap()))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Vec<Node>, LoadError> {
+    let wide = version(bytes).ok_or_else(|| error("truncated header"))? >= 7500;
+    let mut reader = Reader {
+        bytes,
+        pos: HEADER_SIZE,
+    };
+    let mut nodes = Vec::new();
+    while reader.pos < bytes.len() {
+        match read_node(&mut reader, wide)? {
+            Some(node) => nodes.push(node),
+            None => break,
+        }
+    }
+    Ok(nodes)
+}
+
+// None for the all-zero record that terminates a list of nodes
+fn read_node(reader: &mut Reader, wide: bool) -> Result<Option<Node>, LoadError> {
+    let end = reader.offset(wide)? as usize;
+    let property_count = reader.offset(wide)?;
+    let _property_list_len = reader.offset(wide)?;
+    let name_len = reader.u8()? as usize;
+    if end == 0 {
+        return Ok(None);
+    }
+    if end > reader.bytes.len() || end < reader.pos {
+        return Err(error("node record points outside the file"));
+    }
+    let name = String::from_utf8_lossy(reader.take(name_len)?).into_owned();
+    let properties = (0..property_count)
+        .map(|_| read_property(reader))
+        .collect::<Result<_, _>>()?;
+    let mut children = Vec::new();
+    while reader.pos < end {
+        match read_node(reader, wide)? {
+            Some(child) => children.push(child),
+            None => break,
+        }
+    }
+    reader.pos = end;
+    Ok(Some(Node {
+        name,
+        properties,
+        children,
+    }))
+}
+
+fn read_property(reader: &mut Reader) -> Result<Property, LoadError> {
+    let ty = reader.u8()?;
+    Ok(match ty {
+        b'C' => Property::Int(reader.u8()? as i64),
+        b'Y' => Property::Int(i16::from_le_bytes(reader.take(2)?.try_into().unwrap()) as i64),
+        b'I' => Property::Int(reader.u32()? as i32 as i64),
+        b'L' => Property::Int(reader.u64()? as i64),
+        b'F' => Property::Float(f32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as f64),
+        b'D' => Property::Float(f64::from_le_bytes(reader.take(8)?.try_into().unwrap())),
+        b'S' | b'R' => {
+            let len = reader.u32()? as usize;
+            let bytes = reader.take(len)?;
+            // raw binary blobs aren't needed for geometry
+            if ty == b'S' {
+                Property::String(String::from_utf8_lossy(bytes).into_owned())
+            } else {
+                Property::String(String::new())
+            }
+        }
+        b'f' | b'd' | b'i' | b'l' | b'b' => read_array(reader, ty)?,
+        _ => return Err(error(&format!("unknown property type '{}'", ty as char))),
+    })
+}
+
+fn read_array(reader: &mut Reader, ty: u8) -> Result<Property, LoadError> {
+    let len = reader.u32()? as usize;
+    let encoding = reader.u32()?;
+    let stored_len = reader.u32()? as usize;
+    let stored = reader.take(stored_len)?;
+    let data: Cow<[u8]> = match encoding {
+        0 => Cow::Borrowed(stored),
+        1 => Cow::Owned(
+            miniz_oxide::inflate::decompress_to_vec_zlib(stored)
+                .map_err(|_| error("corrupt compressed array"))?,
+        ),
+        _ => return Err(error(&format!("unknown array encoding {}", encoding))),
+    };
+    let element_size = match ty {
+        b'd' | b'l' => 8,
+        b'f' | b'i' => 4,
+        _ => 1,
+    };
+    let data = data
+        .get(..len * element_size)
+        .ok_or_else(|| error("array shorter than its declared length"))?;
+    let elements = data.chunks_exact(element_size);
+    Ok(match ty {
+        b'd' => Property::FloatArray(
+            elements
+                .map(|e| f64::from_le_bytes(e.try_into().unwrap()))
+                .collect(),
+        ),
+        b'f' => Property::FloatArray(
+            elements
+                .map(|e| f32::from_le_bytes(e.try_into().unwrap()) as f64)
+                .collect(),
+        ),
+        b'l' => Property::IntArray(
+            elements
+                .map(|e| i64::from_le_bytes(e.try_into().unwrap()))
+                .collect(),
+        ),
+        b'i' => Property::IntArray(
+            elements
+                .map(|e| i32::from_le_bytes(e.try_into().unwrap()) as i64)
+                .collect(),
+        ),
+        _ => Property::IntArray(elements.map(|e| e[0] as i64).collect()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 7.4 file holding one node with an int and an uncompressed int array
+    fn file() -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0x1a, 0x00]);
+        bytes.extend_from_slice(&7400u32.to_le_bytes());
+        let mut properties = vec![b'I'];
+        properties.extend_from_slice(&42i32.to_le_bytes());
+        properties.push(b'i');
+        for value in [3u32, 0, 12].iter() {
+            properties.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [1i32, -2, 3].iter() {
+            properties.extend_from_slice(&value.to_le_bytes());
+        }
+        let name = b"Node";
+        let end = bytes.len() + 13 + name.len() + properties.len();
+        bytes.extend_from_slice(&(end as u32).to_le_bytes());
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&(properties.len() as u32).to_le_bytes());
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name);
+        bytes.extend_from_slice(&properties);
+        // the null record closing the top level
+        bytes.extend_from_slice(&[0; 13]);
+        bytes
+    }
+
+    #[test]
+    fn reads_nodes_and_properties() {
+        let bytes = file();
+        assert_eq!(version(&bytes), Some(7400));
+        let nodes = parse(&bytes).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].name, "Node");
+        assert_eq!(nodes[0].id(), Some(42));
+        assert_eq!(
+            nodes[0].value(1).and_then(Property::i64s),
+            Some(&[1, -2, 3][..])
+        );
+    }
+
+    #[test]
+    fn truncated_files_are_an_error() {
+        let bytes = file();
+        assert!(parse(&bytes[..bytes.len() - 20]).is_err());
+    }
+}
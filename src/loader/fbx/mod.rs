@@ -0,0 +1,471 @@
+mod ascii;
+mod binary;
+
+use cgmath::{Deg, InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fs,
+    path::Path,
+};
+
+use super::LoadError;
+use crate::render::{MeshData, Vertex};
+
+// anything older stores geometry in a different layout
+const MIN_VERSION: i64 = 7000;
+// guards the parent walk against cyclic connections
+const MAX_DEPTH: usize = 64;
+
+/*
+ * Both encodings describe the same tree: named nodes holding a list of values and
+ * child nodes. Everything past parsing only looks at this tree.
+ */
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug)]
+pub enum Property {
+    Int(i64),
+    Float(f64),
+    String(String),
+    IntArray(Vec<i64>),
+    FloatArray(Vec<f64>),
+}
+
+impl Property {
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Property::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Property::Int(value) => Some(*value as f64),
+            Property::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Property::String(value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    fn f64s(&self) -> Option<Cow<[f64]>> {
+        match self {
+            Property::FloatArray(values) => Some(Cow::Borrowed(values.as_slice())),
+            Property::IntArray(values) => Some(values.iter().map(|&v| v as f64).collect()),
+            _ => None,
+        }
+    }
+
+    fn i64s(&self) -> Option<&[i64]> {
+        match self {
+            Property::IntArray(values) => Some(values.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn value(&self, index: usize) -> Option<&Property> {
+        self.properties.get(index)
+    }
+
+    fn id(&self) -> Option<i64> {
+        self.value(0).and_then(Property::as_i64)
+    }
+}
+
+pub fn load(path: &Path) -> Result<MeshData, LoadError> {
+    let bytes = fs::read(path)?;
+    let nodes = if bytes.starts_with(binary::MAGIC) {
+        let version = binary::version(&bytes).unwrap_or(0) as i64;
+        check_version(version)?;
+        binary::parse(&bytes)?
+    } else {
+        let nodes = ascii::parse(&String::from_utf8_lossy(&bytes))?;
+        let version = nodes
+            .iter()
+            .find(|node| node.name == "FBXHeaderExtension")
+            .and_then(|header| header.child("FBXVersion"))
+            .and_then(|version| version.value(0))
+            .and_then(Property::as_i64);
+        if let Some(version) = version {
+            check_version(version)?;
+        }
+        nodes
+    };
+    build(&nodes)
+}
+
+fn check_version(version: i64) -> Result<(), LoadError> {
+    if version < MIN_VERSION {
+        return Err(LoadError::Parse(format!(
+            "FBX version {}.{} isn't supported, only 7.0 and newer",
+            version / 1000,
+            version % 1000 / 100
+        )));
+    }
+    Ok(())
+}
+
+/*
+ * Geometry nodes are attached to Model nodes, which carry the transform and are
+ * parented to each other, all through the Connections section. A geometry used by
+ * several models is emitted once per model.
+ */
+fn build(nodes: &[Node]) -> Result<MeshData, LoadError> {
+    let objects = nodes
+        .iter()
+        .find(|node| node.name == "Objects")
+        .ok_or_else(|| LoadError::Parse("FBX file has no Objects section".to_owned()))?;
+    let models: HashMap<i64, &Node> = objects
+        .children_named("Model")
+        .filter_map(|model| Some((model.id()?, model)))
+        .collect();
+    let mut parents = HashMap::new();
+    let mut users: HashMap<i64, Vec<i64>> = HashMap::new();
+    let connections = nodes.iter().find(|node| node.name == "Connections");
+    for connection in connections.iter().flat_map(|node| node.children_named("C")) {
+        let kind = connection.value(0).and_then(Property::as_str);
+        let child = connection.value(1).and_then(Property::as_i64);
+        let parent = connection.value(2).and_then(Property::as_i64);
+        if let (Some("OO"), Some(child), Some(parent)) = (kind, child, parent) {
+            if !models.contains_key(&parent) {
+                continue;
+            }
+            if models.contains_key(&child) {
+                parents.insert(child, parent);
+            } else {
+                users.entry(child).or_default().push(parent);
+            }
+        }
+    }
+
+    let mut data = MeshData::default();
+    for geometry in objects.children_named("Geometry") {
+        // shapes (blend targets) and curves are geometry too
+        if geometry.value(2).and_then(Property::as_str) != Some("Mesh") {
+            continue;
+        }
+        match geometry.id().and_then(|id| users.get(&id)) {
+            Some(models_using) => {
+                for &model in models_using {
+                    let world = world_transform(model, &models, &parents);
+                    data.append(mesh(geometry, world)?);
+                }
+            }
+            None => data.append(mesh(geometry, Matrix4::identity())?),
+        }
+    }
+    if data.vertices.is_empty() {
+        return Err(LoadError::Parse("FBX file contains no meshes".to_owned()));
+    }
+    Ok(data)
+}
+
+fn world_transform(
+    id: i64,
+    models: &HashMap<i64, &Node>,
+    parents: &HashMap<i64, i64>,
+) -> Matrix4<f64> {
+    let mut world = Matrix4::identity();
+    let mut current = Some(id);
+    for _ in 0..MAX_DEPTH {
+        let id = match current {
+            Some(id) => id,
+            None => break,
+        };
+        if let Some(model) = models.get(&id) {
+            world = local_transform(model) * world;
+        }
+        current = parents.get(&id).copied();
+    }
+    world
+}
+
+// FBX's default rotation order applies X first, then Y, then Z
+fn euler_xyz(degrees: Vector3<f64>) -> Matrix4<f64> {
+    Matrix4::from_angle_z(Deg(degrees.z))
+        * Matrix4::from_angle_y(Deg(degrees.y))
+        * Matrix4::from_angle_x(Deg(degrees.x))
+}
+
+// pivots and offsets are ignored, exporters rarely write anything but zero for them
+fn local_transform(model: &Node) -> Matrix4<f64> {
+    let mut translation = Vector3::new(0.0, 0.0, 0.0);
+    let mut pre_rotation = Vector3::new(0.0, 0.0, 0.0);
+    let mut rotation = Vector3::new(0.0, 0.0, 0.0);
+    let mut scaling = Vector3::new(1.0, 1.0, 1.0);
+    let properties = model.child("Properties70");
+    for property in properties.iter().flat_map(|node| node.children_named("P")) {
+        let value = || -> Option<Vector3<f64>> {
+            Some(Vector3::new(
+                property.value(4)?.as_f64()?,
+                property.value(5)?.as_f64()?,
+                property.value(6)?.as_f64()?,
+            ))
+        };
+        let target = match property.value(0).and_then(Property::as_str) {
+            Some("Lcl Translation") => &mut translation,
+            Some("PreRotation") => &mut pre_rotation,
+            Some("Lcl Rotation") => &mut rotation,
+            Some("Lcl Scaling") => &mut scaling,
+            _ => continue,
+        };
+        if let Some(value) = value() {
+            *target = value;
+        }
+    }
+    Matrix4::from_translation(translation)
+        * euler_xyz(pre_rotation)
+        * euler_xyz(rotation)
+        * Matrix4::from_nonuniform_scale(scaling.x, scaling.y, scaling.z)
+}
+
+enum Mapping {
+    PolygonVertex,
+    ControlPoint,
+    Polygon,
+    AllSame,
+}
+
+// one of a geometry's per-vertex attribute layers, normals or uvs
+struct Layer<'a> {
+    values: Cow<'a, [f64]>,
+    indices: Option<&'a [i64]>,
+    mapping: Mapping,
+}
+
+impl<'a> Layer<'a> {
+    fn read(geometry: &'a Node, layer: &str, values: &str, indices: &str) -> Option<Self> {
+        let layer = geometry.child(layer)?;
+        let string = |name: &str| layer.child(name)?.value(0)?.as_str();
+        let mapping = match string("MappingInformationType")? {
+            "ByPolygonVertex" => Mapping::PolygonVertex,
+            "ByVertice" | "ByVertex" | "ByControlPoint" => Mapping::ControlPoint,
+            "ByPolygon" => Mapping::Polygon,
+            "AllSame" => Mapping::AllSame,
+            _ => return None,
+        };
+        let indexed = matches!(string("ReferenceInformationType"), Some("IndexToDirect"));
+        Some(Self {
+            values: layer.child(values)?.value(0)?.f64s()?,
+            indices: if indexed {
+                layer.child(indices).and_then(|n| n.value(0)?.i64s())
+            } else {
+                None
+            },
+            mapping,
+        })
+    }
+
+    // index of the element, already resolved through the index array
+    fn element(
+        &self,
+        control_point: usize,
+        polygon_vertex: usize,
+        polygon: usize,
+    ) -> Option<usize> {
+        let key = match self.mapping {
+            Mapping::PolygonVertex => polygon_vertex,
+            Mapping::ControlPoint => control_point,
+            Mapping::Polygon => polygon,
+            Mapping::AllSame => 0,
+        };
+        match self.indices {
+            Some(indices) => usize::try_from(*indices.get(key)?).ok(),
+            None => Some(key),
+        }
+    }
+
+    fn get<const N: usize>(&self, element: usize) -> Option<[f64; N]> {
+        let values = self.values.get(element * N..element * N + N)?;
+        let mut out = [0.0; N];
+        out.copy_from_slice(values);
+        Some(out)
+    }
+}
+
+fn mesh(geometry: &Node, world: Matrix4<f64>) -> Result<MeshData, LoadError> {
+    let missing = |what: &str| LoadError::Parse(format!("FBX geometry without {}", what));
+    let positions = geometry
+        .child("Vertices")
+        .and_then(|node| node.value(0)?.f64s())
+        .ok_or_else(|| missing("vertices"))?;
+    let polygon_indices = geometry
+        .child("PolygonVertexIndex")
+        .and_then(|node| node.value(0)?.i64s())
+        .ok_or_else(|| missing("polygons"))?;
+    let normals = Layer::read(geometry, "LayerElementNormal", "Normals", "NormalsIndex");
+    let uvs = Layer::read(geometry, "LayerElementUV", "UV", "UVIndex");
+    let normal_matrix =
+        Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate())
+            .invert()
+            .map(|m| m.transpose())
+            .unwrap_or_else(Matrix3::identity);
+
+    let mut data = MeshData::default();
+    let mut vertex_cache: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut face = Vec::new();
+    let mut polygon = 0;
+    for (polygon_vertex, &raw) in polygon_indices.iter().enumerate() {
+        // the last corner of each polygon is stored xor'd with -1
+        let last = raw < 0;
+        let control_point = (if last { !raw } else { raw }) as usize;
+        let position: [f64; 3] = positions
+            .get(control_point * 3..control_point * 3 + 3)
+            .and_then(|p| p.try_into().ok())
+            .ok_or_else(|| LoadError::Parse("FBX polygon index out of range".to_owned()))?;
+        // (element index, value), elements that point outside the layer are ignored
+        let normal = normals.as_ref().and_then(|layer| {
+            let element = layer.element(control_point, polygon_vertex, polygon)?;
+            Some((element, layer.get::<3>(element)?))
+        });
+        let uv = uvs.as_ref().and_then(|layer| {
+            let element = layer.element(control_point, polygon_vertex, polygon)?;
+            Some((element, layer.get::<2>(element)?))
+        });
+        let key = (
+            control_point,
+            normal.map(|(element, _)| element),
+            uv.map(|(element, _)| element),
+        );
+        let vertices = &mut data.vertices;
+        let index = *vertex_cache.entry(key).or_insert_with(|| {
+            let position = world.transform_point(Point3::from(position));
+            let normal = match normal {
+                Some((_, n)) => (normal_matrix * Vector3::from(n)).normalize(),
+                None => Vector3::new(0.0, 0.0, 0.0),
+            };
+            // FBX puts the uv origin at the bottom left, like OBJ
+            let uv = match uv {
+                Some((_, [u, v])) => [u as f32, 1.0 - v as f32],
+                None => [0.0; 2],
+            };
+            vertices.push(Vertex {
+                position: position.cast::<f32>().unwrap().into(),
+                normal: normal.cast::<f32>().unwrap().into(),
+                uv,
+            });
+            (vertices.len() - 1) as u32
+        });
+        face.push(index);
+        if last {
+            // fan triangulation, same as the OBJ loader
+            for i in 1..face.len().saturating_sub(1) {
+                data.indices
+                    .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+            }
+            face.clear();
+            polygon += 1;
+        }
+    }
+    if normals.is_none() {
+        data.compute_normals();
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a quad and a model moving it 10 along x, the way exporters write them
+    const QUAD: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+    FBXVersion: 7400
+}
+Objects:  {
+    Geometry: 100, "Geometry::Quad", "Mesh" {
+        Vertices: *12 {
+            a: 0,0,0,1,0,0,1,1,0,0,1,0
+        }
+        PolygonVertexIndex: *4 {
+            a: 0,1,2,-4
+        }
+        LayerElementUV: 0 {
+            MappingInformationType: "ByPolygonVertex"
+            ReferenceInformationType: "Direct"
+            UV: *8 {
+                a: 0,0,1,0,1,1,0,1
+            }
+        }
+    }
+    Model: 200, "Model::Quad", "Mesh" {
+        Properties70:  {
+            P: "Lcl Translation", "Lcl Translation", "", "A",10,0,0
+        }
+    }
+}
+Connections:  {
+    C: "OO",100,200
+}
+"#;
+
+    #[test]
+    fn ascii_quad_is_fan_triangulated_and_placed() {
+        let nodes = ascii::parse(QUAD).unwrap();
+        let data = build(&nodes).unwrap();
+        let positions: Vec<[f32; 3]> = data.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(
+            positions,
+            vec![
+                [10.0, 0.0, 0.0],
+                [11.0, 0.0, 0.0],
+                [11.0, 1.0, 0.0],
+                [10.0, 1.0, 0.0]
+            ]
+        );
+        assert_eq!(data.indices, vec![0, 1, 2, 0, 2, 3]);
+        // the uv origin moves from the bottom left to the top left
+        assert_eq!(data.vertices[0].uv, [0.0, 1.0]);
+        assert_eq!(data.vertices[2].uv, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn ascii_arrays_are_flattened() {
+        let nodes = ascii::parse(QUAD).unwrap();
+        let objects = nodes.iter().find(|node| node.name == "Objects").unwrap();
+        let geometry = objects.child("Geometry").unwrap();
+        assert_eq!(geometry.id(), Some(100));
+        let polygons = geometry.child("PolygonVertexIndex").unwrap();
+        assert_eq!(
+            polygons.value(0).and_then(Property::i64s),
+            Some(&[0, 1, 2, -4][..])
+        );
+    }
+
+    #[test]
+    fn files_without_meshes_are_rejected() {
+        let nodes = ascii::parse("Objects:  {\n}\n").unwrap();
+        assert!(build(&nodes).is_err());
+        assert!(build(&[]).is_err());
+    }
+
+    #[test]
+    fn versions_before_7_are_rejected() {
+        assert!(check_version(6100).is_err());
+        assert!(check_version(7000).is_ok());
+        assert!(check_version(7500).is_ok());
+    }
+}
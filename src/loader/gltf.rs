@@ -0,0 +1,379 @@
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, Rad, SquareMatrix, Transform,
+    Vector3,
+};
+use std::{collections::HashMap, fs, path::Path};
+
+use super::{
+    progress::{LoadPhase, LoadProgress},
+    CoordinateSystem, LoadError, LoadedModel,
+};
+use crate::render::{
+    CameraDef, CameraProjection, Joint, LineData, LineVertex, MaterialFactors, MeshData, SceneInfo,
+    Skeleton, Topology, Vertex, DEFAULT_LINE_COLOR,
+};
+
+// what the loader reads, the gltf crate parses more than this without it being used
+const SUPPORTED_EXTENSIONS: &[&str] = &[];
+
+/*
+ * The extensions the file uses that aren't in SUPPORTED_EXTENSIONS, or an error for the
+ * first one it requires. Checked on the raw json ahead of the import, which rejects some
+ * required extensions with a less helpful validation error.
+ */
+fn unsupported_extensions(path: &Path) -> Result<Vec<String>, LoadError> {
+    let bytes = fs::read(path)?;
+    let json = if bytes.starts_with(b"glTF") {
+        ::gltf::Glb::from_slice(&bytes)?.json.into_owned()
+    } else {
+        bytes
+    };
+    let root =
+        ::gltf::json::Root::from_slice(&json).map_err(|err| LoadError::Parse(err.to_string()))?;
+    let supported = |name: &String| SUPPORTED_EXTENSIONS.contains(&name.as_str());
+    if let Some(name) = root
+        .extensions_required
+        .iter()
+        .find(|name| !supported(name))
+    {
+        return Err(LoadError::UnsupportedExtension(name.clone()));
+    }
+    Ok(root
+        .extensions_used
+        .into_iter()
+        .filter(|name| !supported(name))
+        .collect())
+}
+
+pub fn load(path: &Path, progress: &mut dyn FnMut(LoadProgress)) -> Result<LoadedModel, LoadError> {
+    load_scene(path, None, progress)
+}
+
+/*
+ * The scene at `index`, or the file's default one, with every scene listed in the model.
+ * Parsing goes by the scene's top level nodes, the images come last as LoadingTextures.
+ */
+pub fn load_scene(
+    path: &Path,
+    index: Option<usize>,
+    progress: &mut dyn FnMut(LoadProgress),
+) -> Result<LoadedModel, LoadError> {
+    progress(LoadProgress::new(LoadPhase::Parsing, 0.0));
+    let unsupported_extensions = unsupported_extensions(path)?;
+    if !unsupported_extensions.is_empty() {
+        log::warn!(
+            "{} uses glTF extensions that are ignored: {}",
+            path.display(),
+            unsupported_extensions.join(", ")
+        );
+    }
+    // what ::gltf::import does, taken apart to report the images separately
+    let ::gltf::Gltf { document, blob } = ::gltf::Gltf::open(path)?;
+    let buffers = ::gltf::import_buffers(&document, path.parent(), blob)?;
+    let scene = match index {
+        Some(index) => document
+            .scenes()
+            .nth(index)
+            .ok_or_else(|| LoadError::Parse(format!("glTF file has no scene {}", index)))?,
+        None => document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| LoadError::Parse("glTF file contains no scenes".to_owned()))?,
+    };
+    // the spec fixes +Y up and +Z forward
+    let mut model = LoadedModel {
+        coordinate_system: CoordinateSystem::y_up(),
+        unsupported_extensions,
+        scenes: document
+            .scenes()
+            .map(|scene| SceneInfo {
+                index: scene.index(),
+                name: scene.name().map(str::to_owned),
+            })
+            .collect(),
+        scene: Some(scene.index()),
+        ..Default::default()
+    };
+    let node_count = scene.nodes().count().max(1);
+    for (i, node) in scene.nodes().enumerate() {
+        visit(&node, Matrix4::identity(), &buffers, &mut model);
+        progress(LoadProgress::new(
+            LoadPhase::Parsing,
+            (i + 1) as f32 / node_count as f32,
+        ));
+    }
+    let mut worlds = HashMap::new();
+    for node in scene.nodes() {
+        world_transforms(&node, Matrix4::identity(), &mut worlds);
+    }
+    for node in document.nodes() {
+        if let (Some(skin), Some(&world)) = (node.skin(), worlds.get(&node.index())) {
+            model
+                .skeletons
+                .push(read_skeleton(&skin, world, &worlds, &buffers));
+        }
+    }
+    progress(LoadProgress::new(LoadPhase::Parsing, 1.0));
+    // nothing draws textures yet, but a broken image still fails the load like it used to
+    progress(LoadProgress {
+        phase: LoadPhase::LoadingTextures,
+        fraction: None,
+    });
+    let _images = ::gltf::import_images(&document, path.parent(), &buffers)?;
+    progress(LoadProgress::new(LoadPhase::LoadingTextures, 1.0));
+    Ok(model)
+}
+
+fn world_transforms(
+    node: &::gltf::Node,
+    parent: Matrix4<f32>,
+    worlds: &mut HashMap<usize, Matrix4<f32>>,
+) {
+    let world = parent * Matrix4::from(node.transform().matrix());
+    worlds.insert(node.index(), world);
+    for child in node.children() {
+        world_transforms(&child, world, worlds);
+    }
+}
+
+/*
+ * Joints go where the inverse bind matrices put them, under the transform of the node
+ * using the skin. That's the pose the mesh was modelled in, which is what gets drawn since
+ * the viewer doesn't skin. Skins without them fall back to the joint nodes' own transforms.
+ */
+fn read_skeleton(
+    skin: &::gltf::Skin,
+    mesh_world: Matrix4<f32>,
+    worlds: &HashMap<usize, Matrix4<f32>>,
+    buffers: &[::gltf::buffer::Data],
+) -> Skeleton {
+    let nodes: Vec<::gltf::Node> = skin.joints().collect();
+    let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+    let inverse_binds: Option<Vec<[[f32; 4]; 4]>> = reader
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.collect());
+    // joints whose parent node isn't in the skin become roots
+    let index_of: HashMap<usize, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.index(), i))
+        .collect();
+    let mut parents = vec![None; nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        for child in node.children() {
+            if let Some(&joint) = index_of.get(&child.index()) {
+                parents[joint] = Some(i);
+            }
+        }
+    }
+    let joints = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let bind = inverse_binds
+                .as_ref()
+                .and_then(|matrices| matrices.get(i))
+                .and_then(|&matrix| Matrix4::from(matrix).invert());
+            let world = match bind {
+                Some(bind) => mesh_world * bind,
+                None => worlds.get(&node.index()).copied().unwrap_or(mesh_world),
+            };
+            Joint {
+                position: world.transform_point(Point3::origin()),
+                parent: parents[i],
+            }
+        })
+        .collect();
+    Skeleton {
+        name: skin.name().map(String::from),
+        joints,
+    }
+}
+
+fn camera_def(camera: &::gltf::Camera, world: Matrix4<f32>) -> CameraDef {
+    let projection = match camera.projection() {
+        ::gltf::camera::Projection::Perspective(p) => CameraProjection::Perspective {
+            yfov: Rad(p.yfov()),
+            znear: p.znear(),
+            zfar: p.zfar(),
+        },
+        ::gltf::camera::Projection::Orthographic(o) => CameraProjection::Orthographic {
+            xmag: o.xmag(),
+            ymag: o.ymag(),
+            znear: o.znear(),
+            zfar: o.zfar(),
+        },
+    };
+    // glTF cameras look down their local -Z with +Y up
+    CameraDef {
+        name: camera.name().map(str::to_owned),
+        eye: world.transform_point(Point3::new(0.0, 0.0, 0.0)),
+        forward: world.transform_vector(-Vector3::unit_z()).normalize(),
+        up: world.transform_vector(Vector3::unit_y()).normalize(),
+        projection,
+    }
+}
+
+fn read_lines(
+    primitive: &::gltf::Primitive,
+    world: Matrix4<f32>,
+    buffers: &[::gltf::buffer::Data],
+) -> LineData {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let mut lines = LineData::default();
+    let positions = match reader.read_positions() {
+        Some(positions) => positions,
+        None => return lines,
+    };
+    lines.vertices = positions
+        .map(|p| LineVertex {
+            position: world.transform_point(Point3::from(p)).into(),
+            color: DEFAULT_LINE_COLOR,
+        })
+        .collect();
+    if let Some(colors) = reader.read_colors(0) {
+        for (vertex, color) in lines.vertices.iter_mut().zip(colors.into_rgba_f32()) {
+            vertex.color = color;
+        }
+    }
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..lines.vertices.len() as u32).collect(),
+    };
+    match primitive.mode() {
+        ::gltf::mesh::Mode::Lines => {
+            lines.indices = indices;
+            lines.indices.truncate(lines.indices.len() / 2 * 2);
+        }
+        mode => {
+            lines.push_strip(&indices);
+            if mode == ::gltf::mesh::Mode::LineLoop && indices.len() > 2 {
+                lines.push_strip(&[indices[indices.len() - 1], indices[0]]);
+            }
+        }
+    }
+    lines
+}
+
+// every triangle shares the first vertex, wound the same way as the fan
+fn fan_to_list(indices: &[u32]) -> Vec<u32> {
+    match indices.split_first() {
+        Some((&center, rest)) => rest
+            .windows(2)
+            .flat_map(|pair| vec![center, pair[0], pair[1]])
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn material_factors(material: &::gltf::Material) -> MaterialFactors {
+    let pbr = material.pbr_metallic_roughness();
+    MaterialFactors {
+        base_color: pbr.base_color_factor(),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        emissive: material.emissive_factor(),
+        alpha_cutoff: match material.alpha_mode() {
+            ::gltf::material::AlphaMode::Mask => Some(material.alpha_cutoff()),
+            _ => None,
+        },
+    }
+}
+
+// flattens the node hierarchy, baking each node's world transform into its vertices
+fn visit(
+    node: &::gltf::Node,
+    parent: Matrix4<f32>,
+    buffers: &[::gltf::buffer::Data],
+    model: &mut LoadedModel,
+) {
+    let world = parent * Matrix4::from(node.transform().matrix());
+    if let Some(camera) = node.camera() {
+        model.cameras.push(camera_def(&camera, world));
+    }
+    if let Some(mesh) = node.mesh() {
+        let normal_matrix =
+            Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate())
+                .invert()
+                .map(|m| m.transpose())
+                .unwrap_or_else(Matrix3::identity);
+        for primitive in mesh.primitives() {
+            let mode = primitive.mode();
+            let topology = match mode {
+                // wgpu can't draw fans, they're turned into lists below
+                ::gltf::mesh::Mode::Triangles | ::gltf::mesh::Mode::TriangleFan => {
+                    Topology::TriangleList
+                }
+                ::gltf::mesh::Mode::TriangleStrip => Topology::TriangleStrip,
+                ::gltf::mesh::Mode::Points => Topology::PointList,
+                ::gltf::mesh::Mode::Lines
+                | ::gltf::mesh::Mode::LineStrip
+                | ::gltf::mesh::Mode::LineLoop => {
+                    model.lines.append(read_lines(&primitive, world, buffers));
+                    continue;
+                }
+            };
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions = match reader.read_positions() {
+                Some(positions) => positions,
+                None => continue,
+            };
+            let mut vertices: Vec<Vertex> = positions
+                .map(|p| Vertex {
+                    position: world.transform_point(Point3::from(p)).into(),
+                    normal: [0.0; 3],
+                    uv: [0.0; 2],
+                })
+                .collect();
+            if let Some(uvs) = reader.read_tex_coords(0) {
+                for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+                    vertex.uv = uv;
+                }
+            }
+            let has_normals = match reader.read_normals() {
+                Some(normals) => {
+                    for (vertex, n) in vertices.iter_mut().zip(normals) {
+                        vertex.normal = (normal_matrix * Vector3::from(n)).normalize().into();
+                    }
+                    true
+                }
+                None => false,
+            };
+            let mut indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..vertices.len() as u32).collect(),
+            };
+            if mode == ::gltf::mesh::Mode::TriangleFan {
+                indices = fan_to_list(&indices);
+            }
+            let morph_targets = reader
+                .read_morph_targets()
+                .map(|(positions, _normals, _tangents)| match positions {
+                    Some(deltas) => deltas
+                        .map(|d| world.transform_vector(Vector3::from(d)).into())
+                        .collect(),
+                    None => vec![[0.0; 3]; vertices.len()],
+                })
+                .collect();
+            let mut part = MeshData {
+                vertices,
+                indices,
+                topology,
+                morph_targets,
+                morph_weights: mesh.weights().map(<[f32]>::to_vec).unwrap_or_default(),
+                material: Some(material_factors(&primitive.material())),
+            };
+            if !has_normals {
+                part.compute_normals();
+            }
+            match topology {
+                Topology::TriangleStrip => model.strips.append(part),
+                Topology::PointList => model.points.append(part),
+                _ => model.mesh.append(part),
+            }
+        }
+    }
+    for child in node.children() {
+        visit(&child, world, buffers, model);
+    }
+}
@@ -0,0 +1,262 @@
+mod coordinates;
+mod fbx;
+mod gltf;
+mod obj;
+mod progress;
+mod stl;
+
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+pub use coordinates::{CoordinateSystem, Handedness};
+pub(crate) use obj::save as save_obj;
+pub use progress::{LoadPhase, LoadProgress};
+
+use crate::render::{CameraDef, LineData, MeshData, SceneInfo, Skeleton};
+
+// everything a loader pulls out of one file
+#[derive(Debug, Default)]
+pub struct LoadedModel {
+    pub mesh: MeshData,
+    // triangle strip primitives, kept apart since they're drawn with their own pipeline
+    pub strips: MeshData,
+    // point clouds, glTF's point primitives
+    pub points: MeshData,
+    pub lines: LineData,
+    pub cameras: Vec<CameraDef>,
+    // one per glTF skin
+    pub skeletons: Vec<Skeleton>,
+    // what the coordinates are in, the format's usual convention unless the file says
+    pub coordinate_system: CoordinateSystem,
+    // optional glTF extensions the file uses that were ignored, so it may not look right
+    pub unsupported_extensions: Vec<String>,
+    // every scene in a glTF file, and which of them the geometry is from
+    pub scenes: Vec<SceneInfo>,
+    pub scene: Option<usize>,
+}
+
+/*
+ * What gets done to a model between loading and uploading it, the same for files loaded
+ * whole and streamed ones, see LoadedModel::prepare.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Conversion {
+    // what the file's coordinates are in, whatever it says itself
+    pub coordinate_system: Option<CoordinateSystem>,
+    pub deterministic_geometry: bool,
+    pub repair_winding: bool,
+    pub optimize_mesh: bool,
+}
+
+impl LoadedModel {
+    // bytes of GPU buffers uploading it takes, textures aren't counted as none are loaded
+    pub fn gpu_memory(&self) -> u64 {
+        self.mesh.gpu_memory()
+            + self.strips.gpu_memory()
+            + self.points.gpu_memory()
+            + self.lines.gpu_memory()
+    }
+
+    // brings a model loaded from `path` into canonical coordinates and applies `conversion`
+    pub fn prepare(&mut self, conversion: &Conversion, path: &Path) {
+        if let Some(system) = conversion.coordinate_system {
+            self.coordinate_system = system;
+        }
+        log::debug!("{} is {:?}", path.display(), self.coordinate_system);
+        self.convert_to(CoordinateSystem::CANONICAL);
+        if conversion.deterministic_geometry {
+            self.mesh.canonicalize();
+            self.strips.canonicalize();
+            self.points.canonicalize();
+            self.lines.canonicalize();
+        }
+        if conversion.repair_winding {
+            let flipped = self.mesh.repair_winding() + self.strips.repair_winding();
+            log::info!("{}: flipped {} triangles", path.display(), flipped);
+        }
+        if conversion.optimize_mesh {
+            if let Some((before, after)) = self.mesh.optimize_vertex_cache() {
+                log::info!(
+                    "{}: vertex cache ACMR {:.3} -> {:.3}",
+                    path.display(),
+                    before,
+                    after
+                );
+            }
+        }
+    }
+}
+
+impl From<MeshData> for LoadedModel {
+    fn from(mesh: MeshData) -> Self {
+        Self {
+            mesh,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(String),
+    Gltf(::gltf::Error),
+    UnsupportedFormat(String),
+    // a glTF extension the file can't be loaded without
+    UnsupportedExtension(String),
+    // the upload would go over the GPU memory budget or an adapter limit, in bytes
+    InsufficientMemory { required: u64, available: u64 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Parse(message) => write!(f, "parse error: {}", message),
+            LoadError::Gltf(err) => write!(f, "glTF error: {}", err),
+            LoadError::UnsupportedFormat(extension) => {
+                write!(f, "unsupported file format '{}'", extension)
+            }
+            LoadError::UnsupportedExtension(name) => {
+                write!(f, "unsupported required glTF extension '{}'", name)
+            }
+            LoadError::InsufficientMemory {
+                required,
+                available,
+            } => write!(
+                f,
+                "needs about {:.1} MiB of GPU memory, only {:.1} MiB available",
+                mebibytes(*required),
+                mebibytes(*available)
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Gltf(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<::gltf::Error> for LoadError {
+    fn from(err: ::gltf::Error) -> Self {
+        LoadError::Gltf(err)
+    }
+}
+
+pub(crate) fn mebibytes(bytes: u64) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+// triangles per chunk handed over by `load_streaming`
+const STREAM_CHUNK_TRIANGLES: usize = 1 << 16;
+// chunks parsed ahead of the receiver before the parser waits for it to catch up
+const STREAM_QUEUE_CHUNKS: usize = 4;
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+// picks a loader based on the file extension
+pub fn load(path: &Path) -> Result<LoadedModel, LoadError> {
+    load_with_progress(path, &mut |_| {})
+}
+
+// for the loaders that parse in one go, there's nothing to measure until they're done
+fn parse_whole(
+    path: &Path,
+    progress: &mut dyn FnMut(LoadProgress),
+    load: fn(&Path) -> Result<MeshData, LoadError>,
+) -> Result<MeshData, LoadError> {
+    progress(LoadProgress {
+        phase: LoadPhase::Parsing,
+        fraction: None,
+    });
+    let mesh = load(path)?;
+    progress(LoadProgress::new(LoadPhase::Parsing, 1.0));
+    Ok(mesh)
+}
+
+// `load`, telling `progress` how far it got along the way, see LoadProgress
+pub fn load_with_progress(
+    path: &Path,
+    progress: &mut dyn FnMut(LoadProgress),
+) -> Result<LoadedModel, LoadError> {
+    let extension = extension(path);
+    match extension.as_str() {
+        "obj" => obj::load(path, progress),
+        "stl" => parse_whole(path, progress, stl::load).map(LoadedModel::from),
+        "gltf" | "glb" => self::gltf::load(path, progress),
+        "fbx" => parse_whole(path, progress, fbx::load).map(|mesh| LoadedModel {
+            coordinate_system: CoordinateSystem::y_up(),
+            ..LoadedModel::from(mesh)
+        }),
+        _ => Err(LoadError::UnsupportedFormat(extension)),
+    }
+}
+
+// one of the scenes of a glTF file instead of its default, see LoadedModel::scenes
+pub fn load_gltf_scene(path: &Path, index: usize) -> Result<LoadedModel, LoadError> {
+    let extension = extension(path);
+    match extension.as_str() {
+        "gltf" | "glb" => self::gltf::load_scene(path, Some(index), &mut |_| {}),
+        _ => Err(LoadError::UnsupportedFormat(extension)),
+    }
+}
+
+// whether `load_streaming` hands the file over in pieces rather than all at once
+pub fn streams_in_chunks(path: &Path) -> bool {
+    extension(path) == "obj"
+}
+
+/*
+ * Parses on a background thread and sends geometry over as it is read. OBJ files arrive in
+ * chunks, other formats are loaded whole and sent as a single model. Nothing is converted,
+ * each piece is in the file's own coordinate system ready for LoadedModel::prepare. The
+ * channel closes once the file is done, an error is sent at most once and ends the stream.
+ * At most STREAM_QUEUE_CHUNKS wait in the channel, so a slow receiver holds the parser back
+ * instead of the whole file piling up in memory.
+ */
+pub fn load_streaming(path: &Path) -> Result<Receiver<Result<LoadedModel, LoadError>>, LoadError> {
+    let extension = extension(path);
+    if !matches!(extension.as_str(), "obj" | "stl" | "gltf" | "glb" | "fbx") {
+        return Err(LoadError::UnsupportedFormat(extension));
+    }
+    let path: PathBuf = path.to_owned();
+    let (sender, receiver) = mpsc::sync_channel(STREAM_QUEUE_CHUNKS);
+    thread::spawn(move || {
+        let result = if extension == "obj" {
+            // a closed channel just means nobody is listening anymore
+            obj::stream(&path, STREAM_CHUNK_TRIANGLES, |chunk| {
+                let _ = sender.send(Ok(LoadedModel::from(chunk)));
+            })
+        } else {
+            load(&path).map(|model| {
+                let _ = sender.send(Ok(model));
+            })
+        };
+        if let Err(err) = result {
+            let _ = sender.send(Err(err));
+        }
+    });
+    Ok(receiver)
+}
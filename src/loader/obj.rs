@@ -0,0 +1,346 @@
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use super::{
+    progress::{LoadPhase, LoadProgress, Throttle},
+    LoadError, LoadedModel,
+};
+use crate::render::{
+    LineData, LineVertex, Lines, MeshData, SceneObject, Vertex, DEFAULT_LINE_COLOR,
+};
+
+pub fn load(path: &Path, progress: &mut dyn FnMut(LoadProgress)) -> Result<LoadedModel, LoadError> {
+    let size = fs::metadata(path)?.len();
+    parse(BufReader::new(File::open(path)?), size, progress)
+}
+
+fn parse_uv<'a, I: Iterator<Item = &'a str>>(
+    tokens: &mut I,
+    line: usize,
+) -> Result<[f32; 2], LoadError> {
+    let mut out = [0.0; 2];
+    for value in out.iter_mut() {
+        *value = tokens
+            .next()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| LoadError::Parse(format!("line {}: expected 2 numbers", line)))?;
+    }
+    // OBJ puts the origin at the bottom left
+    out[1] = 1.0 - out[1];
+    Ok(out)
+}
+
+fn parse_vec3<'a, I: Iterator<Item = &'a str>>(
+    tokens: &mut I,
+    line: usize,
+) -> Result<[f32; 3], LoadError> {
+    let mut out = [0.0; 3];
+    for value in out.iter_mut() {
+        *value = tokens
+            .next()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| LoadError::Parse(format!("line {}: expected 3 numbers", line)))?;
+    }
+    Ok(out)
+}
+
+// OBJ indices are 1-based, negative values count back from the latest element
+fn resolve_index(token: &str, len: usize, line: usize) -> Result<usize, LoadError> {
+    let invalid = || LoadError::Parse(format!("line {}: invalid index '{}'", line, token));
+    let index: i64 = token.parse().map_err(|_| invalid())?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(invalid());
+    }
+    Ok(resolved as usize)
+}
+
+/*
+ * Where a face's vertices get their normals from when the file doesn't give any: shared
+ * with the other faces of its smoothing group, or the face's own for group 0. Vertices
+ * are only shared within a group, so averaging over shared vertices stays inside it.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Smoothing {
+    // from the file
+    Given,
+    Group(u32),
+    // `s off` or `s 0`, numbered so no two faces share a vertex
+    Flat(usize),
+}
+
+// attribute pools span the whole file, output geometry is collected per chunk
+#[derive(Default)]
+struct Parser {
+    positions: Vec<[f32; 3]>,
+    // one per position
+    colors: Vec<[f32; 4]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    chunk: MeshData,
+    // faces reference attributes separately, a vertex is a unique combination
+    vertex_cache: HashMap<(usize, Option<usize>, Option<usize>, Smoothing), u32>,
+    missing_normals: bool,
+    // from the last `s` statement, None before any smooths everything together like group 1
+    smoothing_group: Option<u32>,
+    faces: usize,
+    lines: LineData,
+    line_cache: HashMap<usize, u32>,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str, line_no: usize) -> Result<(), LoadError> {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                self.positions.push(parse_vec3(&mut tokens, line_no)?);
+                // a common extension appends an rgb colour to the position
+                let rgb: Vec<f32> = tokens.filter_map(|token| token.parse().ok()).collect();
+                self.colors.push(match rgb[..] {
+                    [r, g, b, ..] => [r, g, b, 1.0],
+                    _ => DEFAULT_LINE_COLOR,
+                });
+            }
+            Some("l") => {
+                let mut strip = Vec::new();
+                for token in tokens {
+                    // lines may reference a uv as well, there is nothing to use it for
+                    let reference = token.split('/').next().unwrap();
+                    let position = resolve_index(reference, self.positions.len(), line_no)?;
+                    let (positions, colors) = (&self.positions, &self.colors);
+                    let vertices = &mut self.lines.vertices;
+                    let index = *self.line_cache.entry(position).or_insert_with(|| {
+                        vertices.push(LineVertex {
+                            position: positions[position],
+                            color: colors[position],
+                        });
+                        (vertices.len() - 1) as u32
+                    });
+                    strip.push(index);
+                }
+                if strip.len() < 2 {
+                    return Err(LoadError::Parse(format!(
+                        "line {}: line element needs at least 2 vertices",
+                        line_no
+                    )));
+                }
+                self.lines.push_strip(&strip);
+            }
+            Some("vn") => self.normals.push(parse_vec3(&mut tokens, line_no)?),
+            Some("vt") => self.uvs.push(parse_uv(&mut tokens, line_no)?),
+            Some("s") => {
+                self.smoothing_group = match tokens.next() {
+                    Some("off") => Some(0),
+                    Some(group) => Some(group.parse().map_err(|_| {
+                        LoadError::Parse(format!(
+                            "line {}: invalid smoothing group {:?}",
+                            line_no, group
+                        ))
+                    })?),
+                    None => None,
+                };
+            }
+            Some("f") => {
+                self.faces += 1;
+                let mut face = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position =
+                        resolve_index(parts.next().unwrap(), self.positions.len(), line_no)?;
+                    let uv = match parts.next() {
+                        Some(part) if !part.is_empty() => {
+                            Some(resolve_index(part, self.uvs.len(), line_no)?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(part) if !part.is_empty() => {
+                            Some(resolve_index(part, self.normals.len(), line_no)?)
+                        }
+                        _ => None,
+                    };
+                    self.missing_normals |= normal.is_none();
+                    let smoothing = match (normal, self.smoothing_group.unwrap_or(1)) {
+                        (Some(_), _) => Smoothing::Given,
+                        (None, 0) => Smoothing::Flat(self.faces),
+                        (None, group) => Smoothing::Group(group),
+                    };
+                    let (positions, normals, uvs) = (&self.positions, &self.normals, &self.uvs);
+                    let vertices = &mut self.chunk.vertices;
+                    let key = (position, uv, normal, smoothing);
+                    let index = *self.vertex_cache.entry(key).or_insert_with(|| {
+                        vertices.push(Vertex {
+                            position: positions[position],
+                            normal: normal.map(|n| normals[n]).unwrap_or([0.0; 3]),
+                            uv: uv.map(|t| uvs[t]).unwrap_or([0.0; 2]),
+                        });
+                        (vertices.len() - 1) as u32
+                    });
+                    face.push(index);
+                }
+                if face.len() < 3 {
+                    return Err(LoadError::Parse(format!(
+                        "line {}: face needs at least 3 vertices",
+                        line_no
+                    )));
+                }
+                // fan triangulation, fine for the convex polygons OBJ exporters emit
+                for i in 1..face.len() - 1 {
+                    self.chunk
+                        .indices
+                        .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn take_chunk(&mut self) -> MeshData {
+        let mut chunk = std::mem::take(&mut self.chunk);
+        if self.missing_normals {
+            chunk.compute_normals();
+        }
+        self.vertex_cache.clear();
+        self.missing_normals = false;
+        chunk
+    }
+}
+
+// reports Parsing by how many of the `size` bytes have been read, in steps of a percent
+fn parse<R: BufRead>(
+    reader: R,
+    size: u64,
+    progress: &mut dyn FnMut(LoadProgress),
+) -> Result<LoadedModel, LoadError> {
+    let mut parser = Parser::default();
+    let mut throttle = Throttle::new(0.01);
+    let mut read = 0;
+    progress(LoadProgress::new(LoadPhase::Parsing, 0.0));
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        parser.line(&line, line_index + 1)?;
+        // the line break isn't part of the line, close enough for either kind
+        read += line.len() as u64 + 1;
+        let fraction = (read as f64 / size.max(1) as f64).min(1.0) as f32;
+        throttle.report(progress, LoadPhase::Parsing, fraction);
+    }
+    progress(LoadProgress::new(LoadPhase::Parsing, 1.0));
+    Ok(LoadedModel {
+        mesh: parser.take_chunk(),
+        lines: parser.lines,
+        ..Default::default()
+    })
+}
+
+// hands geometry over every `chunk_triangles` triangles instead of building one big mesh.
+// vertices shared across a chunk boundary are duplicated. line elements are skipped
+pub fn stream<F>(path: &Path, chunk_triangles: usize, mut emit: F) -> Result<(), LoadError>
+where
+    F: FnMut(MeshData),
+{
+    let mut parser = Parser::default();
+    for (line_index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        parser.line(&line?, line_index + 1)?;
+        if parser.chunk.triangle_count() >= chunk_triangles {
+            emit(parser.take_chunk());
+        }
+    }
+    if !parser.chunk.indices.is_empty() {
+        emit(parser.take_chunk());
+    }
+    Ok(())
+}
+
+/*
+ * Writes every object as its own group in one file, moved by `transform` the way it's
+ * drawn. Positions get `origin` added back so they come out in the coordinates they were
+ * loaded in, and v is flipped back to OBJ's bottom-left convention, so loading the export
+ * reproduces the scene.
+ */
+pub fn save(
+    path: &Path,
+    objects: &[SceneObject],
+    lines: &[Lines],
+    transform: Matrix4<f32>,
+    origin: Vector3<f64>,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let position = |p: [f32; 3]| -> [f32; 3] { transform.transform_point(Point3::from(p)).into() };
+    let linear = Matrix3::from_cols(
+        transform.x.truncate(),
+        transform.y.truncate(),
+        transform.z.truncate(),
+    );
+    let normal_matrix = linear
+        .invert()
+        .map(|m| m.transpose())
+        .unwrap_or_else(Matrix3::identity);
+    // a mirroring transform turns the faces inside out unless the winding goes with it
+    let mirrored = linear.determinant() < 0.0;
+    let mut offset = 1;
+    for object in objects {
+        let data = &object.mesh.data;
+        writeln!(out, "g {}", object.name)?;
+        for vertex in &data.vertices {
+            let [x, y, z] = position(vertex.position);
+            writeln!(
+                out,
+                "v {} {} {}",
+                x as f64 + origin.x,
+                y as f64 + origin.y,
+                z as f64 + origin.z
+            )?;
+        }
+        for vertex in &data.vertices {
+            writeln!(out, "vt {} {}", vertex.uv[0], 1.0 - vertex.uv[1])?;
+        }
+        for vertex in &data.vertices {
+            let normal = normal_matrix * Vector3::from(vertex.normal);
+            let [x, y, z]: [f32; 3] = if normal.magnitude2() > 0.0 {
+                normal.normalize().into()
+            } else {
+                vertex.normal
+            };
+            writeln!(out, "vn {} {} {}", x, y, z)?;
+        }
+        // attributes are written per vertex, so one index covers all three
+        for tri in data.triangles() {
+            let [a, b, c] = [tri[0] + offset, tri[1] + offset, tri[2] + offset];
+            let (b, c) = if mirrored { (c, b) } else { (b, c) };
+            writeln!(out, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+        }
+        offset += data.vertices.len() as u32;
+    }
+    for lines in lines {
+        writeln!(out, "g {}", lines.name)?;
+        for vertex in &lines.data.vertices {
+            let [x, y, z] = position(vertex.position);
+            let [r, g, b, _] = vertex.color;
+            writeln!(
+                out,
+                "v {} {} {} {} {} {}",
+                x as f64 + origin.x,
+                y as f64 + origin.y,
+                z as f64 + origin.z,
+                r,
+                g,
+                b
+            )?;
+        }
+        for segment in lines.data.indices.chunks_exact(2) {
+            writeln!(out, "l {} {}", segment[0] + offset, segment[1] + offset)?;
+        }
+        offset += lines.data.vertices.len() as u32;
+    }
+    out.flush()
+}
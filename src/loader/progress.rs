@@ -0,0 +1,56 @@
+// what a load is busy with, in the order they happen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadPhase {
+    // reading the file and building geometry from it
+    Parsing,
+    // decoding the images glTF files reference
+    LoadingTextures,
+    // handing the geometry to the renderer, only reported by the application
+    UploadingGeometry,
+}
+
+/*
+ * Passed to the callback of `load_with_progress` as a load goes along. Each phase that
+ * happens is reported at least twice: once as it starts and once with a fraction of 1 as
+ * it ends. Where the loader can tell how far it got, bytes read for OBJ and top level glTF
+ * nodes visited, it starts at 0 and reports in between, otherwise it starts with None.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoadProgress {
+    pub phase: LoadPhase,
+    // 0 to 1 through the phase
+    pub fraction: Option<f32>,
+}
+
+impl LoadProgress {
+    pub fn new(phase: LoadPhase, fraction: f32) -> Self {
+        Self {
+            phase,
+            fraction: Some(fraction),
+        }
+    }
+}
+
+// keeps frequent callers like the OBJ line loop from reporting more than every `step`
+pub(super) struct Throttle {
+    step: f32,
+    last: f32,
+}
+
+impl Throttle {
+    pub fn new(step: f32) -> Self {
+        Self { step, last: 0.0 }
+    }
+
+    pub fn report(
+        &mut self,
+        progress: &mut dyn FnMut(LoadProgress),
+        phase: LoadPhase,
+        fraction: f32,
+    ) {
+        if fraction - self.last >= self.step {
+            self.last = fraction;
+            progress(LoadProgress::new(phase, fraction));
+        }
+    }
+}
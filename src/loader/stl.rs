@@ -0,0 +1,105 @@
+use std::{convert::TryInto, fs, path::Path};
+
+use super::LoadError;
+use crate::render::{face_normal, MeshData, Vertex};
+
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+pub fn load(path: &Path) -> Result<MeshData, LoadError> {
+    let bytes = fs::read(path)?;
+    if is_binary(&bytes) {
+        Ok(parse_binary(&bytes))
+    } else {
+        let text = std::str::from_utf8(&bytes)
+            .map_err(|_| LoadError::Parse("not a valid binary or ASCII STL".to_owned()))?;
+        parse_ascii(text)
+    }
+}
+
+// ASCII files start with "solid" too, so trust the triangle count instead
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < HEADER_SIZE {
+        return false;
+    }
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    bytes.len() == HEADER_SIZE + count * TRIANGLE_SIZE
+}
+
+fn read_vec3(bytes: &[u8]) -> [f32; 3] {
+    let mut out = [0.0; 3];
+    for (i, value) in out.iter_mut().enumerate() {
+        *value = f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+    }
+    out
+}
+
+fn push_triangle(data: &mut MeshData, normal: [f32; 3], corners: [[f32; 3]; 3]) {
+    // STL is unindexed and many exporters write zero facet normals
+    let normal = if normal == [0.0; 3] {
+        face_normal(corners[0], corners[1], corners[2]).into()
+    } else {
+        normal
+    };
+    for &position in corners.iter() {
+        data.indices.push(data.vertices.len() as u32);
+        data.vertices.push(Vertex {
+            position,
+            normal,
+            uv: [0.0; 2],
+        });
+    }
+}
+
+fn parse_binary(bytes: &[u8]) -> MeshData {
+    let mut data = MeshData::default();
+    for triangle in bytes[HEADER_SIZE..].chunks_exact(TRIANGLE_SIZE) {
+        push_triangle(
+            &mut data,
+            read_vec3(&triangle[0..12]),
+            [
+                read_vec3(&triangle[12..24]),
+                read_vec3(&triangle[24..36]),
+                read_vec3(&triangle[36..48]),
+            ],
+        );
+    }
+    data
+}
+
+fn parse_ascii(text: &str) -> Result<MeshData, LoadError> {
+    let mut data = MeshData::default();
+    let mut normal = [0.0; 3];
+    let mut corners = Vec::with_capacity(3);
+    for (line_index, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next();
+        if keyword != Some("facet") && keyword != Some("vertex") {
+            continue;
+        }
+        if keyword == Some("facet") {
+            // "facet normal x y z"
+            tokens.next();
+        }
+        let mut values = [0.0f32; 3];
+        for value in values.iter_mut() {
+            *value = tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or_else(|| {
+                    LoadError::Parse(format!("line {}: expected 3 numbers", line_index + 1))
+                })?;
+        }
+        if keyword == Some("facet") {
+            normal = values;
+            corners.clear();
+        } else {
+            corners.push(values);
+            if corners.len() == 3 {
+                push_triangle(&mut data, normal, [corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+        }
+    }
+    Ok(data)
+}
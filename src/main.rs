@@ -1,8 +1,33 @@
-mod app;
-mod render;
-use app::Application;
+use model::{Application, Preset};
+use std::path::PathBuf;
+
+// frames `--bench` draws when it isn't given a count, as in `--bench=300`
+const DEFAULT_BENCHMARK_FRAMES: u32 = 600;
 
 fn main() {
     env_logger::init();
-    Application::new("Model Viewer", 60.0).run();
+    let (flags, files): (Vec<_>, Vec<_>) = std::env::args_os()
+        .skip(1)
+        .partition(|arg| arg.to_str().map_or(false, |arg| arg.starts_with("--")));
+    let mut builder = Application::builder()
+        .title("Model Viewer")
+        .frame_rate(60.0)
+        .files(files.into_iter().map(PathBuf::from));
+    let mut benchmark = None;
+    for flag in &flags {
+        match flag.to_str() {
+            Some("--technical") => builder = builder.preset(Preset::TechnicalDrawing),
+            Some("--bench") => benchmark = Some(DEFAULT_BENCHMARK_FRAMES),
+            Some(flag) if flag.starts_with("--bench=") => match flag["--bench=".len()..].parse() {
+                Ok(frames) => benchmark = Some(frames),
+                Err(_) => eprintln!("bad frame count in {}", flag),
+            },
+            _ => eprintln!("unknown flag {}", flag.to_string_lossy()),
+        }
+    }
+    let app = builder.build();
+    match benchmark {
+        Some(frames) => app.run_benchmark(frames),
+        None => app.run(),
+    }
 }
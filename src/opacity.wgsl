@@ -0,0 +1,14 @@
+[[group(0), binding(0)]]
+var t_frame: texture_2d<f32>;
+
+// the blend state does the scaling, see render/opacity.rs
+[[stage(vertex)]]
+fn vs_opacity([[builtin(vertex_index)]] vertex_index: u32) -> [[builtin(position)]] vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - vec2<f32>(1.0, 1.0), 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_opacity([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    return textureLoad(t_frame, vec2<i32>(position.xy), 0);
+}
@@ -0,0 +1,164 @@
+use std::{borrow::Cow, cell::Cell};
+use wgpu::*;
+
+use super::{texture::Texture, HdrTarget, HDR_FORMAT};
+
+// history * (1 - constant) + frame * constant, the constant being the new frame's weight
+const RUNNING_AVERAGE: BlendComponent = BlendComponent {
+    src_factor: BlendFactor::Constant,
+    dst_factor: BlendFactor::OneMinusConstant,
+    operation: BlendOperation::Add,
+};
+
+/*
+ * Temporal accumulation for still images: while the view holds still every frame is
+ * rendered with the projection jittered by a different sub-pixel offset and averaged into
+ * `history`, which the tonemap reads instead of the frame until the view moves again.
+ * After `samples` frames it has converged on a supersampled image.
+ */
+pub struct Accumulation {
+    pub samples: u32,
+    pub history: Texture,
+    // the tonemap pass reading `history`, see HdrTarget::tonemap_from
+    pub tonemap_bind_group: BindGroup,
+    // frames averaged into `history` so far
+    accumulated: Cell<u32>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl Accumulation {
+    pub fn new(device: &Device, hdr: &HdrTarget, width: u32, height: u32, samples: u32) -> Self {
+        let history = Texture::render_target(
+            device,
+            "Accumulation History Texture",
+            HDR_FORMAT,
+            width,
+            height,
+            1,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        );
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Accumulation Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Accumulation Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&hdr.resolved.view),
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Accumulation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Accumulation Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../accumulate.wgsl"))),
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Accumulation Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_accumulate",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_accumulate",
+                targets: &[ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(BlendState {
+                        color: RUNNING_AVERAGE,
+                        alpha: RUNNING_AVERAGE,
+                    }),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+        });
+        let tonemap_bind_group = hdr.source_bind_group(device, &history.view);
+
+        Self {
+            samples: samples.max(1),
+            history,
+            tonemap_bind_group,
+            accumulated: Cell::new(0),
+            bind_group,
+            pipeline,
+        }
+    }
+
+    // starts over, for when the view changed
+    pub fn reset(&self) {
+        self.accumulated.set(0);
+    }
+
+    pub fn accumulated(&self) -> u32 {
+        self.accumulated.get()
+    }
+
+    pub fn converged(&self) -> bool {
+        self.accumulated.get() >= self.samples
+    }
+
+    // in pixels, where within the pixel the next frame samples, from the Halton (2, 3) sequence
+    pub fn jitter(&self) -> (f32, f32) {
+        let index = self.accumulated.get() + 1;
+        (halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    // averages the frame HdrTarget::resolved holds into `history`
+    pub fn accumulate(&self, encoder: &mut CommandEncoder) {
+        let count = self.accumulated.get();
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Accumulation Pass"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: &self.history.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        // the first frame gets all the weight, replacing whatever was there before the reset
+        let weight = 1.0 / (count + 1) as f64;
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_blend_constant(Color {
+            r: weight,
+            g: weight,
+            b: weight,
+            a: weight,
+        });
+        rpass.draw(0..3, 0..1);
+        self.accumulated.set(count + 1);
+    }
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
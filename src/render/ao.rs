@@ -0,0 +1,104 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+use rayon::prelude::*;
+use std::{f32::consts::PI, mem};
+use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+use super::{bvh::Bvh, scene, SceneObject};
+
+// vertex buffer slot the baked occlusion is bound to, after heatmap::SCALAR_SLOT
+pub const AO_SLOT: u32 = 2;
+// fractions of the scene's bounding radius: how far away geometry still occludes, and
+// how far off the surface rays start so a vertex doesn't occlude itself
+const AO_DISTANCE: f32 = 0.5;
+const AO_BIAS: f32 = 1e-4;
+
+const AO_ATTRIBUTES: [VertexAttribute; 1] = wgpu::vertex_attr_array![4 => Float32];
+
+// one f32 per vertex, like heatmap::scalar_layout
+pub fn ao_layout<'a>() -> VertexBufferLayout<'a> {
+    VertexBufferLayout {
+        array_stride: mem::size_of::<f32>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &AO_ATTRIBUTES,
+    }
+}
+
+// van der Corput sequence, spreads the sample angles evenly whatever the count
+fn radical_inverse(i: u32) -> f32 {
+    i.reverse_bits() as f32 / 4_294_967_296.0
+}
+
+// sample `i` of `n`, cosine weighted around +Z and turned by `rotation` of a full turn
+fn hemisphere_sample(i: u32, n: u32, rotation: f32) -> Vector3<f32> {
+    let u = (i as f32 + 0.5) / n as f32;
+    let phi = 2.0 * PI * (radical_inverse(i) + rotation).fract();
+    let r = u.sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), (1.0 - u).sqrt())
+}
+
+fn tangent_basis(normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let reference = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let tangent = normal.cross(reference).normalize();
+    (tangent, normal.cross(tangent))
+}
+
+/*
+ * Per-vertex ambient occlusion: the fraction of `samples` rays over each vertex's normal
+ * that get away without hitting anything, every object occluding every other one. The
+ * vertices are spread over the rayon pool and the sample pattern is fixed, so the same
+ * scene always bakes the same. Morph targets are left out.
+ */
+pub fn bake(objects: &[SceneObject], samples: u32) -> Vec<Vec<f32>> {
+    let radius = match scene::combined_bounds(objects) {
+        Some(bounds) => bounds.radius().max(f32::EPSILON),
+        None => return objects.iter().map(|_| Vec::new()).collect(),
+    };
+    let triangles = objects
+        .iter()
+        .flat_map(|object| {
+            let data = &object.mesh.data;
+            let position = move |index: u32| Point3::from(data.vertices[index as usize].position);
+            data.triangles()
+                .into_iter()
+                .map(move |[a, b, c]| [position(a), position(b), position(c)])
+        })
+        .collect();
+    let bvh = Bvh::new(triangles);
+    let samples = samples.max(1);
+    let (distance, bias) = (radius * AO_DISTANCE, radius * AO_BIAS);
+    objects
+        .iter()
+        .map(|object| {
+            object
+                .mesh
+                .data
+                .vertices
+                .par_iter()
+                .enumerate()
+                .map(|(index, vertex)| {
+                    let normal = Vector3::from(vertex.normal);
+                    if normal.magnitude2() == 0.0 {
+                        return 1.0;
+                    }
+                    let normal = normal.normalize();
+                    let (tangent, bitangent) = tangent_basis(normal);
+                    let origin = Point3::from(vertex.position) + normal * bias;
+                    // golden ratio turns keep neighbouring vertices from banding together
+                    let rotation = (index as f32 * 0.618_034).fract();
+                    let escaped = (0..samples)
+                        .filter(|&i| {
+                            let s = hemisphere_sample(i, samples, rotation);
+                            let direction = tangent * s.x + bitangent * s.y + normal * s.z;
+                            !bvh.occluded(origin, direction, distance)
+                        })
+                        .count();
+                    escaped as f32 / samples as f32
+                })
+                .collect()
+        })
+        .collect()
+}
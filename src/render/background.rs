@@ -0,0 +1,189 @@
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::texture::{Texture, DEPTH_FORMAT};
+
+// what happens when the image and the window have different aspect ratios
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackgroundFit {
+    // the whole image shows, with bars of the clear colour on two sides
+    Letterbox,
+    // the image fills the window, cutting off two sides
+    Crop,
+}
+
+impl Default for BackgroundFit {
+    fn default() -> Self {
+        BackgroundFit::Letterbox
+    }
+}
+
+/*
+ * A flat image behind the scene that doesn't move with the camera. The pixels are shown
+ * as authored: the texture decodes sRGB only when the target encodes it again on write,
+ * so the colour space setting doesn't apply. An HDR target still tonemaps it.
+ */
+pub struct Background {
+    pub fit: BackgroundFit,
+    // width over height of the image
+    pub aspect_ratio: f32,
+    pub texture: Texture,
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl Background {
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Background Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<[f32; 2]>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        image: &image::RgbaImage,
+        target_srgb: bool,
+        fit: BackgroundFit,
+    ) -> Self {
+        let format = if target_srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        let texture = Texture::from_rgba(device, queue, "Background Texture", image, format);
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Background Uniform Buffer"),
+            contents: bytemuck::bytes_of(&[1.0f32, 1.0]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Background Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        let (width, height) = image.dimensions();
+
+        Self {
+            fit,
+            aspect_ratio: width as f32 / height.max(1) as f32,
+            texture,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // how much of the image one screen width and height covers, above 1 letterboxes
+    pub fn uv_scale(&self, target_aspect_ratio: f32) -> [f32; 2] {
+        let ratio = target_aspect_ratio / self.aspect_ratio;
+        let wider = ratio > 1.0;
+        match (self.fit, wider) {
+            (BackgroundFit::Letterbox, true) | (BackgroundFit::Crop, false) => [ratio, 1.0],
+            (BackgroundFit::Letterbox, false) | (BackgroundFit::Crop, true) => [1.0, 1.0 / ratio],
+        }
+    }
+
+    pub fn write_uniforms(&self, queue: &Queue, target_aspect_ratio: f32) {
+        let scale = self.uv_scale(target_aspect_ratio);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&scale));
+    }
+
+    pub fn draw<'a>(&'a self, pipeline: &'a RenderPipeline, rpass: &mut RenderPass<'a>) {
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    pub fn create_pipeline(
+        device: &Device,
+        layout: &BindGroupLayout,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Background Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../background.wgsl"))),
+        });
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Background Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_background",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            // shares the scene pass, so it has to declare the depth target without touching it
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_background",
+                targets: &[format.into()],
+            }),
+        })
+    }
+}
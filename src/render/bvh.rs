@@ -0,0 +1,131 @@
+use cgmath::{Point3, Vector3};
+use std::cmp::Ordering;
+
+use super::{mesh::intersect_triangle, Aabb};
+
+// triangles per leaf, past this a node gets split in two
+const LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    // a leaf's triangles start here, an inner node's children sit at start and start + 1
+    start: usize,
+    // zero for inner nodes
+    count: usize,
+}
+
+/*
+ * Bounding volume hierarchy over a triangle soup, for casting lots of rays at once.
+ * Built by sorting on the longest axis and splitting at the median, which is cheap to
+ * build and plenty for occlusion queries.
+ */
+pub struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<[Point3<f32>; 3]>,
+}
+
+impl Bvh {
+    pub fn new(triangles: Vec<[Point3<f32>; 3]>) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            triangles,
+        };
+        let count = bvh.triangles.len();
+        if count > 0 {
+            let origin = Point3::new(0.0, 0.0, 0.0);
+            bvh.nodes.push(Node {
+                bounds: Aabb {
+                    min: origin,
+                    max: origin,
+                },
+                start: 0,
+                count: 0,
+            });
+            bvh.build(0, 0, count);
+        }
+        bvh
+    }
+
+    fn build(&mut self, node: usize, start: usize, count: usize) {
+        let triangles = &mut self.triangles[start..start + count];
+        let bounds = Aabb::from_points(triangles.iter().flat_map(|t| t.iter().copied())).unwrap();
+        if count <= LEAF_SIZE {
+            self.nodes[node] = Node {
+                bounds,
+                start,
+                count,
+            };
+            return;
+        }
+        let extent = bounds.max - bounds.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let centroid = |t: &[Point3<f32>; 3]| t[0][axis] + t[1][axis] + t[2][axis];
+        triangles.sort_unstable_by(|a, b| {
+            centroid(a)
+                .partial_cmp(&centroid(b))
+                .unwrap_or(Ordering::Equal)
+        });
+        let left = self.nodes.len();
+        let placeholder = self.nodes[node];
+        self.nodes.extend_from_slice(&[placeholder, placeholder]);
+        self.nodes[node] = Node {
+            bounds,
+            start: left,
+            count: 0,
+        };
+        let half = count / 2;
+        self.build(left, start, half);
+        self.build(left + 1, start + half, count - half);
+    }
+
+    // whether the ray hits anything closer than `max_distance`, with a unit `direction`
+    pub fn occluded(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inverse = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !hits_box(&node.bounds, origin, inverse, max_distance) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend_from_slice(&[node.start, node.start + 1]);
+                continue;
+            }
+            let triangles = &self.triangles[node.start..node.start + node.count];
+            let hit = triangles.iter().any(|&triangle| {
+                intersect_triangle(triangle, origin, direction).map_or(false, |t| t < max_distance)
+            });
+            if hit {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// slab test against the part of the ray from the origin out to `max_distance`
+fn hits_box(bounds: &Aabb, origin: Point3<f32>, inverse: Vector3<f32>, max_distance: f32) -> bool {
+    let (mut near, mut far) = (0.0f32, max_distance);
+    for axis in 0..3 {
+        let t0 = (bounds.min[axis] - origin[axis]) * inverse[axis];
+        let t1 = (bounds.max[axis] - origin[axis]) * inverse[axis];
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    near <= far
+}
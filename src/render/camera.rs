@@ -0,0 +1,386 @@
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4};
+
+use super::{Aabb, Axis};
+
+#[rustfmt::skip]
+pub(crate) const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+// how much empty space to leave around framed geometry
+const FRAMING_MARGIN: f32 = 1.1;
+// orthographic framing fits the silhouette, so it can get away with much less
+const TIGHT_FRAMING_MARGIN: f32 = 1.02;
+// the adaptive near plane never gets closer than this fraction of the far plane
+const MIN_NEAR_RATIO: f32 = 1e-4;
+// keeps orbiting from flipping the view over the poles
+const MAX_ORBIT_PITCH: Deg<f32> = Deg(89.0);
+const DEFAULT_SNAP_ANGLE: Deg<f32> = Deg(15.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraProjection {
+    Perspective {
+        yfov: Rad<f32>,
+        znear: f32,
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+// a camera authored in a model file, already placed in world space
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraDef {
+    pub name: Option<String>,
+    pub eye: Point3<f32>,
+    pub forward: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: CameraProjection,
+}
+
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub fovy: Deg<f32>,
+    pub znear: f32,
+    pub zfar: f32,
+    // remap GL's [-1, 1] clip depth to wgpu's [0, 1]
+    pub depth_correction: bool,
+    // pull the near plane in as the eye approaches the framed bounds
+    pub adaptive_near: bool,
+    pub bounds: Option<Aabb>,
+    // orthographic with this half height of the view in world units, perspective while None
+    pub ortho_height: Option<f32>,
+    // increment snapped orbits round yaw and pitch to, see `set_orbit_angles`
+    pub snap_angle: Option<Deg<f32>>,
+    // multipliers on how far mouse input moves the camera, see `set_sensitivity`
+    pub orbit_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    // flip horizontal and vertical drags, see `drag_delta`
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl Camera {
+    // only worth turning off when checking whether a depth artifact comes from the remap
+    pub fn apply_depth_correction(&mut self, enabled: bool) {
+        self.depth_correction = enabled;
+    }
+
+    pub fn set_adaptive_near(&mut self, enabled: bool) {
+        self.adaptive_near = enabled;
+    }
+
+    // None turns snapping off even where it's asked for
+    pub fn set_snap_angle(&mut self, angle: Option<Deg<f32>>) {
+        self.snap_angle = angle.filter(|angle| angle.0 > 0.0);
+    }
+
+    // 1 is the default for each, negative values are clamped to 0
+    pub fn set_sensitivity(&mut self, orbit: f32, pan: f32, zoom: f32) {
+        self.orbit_sensitivity = orbit.max(0.0);
+        self.pan_sensitivity = pan.max(0.0);
+        self.zoom_sensitivity = zoom.max(0.0);
+    }
+
+    pub fn set_invert(&mut self, x: bool, y: bool) {
+        self.invert_x = x;
+        self.invert_y = y;
+    }
+
+    // a mouse movement in pixels with the invert toggles applied, for orbit and pan handlers
+    pub fn drag_delta(&self, delta: (f32, f32)) -> (f32, f32) {
+        let flip = |inverted: bool| if inverted { -1.0 } else { 1.0 };
+        (delta.0 * flip(self.invert_x), delta.1 * flip(self.invert_y))
+    }
+
+    // the directions yaw 0 and yaw 90 point in, and the pole orbits turn around
+    fn orbit_basis(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        let up = self.up.normalize();
+        let reference = if up.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        };
+        let zero = (reference - up * reference.dot(up)).normalize();
+        (zero, up.cross(zero), up)
+    }
+
+    /*
+     * Turns `up` to the axis and the eye to the three-quarter front view the default camera
+     * starts with, keeping its distance from the target. Front is -Y with Z up, +Z with Y
+     * up like glTF, and -Z with X up.
+     */
+    pub fn look_from_home(&mut self, up: Axis) {
+        let (right, front) = match up {
+            Axis::X => (Vector3::unit_y(), -Vector3::unit_z()),
+            Axis::Y => (Vector3::unit_x(), Vector3::unit_z()),
+            Axis::Z => (Vector3::unit_x(), -Vector3::unit_y()),
+        };
+        let distance = (self.eye - self.target).magnitude();
+        let direction = (right * 1.5 + front * 5.0 + up.unit() * 3.0).normalize();
+        self.up = up.unit();
+        self.eye = self.target + direction * distance;
+    }
+
+    // yaw around `up` and pitch above the horizon of the eye as seen from the target
+    pub fn orbit_angles(&self) -> (Rad<f32>, Rad<f32>) {
+        let (zero, quarter, up) = self.orbit_basis();
+        let offset = (self.eye - self.target).normalize();
+        let yaw = offset.dot(quarter).atan2(offset.dot(zero));
+        let pitch = offset.dot(up).max(-1.0).min(1.0).asin();
+        (Rad(yaw), Rad(pitch))
+    }
+
+    /*
+     * Moves the eye to these angles around the target, keeping its distance. With `snap`
+     * both get rounded to the nearest multiple of the snap angle, so callers should keep
+     * accumulating the unsnapped angles or small movements never get anywhere.
+     */
+    pub fn set_orbit_angles(&mut self, yaw: Rad<f32>, pitch: Rad<f32>, snap: bool) {
+        let round = |angle: Rad<f32>| match self.snap_angle {
+            Some(step) if snap => {
+                let step = Rad::from(step).0;
+                Rad((angle.0 / step).round() * step)
+            }
+            _ => angle,
+        };
+        let limit = Rad::from(MAX_ORBIT_PITCH).0;
+        let (yaw, pitch) = (round(yaw).0, round(pitch).0.max(-limit).min(limit));
+        let (zero, quarter, up) = self.orbit_basis();
+        let distance = (self.eye - self.target).magnitude();
+        let horizontal = zero * yaw.cos() + quarter * yaw.sin();
+        self.eye = self.target + (horizontal * pitch.cos() + up * pitch.sin()) * distance;
+    }
+
+    /*
+     * Turns the eye `steps` increments of the snap angle around the pole, landing exactly on
+     * a multiple of it, so repeated presses give repeatable views. Pitch is left alone.
+     */
+    pub fn step_orbit(&mut self, steps: i32) {
+        let step = Rad::from(self.snap_angle.unwrap_or(DEFAULT_SNAP_ANGLE)).0;
+        let (yaw, pitch) = self.orbit_angles();
+        let yaw = ((yaw.0 / step).round() + steps as f32) * step;
+        self.set_orbit_angles(Rad(yaw), pitch, false);
+    }
+
+    // the near and far distances actually used for projection
+    pub fn clip_range(&self) -> (f32, f32) {
+        match self.bounds {
+            Some(bounds) if self.adaptive_near => {
+                let far = (self.eye - bounds.center()).magnitude() + bounds.radius();
+                // leave some slack so faces right in front of the eye don't get cut
+                let near = (bounds.distance_to(self.eye) * 0.5).max(far * MIN_NEAR_RATIO);
+                (near, far)
+            }
+            _ => (self.znear, self.zfar),
+        }
+    }
+
+    /*
+     * Switches projection without the target's depth changing size on screen: the
+     * orthographic half height is what the field of view spans at the target's distance,
+     * and going back the eye moves to where the field of view spans the half height again,
+     * taking the clip range along.
+     */
+    pub fn set_orthographic(&mut self, enabled: bool) {
+        let tan = (Rad::from(self.fovy).0 / 2.0).tan();
+        let offset = self.eye - self.target;
+        match (enabled, self.ortho_height) {
+            (true, None) => {
+                self.ortho_height = Some((offset.magnitude() * tan).max(f32::EPSILON));
+            }
+            (false, Some(half_height)) => {
+                let distance = half_height / tan;
+                let shift = distance - offset.magnitude();
+                self.eye = self.target + offset.normalize() * distance;
+                self.znear = (self.znear + shift).max(distance * 0.01);
+                self.zfar += shift;
+                self.ortho_height = None;
+            }
+            _ => {}
+        }
+    }
+
+    /*
+     * How far the target is from the eye, or orthographically how far it would have to be
+     * for the field of view to span as much, for things that scale with the zoom.
+     */
+    pub fn view_distance(&self) -> f32 {
+        match self.ortho_height {
+            Some(half_height) => half_height / (Rad::from(self.fovy).0 / 2.0).tan(),
+            None => (self.eye - self.target).magnitude(),
+        }
+    }
+
+    /*
+     * Moves the eye along the view direction, factors below 1 move closer. Orthographically
+     * moving doesn't change the size on screen, so the half height scales instead.
+     */
+    pub fn dolly(&mut self, factor: f32) {
+        match self.ortho_height.as_mut() {
+            Some(half_height) => *half_height = (*half_height * factor).max(f32::EPSILON),
+            None => self.eye = self.target + (self.eye - self.target) * factor,
+        }
+    }
+
+    /*
+     * Slides eye and target together across the view by `delta` pixels of a viewport
+     * `height` pixels tall, times the pan sensitivity. At 1 whatever sits at the target's
+     * depth follows the cursor.
+     */
+    pub fn pan(&mut self, delta: (f32, f32), height: f32) {
+        let offset = self.eye - self.target;
+        let forward = -offset.normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        let view_height = match self.ortho_height {
+            Some(h) => 2.0 * h,
+            None => 2.0 * offset.magnitude() * (Rad::from(self.fovy).0 / 2.0).tan(),
+        };
+        let scale = view_height / height.max(1.0) * self.pan_sensitivity;
+        // dragging right moves the scene right, so the camera goes left
+        let shift = (up * delta.1 - right * delta.0) * scale;
+        self.eye += shift;
+        self.target += shift;
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Matrix4<f32> {
+        let (znear, zfar) = self.clip_range();
+        let mx_projection = match self.ortho_height {
+            Some(h) => {
+                let w = h * aspect_ratio;
+                cgmath::ortho(-w, w, -h, h, znear, zfar)
+            }
+            None => cgmath::perspective(self.fovy, aspect_ratio, znear, zfar),
+        };
+        if self.depth_correction {
+            OPENGL_TO_WGPU_MATRIX * mx_projection
+        } else {
+            mx_projection
+        }
+    }
+
+    pub fn view_projection_matrix(&self, aspect_ratio: f32) -> Matrix4<f32> {
+        self.projection_matrix(aspect_ratio) * self.view_matrix()
+    }
+
+    /*
+     * World space ray through a pixel, `screen` measured from the top left of a `viewport`
+     * sized window. Returns the point on the near plane and the unit direction away from it.
+     */
+    pub fn ray_from_screen(
+        &self,
+        screen: (f32, f32),
+        viewport: (u32, u32),
+    ) -> (Point3<f32>, Vector3<f32>) {
+        let (width, height) = (viewport.0.max(1) as f32, viewport.1.max(1) as f32);
+        let x = screen.0 / width * 2.0 - 1.0;
+        let y = 1.0 - screen.1 / height * 2.0;
+        let inverse = self
+            .view_projection_matrix(width / height)
+            .invert()
+            .unwrap_or_else(Matrix4::identity);
+        let near_depth = if self.depth_correction { 0.0 } else { -1.0 };
+        let unproject = |depth: f32| {
+            let p = inverse * Vector4::new(x, y, depth, 1.0);
+            Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+        };
+        let near = unproject(near_depth);
+        let far = unproject(1.0);
+        (near, (far - near).normalize())
+    }
+
+    // the authored clip range is kept as is until the camera frames something again
+    pub fn apply_def(&mut self, def: &CameraDef) {
+        self.bounds = None;
+        self.eye = def.eye;
+        self.target = def.eye + def.forward;
+        self.up = def.up;
+        match def.projection {
+            CameraProjection::Perspective { yfov, znear, zfar } => {
+                self.fovy = yfov.into();
+                self.ortho_height = None;
+                self.znear = znear;
+                // glTF allows an infinite far plane, approximate it
+                self.zfar = zfar.unwrap_or(znear * 10000.0);
+            }
+            CameraProjection::Orthographic {
+                ymag, znear, zfar, ..
+            } => {
+                // the aspect ratio comes from the window, so xmag is left out
+                self.ortho_height = Some(ymag);
+                self.znear = znear.max(f32::EPSILON);
+                self.zfar = zfar;
+            }
+        }
+    }
+
+    // keeps the current viewing direction and backs off until the bounds fit on screen
+    pub fn frame(&mut self, bounds: &Aabb) {
+        let radius = bounds.radius().max(f32::EPSILON);
+        let direction = (self.eye - self.target).normalize();
+        let half_fovy: cgmath::Rad<f32> = (self.fovy / 2.0).into();
+        let distance = radius * FRAMING_MARGIN / half_fovy.0.sin();
+        self.target = bounds.center();
+        self.eye = self.target + direction * distance;
+        self.znear = (distance - radius).max(distance * 0.01);
+        self.zfar = distance + radius;
+        self.bounds = Some(*bounds);
+    }
+
+    /*
+     * Switches to an orthographic projection fitted tightly around the box's corners as
+     * seen from the current direction, rather than around its bounding sphere.
+     */
+    pub fn frame_orthographic(&mut self, bounds: &Aabb, aspect_ratio: f32) {
+        self.frame(bounds);
+        let forward = (self.target - self.eye).normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        let (mut half_width, mut half_height) = (0.0f32, 0.0f32);
+        for corner in bounds.corners().iter() {
+            let offset = corner - self.target;
+            half_width = half_width.max(offset.dot(right).abs());
+            half_height = half_height.max(offset.dot(up).abs());
+        }
+        let half_height = half_height.max(half_width / aspect_ratio.max(f32::EPSILON));
+        self.ortho_height = Some((half_height * TIGHT_FRAMING_MARGIN).max(f32::EPSILON));
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: Point3::new(1.5, -5.0, 3.0),
+            target: Point3::new(0.0, 0.0, 0.0),
+            up: Vector3::unit_z(),
+            fovy: Deg(45.0),
+            znear: 1.0,
+            zfar: 10.0,
+            depth_correction: true,
+            adaptive_near: true,
+            bounds: None,
+            ortho_height: None,
+            snap_angle: Some(DEFAULT_SNAP_ANGLE),
+            orbit_sensitivity: 1.0,
+            pan_sensitivity: 1.0,
+            zoom_sensitivity: 1.0,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use wgpu::{Adapter, DownlevelFlags, Features};
+
+use super::hdr::SUPPORTED_SAMPLE_COUNTS;
+
+/*
+ * What the device can do of the things that depend on it, see Renderer::capabilities, so
+ * options can be greyed out up front rather than falling back with a warning.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capabilities {
+    // PolygonMode::Line and Point for custom pipelines, the wireframe overlay doesn't need it
+    pub polygon_mode_line: bool,
+    // the highest sample count Renderer::set_hdr takes
+    pub max_msaa: u32,
+    // the largest anisotropy_clamp samplers can ask for, 1 without anisotropic filtering
+    pub max_anisotropy: u8,
+    // GPU pass timings in the stats
+    pub timestamp_query: bool,
+    // KHR_draco_mesh_compression, which the glTF loader can't decode
+    pub draco: bool,
+}
+
+impl Capabilities {
+    // `features` are the ones the device got, the adapter may support more
+    pub fn new(adapter: &Adapter, features: Features) -> Self {
+        let downlevel = adapter.get_downlevel_properties().flags;
+        Self {
+            polygon_mode_line: features.contains(Features::NON_FILL_POLYGON_MODE),
+            max_msaa: SUPPORTED_SAMPLE_COUNTS.iter().copied().max().unwrap_or(1),
+            max_anisotropy: if downlevel.contains(DownlevelFlags::ANISOTROPIC_FILTERING) {
+                16
+            } else {
+                1
+            },
+            timestamp_query: features.contains(Features::TIMESTAMP_QUERY),
+            draco: false,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{num::NonZeroU32, path::Path};
+use wgpu::*;
+
+// the frame against a reference image, see Renderer::compare_to_reference
+pub struct DiffReport {
+    // pixels where some channel differs by more than the tolerance
+    pub mismatched_pixels: usize,
+    pub max_channel_diff: u8,
+    // mismatches in red over a dimmed copy of the frame
+    pub diff_image: image::RgbaImage,
+}
+
+impl DiffReport {
+    pub fn matches(&self) -> bool {
+        self.mismatched_pixels == 0
+    }
+
+    pub fn write_diff_image(&self, path: &Path) -> image::ImageResult<()> {
+        self.diff_image.save(path)
+    }
+}
+
+// images of different sizes count every pixel of the larger one as mismatched
+pub fn diff(frame: &image::RgbaImage, reference: &image::RgbaImage, tolerance: u8) -> DiffReport {
+    if frame.dimensions() != reference.dimensions() {
+        let (width, height) = (
+            frame.width().max(reference.width()),
+            frame.height().max(reference.height()),
+        );
+        return DiffReport {
+            mismatched_pixels: (width * height) as usize,
+            max_channel_diff: u8::MAX,
+            diff_image: image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255])),
+        };
+    }
+    let mut mismatched_pixels = 0;
+    let mut max_channel_diff = 0;
+    let diff_image = image::RgbaImage::from_fn(frame.width(), frame.height(), |x, y| {
+        let (a, b) = (frame.get_pixel(x, y), reference.get_pixel(x, y));
+        let diff =
+            a.0.iter()
+                .zip(b.0.iter())
+                .map(|(&a, &b)| (a as i16 - b as i16).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0);
+        max_channel_diff = max_channel_diff.max(diff);
+        if diff > tolerance {
+            mismatched_pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            image::Rgba([a[0] / 4, a[1] / 4, a[2] / 4, 255])
+        }
+    });
+    DiffReport {
+        mismatched_pixels,
+        max_channel_diff,
+        diff_image,
+    }
+}
+
+// blocks until the copy is done, only 8-bit four channel formats are handled
+pub fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &wgpu::Texture,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+) -> image::RgbaImage {
+    assert_eq!(
+        format.describe().block_size,
+        4,
+        "can't read back {:?}",
+        format
+    );
+    let unpadded_row = 4 * width;
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_row = (unpadded_row + align - 1) / align * align;
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: (padded_row * height) as BufferAddress,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Capture Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row),
+                rows_per_image: NonZeroU32::new(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+    let slice = buffer.slice(..);
+    let mapping = slice.map_async(MapMode::Read);
+    device.poll(Maintain::Wait);
+    pollster::block_on(mapping).expect("Failed to map the capture buffer.");
+    let bgra = matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    );
+    let mut pixels = Vec::with_capacity((unpadded_row * height) as usize);
+    for row in slice.get_mapped_range().chunks(padded_row as usize) {
+        for pixel in row[..unpadded_row as usize].chunks_exact(4) {
+            if bgra {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+    }
+    buffer.unmap();
+    image::RgbaImage::from_raw(width, height, pixels).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_counts_pixels_past_the_tolerance() {
+        let reference = image::RgbaImage::from_pixel(2, 2, image::Rgba([100, 100, 100, 255]));
+        let mut frame = reference.clone();
+        // within tolerance, just past it, and far off on one channel
+        frame.put_pixel(0, 0, image::Rgba([102, 100, 100, 255]));
+        frame.put_pixel(1, 0, image::Rgba([100, 103, 100, 255]));
+        frame.put_pixel(0, 1, image::Rgba([100, 100, 40, 255]));
+        let report = diff(&frame, &reference, 2);
+        assert_eq!(report.mismatched_pixels, 2);
+        assert_eq!(report.max_channel_diff, 60);
+        assert!(!report.matches());
+        assert_eq!(
+            report.diff_image.get_pixel(1, 0),
+            &image::Rgba([255, 0, 0, 255])
+        );
+        assert_eq!(
+            report.diff_image.get_pixel(1, 1),
+            &image::Rgba([25, 25, 25, 255])
+        );
+    }
+
+    #[test]
+    fn diff_of_identical_images_matches() {
+        let image = image::RgbaImage::from_pixel(3, 1, image::Rgba([10, 20, 30, 40]));
+        let report = diff(&image, &image, 0);
+        assert!(report.matches());
+        assert_eq!(report.max_channel_diff, 0);
+    }
+
+    #[test]
+    fn diff_of_different_sizes_mismatches_everything() {
+        let frame = image::RgbaImage::new(2, 1);
+        let reference = image::RgbaImage::new(1, 3);
+        let report = diff(&frame, &reference, u8::MAX);
+        assert_eq!(report.mismatched_pixels, 6);
+        assert_eq!(report.diff_image.dimensions(), (2, 3));
+    }
+}
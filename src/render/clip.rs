@@ -0,0 +1,78 @@
+use cgmath::{InnerSpace, Vector3};
+
+pub const MAX_CLIP_PLANES: usize = 4;
+
+/*
+ * A world-space plane satisfying dot(normal, p) == distance.
+ * Everything on the side the normal points towards is clipped away.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<f32>, distance: f32) -> Self {
+        Self {
+            normal: normal.normalize(),
+            distance,
+        }
+    }
+
+    // moves the plane along its own normal
+    pub fn translate(&mut self, amount: f32) {
+        self.distance += amount;
+    }
+
+    pub fn as_vec4(plane: Option<&Plane>) -> [f32; 4] {
+        match plane {
+            Some(plane) => [
+                plane.normal.x,
+                plane.normal.y,
+                plane.normal.z,
+                plane.distance,
+            ],
+            // a zero normal never satisfies dot(n, p) > 1, so nothing is clipped
+            None => [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn unit(self) -> Vector3<f32> {
+        match self {
+            Axis::X => Vector3::unit_x(),
+            Axis::Y => Vector3::unit_y(),
+            Axis::Z => Vector3::unit_z(),
+        }
+    }
+}
+
+// keeps only the slab `thickness` wide centred at `position` along `axis`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slice {
+    pub axis: Axis,
+    pub position: f32,
+    pub thickness: f32,
+}
+
+impl Slice {
+    // xyz: slab normal, w: centre along it, and the half width. a negative one keeps everything
+    pub fn as_uniform(slice: Option<&Slice>) -> ([f32; 4], f32) {
+        match slice {
+            Some(slice) => (
+                slice.axis.unit().extend(slice.position).into(),
+                slice.thickness.max(0.0) / 2.0,
+            ),
+            None => ([0.0; 4], -1.0),
+        }
+    }
+}
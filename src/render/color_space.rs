@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use wgpu::TextureFormat;
+
+// the encoding colour textures are read with and the final image is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+    Rec709,
+}
+
+impl ColorSpace {
+    // must match the COLOR_SPACE_* constants in shader.wgsl
+    pub fn shader_id(self) -> u32 {
+        match self {
+            ColorSpace::Linear => 0,
+            ColorSpace::Srgb => 1,
+            ColorSpace::Rec709 => 2,
+        }
+    }
+
+    // Rec.709 material is decoded with the sRGB curve, the two only differ near black
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            ColorSpace::Linear => TextureFormat::Rgba8Unorm,
+            ColorSpace::Srgb | ColorSpace::Rec709 => TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::Srgb
+    }
+}
@@ -0,0 +1,77 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+// bind group index custom WGSL reads its uniforms from
+pub const CUSTOM_UNIFORM_GROUP: u32 = 2;
+
+/*
+ * Raw uniform bytes supplied by the user for a custom shader, bound at
+ * CUSTOM_UNIFORM_GROUP binding 0. The layout doesn't fix a size, so the buffer
+ * is only recreated when the data grows or shrinks. The built-in shader ignores it.
+ */
+pub struct CustomUniforms {
+    size: u64,
+    buffer: Buffer,
+    pub bind_group: BindGroup,
+}
+
+// uniform structs are 16 byte aligned in WGSL
+fn padded(data: &[u8]) -> Vec<u8> {
+    let mut bytes = data.to_vec();
+    let len = ((bytes.len() + 15) / 16).max(1) * 16;
+    bytes.resize(len, 0);
+    bytes
+}
+
+impl CustomUniforms {
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Custom Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+    }
+
+    pub fn new(device: &Device, layout: &BindGroupLayout, data: &[u8]) -> Self {
+        let bytes = padded(data);
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Custom Uniform Buffer"),
+            contents: &bytes,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Custom Bind Group"),
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            size: bytes.len() as u64,
+            buffer,
+            bind_group,
+        }
+    }
+
+    // returns false when the size changed, the caller then needs a new CustomUniforms
+    pub fn write(&self, queue: &Queue, data: &[u8]) -> bool {
+        let bytes = padded(data);
+        if bytes.len() as u64 != self.size {
+            return false;
+        }
+        queue.write_buffer(&self.buffer, 0, &bytes);
+        true
+    }
+}
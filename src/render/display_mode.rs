@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+// what DisplayMode::next starts DisplayMode::FlatColor out with
+pub const DEFAULT_FLAT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// and DisplayMode::FeatureEdges with this crease angle, in degrees
+pub const DEFAULT_CREASE_ANGLE: f32 = 30.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DisplayMode {
+    Default,
+    Matcap,
+    UVChecker,
+    // highlights creases and dents from how fast the normal turns across the screen
+    Curvature,
+    // per-vertex data from Renderer::set_vertex_scalars through a ColorRamp
+    Heatmap,
+    // xyz mapped to rgb, in the space picked with Renderer::set_normal_space
+    Normals,
+    // faceted, each triangle shaded with a normal the fragment shader derives from
+    // screen space derivatives of the position, whatever normals the mesh has
+    FlatDerived,
+    // one material input on its own and unlit, see MaterialFactors
+    Channel(MaterialChannel),
+    // everything in one unlit linear rgba colour, for looking at the silhouette
+    FlatColor([f32; 4]),
+    // linear view distance in grey, white at the near plane and black at the far one, for
+    // checking depth precision and where the clip range sits
+    Depth,
+    /*
+     * Only the outlines, as lines over surfaces that just fill in depth to hide what's
+     * behind them: silhouettes where a front face meets a back face, creases between
+     * faces turning by more than this many degrees, and boundary edges. Silhouettes follow
+     * the camera, the rest comes from the edges found on load, see EdgeDiagnostics.
+     */
+    FeatureEdges(f32),
+}
+
+/*
+ * Which input DisplayMode::Channel shows. There's no texture pipeline yet, so every channel
+ * is the material's flat factor, what it would be without its map: a textured albedo shows
+ * just the base colour factor, normals stay flat and occlusion white. Sampling the maps is
+ * left for when materials carry textures.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaterialChannel {
+    Albedo,
+    // greyscale
+    Metallic,
+    Roughness,
+    // tangent space mapped to rgb, flat without a normal map
+    Normal,
+    // white without an occlusion map
+    Ao,
+    Emissive,
+}
+
+impl MaterialChannel {
+    const ALL: [MaterialChannel; 6] = [
+        MaterialChannel::Albedo,
+        MaterialChannel::Metallic,
+        MaterialChannel::Roughness,
+        MaterialChannel::Normal,
+        MaterialChannel::Ao,
+        MaterialChannel::Emissive,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&channel| channel == self)
+            .unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // offset from DISPLAY_MODE_CHANNEL in shader.wgsl
+    fn shader_id(self) -> u32 {
+        match self {
+            MaterialChannel::Albedo => 0,
+            MaterialChannel::Metallic => 1,
+            MaterialChannel::Roughness => 2,
+            MaterialChannel::Normal => 3,
+            MaterialChannel::Ao => 4,
+            MaterialChannel::Emissive => 5,
+        }
+    }
+}
+
+// which normals DisplayMode::Normals shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalSpace {
+    // absolute orientation, flipped or inconsistent normals stand out
+    World,
+    // facing relative to the camera, back-facing normals stand out
+    View,
+}
+
+impl NormalSpace {
+    // must match the NORMAL_SPACE_* constants in shader.wgsl
+    pub fn shader_id(self) -> u32 {
+        match self {
+            NormalSpace::World => 0,
+            NormalSpace::View => 1,
+        }
+    }
+}
+
+impl Default for NormalSpace {
+    fn default() -> Self {
+        NormalSpace::World
+    }
+}
+
+impl DisplayMode {
+    // the channels are one stop, stepped through with MaterialChannel::next
+    const ALL: [DisplayMode; 11] = [
+        DisplayMode::Default,
+        DisplayMode::Matcap,
+        DisplayMode::UVChecker,
+        DisplayMode::Curvature,
+        DisplayMode::Heatmap,
+        DisplayMode::Normals,
+        DisplayMode::FlatDerived,
+        DisplayMode::Channel(MaterialChannel::Albedo),
+        DisplayMode::FlatColor(DEFAULT_FLAT_COLOR),
+        DisplayMode::Depth,
+        DisplayMode::FeatureEdges(DEFAULT_CREASE_ANGLE),
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&mode| match (mode, self) {
+                (DisplayMode::Channel(_), DisplayMode::Channel(_)) => true,
+                (DisplayMode::FlatColor(_), DisplayMode::FlatColor(_)) => true,
+                (DisplayMode::FeatureEdges(_), DisplayMode::FeatureEdges(_)) => true,
+                _ => mode == self,
+            })
+            .unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // must match the DISPLAY_MODE_* constants in shader.wgsl, the channels take up the end
+    pub fn shader_id(self) -> u32 {
+        match self {
+            DisplayMode::Default => 0,
+            DisplayMode::Matcap => 1,
+            DisplayMode::UVChecker => 2,
+            DisplayMode::Curvature => 3,
+            DisplayMode::Heatmap => 4,
+            DisplayMode::Normals => 5,
+            DisplayMode::FlatDerived => 6,
+            DisplayMode::FlatColor(_) => 7,
+            DisplayMode::Depth => 8,
+            // its surfaces don't write colour, see Renderer::depth_only_pipelines
+            DisplayMode::FeatureEdges(_) => 0,
+            DisplayMode::Channel(channel) => 9 + channel.shader_id(),
+        }
+    }
+}
+
+impl Default for DisplayMode {
+    fn default() -> Self {
+        DisplayMode::Default
+    }
+}
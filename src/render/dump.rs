@@ -0,0 +1,165 @@
+use cgmath::Vector3;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, Write},
+    mem,
+    path::Path,
+};
+
+use super::{MeshData, SceneObject, Topology, Vertex};
+
+const MAGIC: &[u8; 8] = b"MVBUFFER";
+const VERSION: u32 = 1;
+// Vertex's fields in order, checked on read so a layout change can't be misread
+const VERTEX_LAYOUT: &str = "position:f32x3 normal:f32x3 uv:f32x2";
+
+/*
+ * The geometry exactly as the renderer uploaded it, see Renderer::dump_buffers. Positions
+ * are in render space, `origin` is what was subtracted from them when the scene got rebased.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct BufferDump {
+    pub origin: Vector3<f64>,
+    // name and geometry of every object, in scene order
+    pub objects: Vec<(String, MeshData)>,
+}
+
+impl BufferDump {
+    /*
+     * The companion to Renderer::dump_buffers. Counts are checked against what's left of
+     * the file before anything is allocated for them, so a damaged header is an error
+     * rather than a huge allocation.
+     */
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut input = BufReader::new(file);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a buffer dump"));
+        }
+        if read_u32(&mut input)? != VERSION {
+            return Err(invalid("unsupported buffer dump version"));
+        }
+        let mut origin = [0.0; 3];
+        for c in origin.iter_mut() {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            *c = f64::from_le_bytes(bytes);
+        }
+        if read_string(&mut input, file_len)? != VERTEX_LAYOUT
+            || read_u32(&mut input)? as usize != mem::size_of::<Vertex>()
+        {
+            return Err(invalid("buffer dump has a different vertex layout"));
+        }
+        let floats_per_vertex = mem::size_of::<Vertex>() / mem::size_of::<f32>();
+        let count = read_u32(&mut input)?;
+        let mut objects = Vec::new();
+        for _ in 0..count {
+            let name = read_string(&mut input, file_len)?;
+            let topology = *Topology::ALL
+                .get(read_u32(&mut input)? as usize)
+                .ok_or_else(|| invalid("unknown topology in buffer dump"))?;
+            let vertex_count = read_u32(&mut input)? as usize;
+            let index_count = read_u32(&mut input)? as usize;
+            let needed = (vertex_count as u64 * mem::size_of::<Vertex>() as u64)
+                .saturating_add(index_count as u64 * mem::size_of::<u32>() as u64);
+            ensure_remaining(&mut input, file_len, needed)?;
+            let mut floats = Vec::with_capacity(vertex_count * floats_per_vertex);
+            for _ in 0..vertex_count * floats_per_vertex {
+                floats.push(f32::from_bits(read_u32(&mut input)?));
+            }
+            let mut indices = Vec::with_capacity(index_count);
+            for _ in 0..index_count {
+                indices.push(read_u32(&mut input)?);
+            }
+            let data = MeshData {
+                vertices: bytemuck::cast_slice(&floats).to_vec(),
+                indices,
+                topology,
+                ..Default::default()
+            };
+            objects.push((name, data));
+        }
+        Ok(Self {
+            origin: Vector3::from(origin),
+            objects,
+        })
+    }
+}
+
+/*
+ * Everything little endian:
+ *   magic, version u32, origin f64 x3, layout string, vertex stride u32, object count u32
+ *   then per object: name string, topology u32, vertex count u32, index count u32,
+ *   the vertices as stride sized records, the indices as u32
+ * where a string is a u32 byte length followed by utf-8.
+ */
+pub fn write(path: &Path, objects: &[SceneObject], origin: Vector3<f64>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    for &c in [origin.x, origin.y, origin.z].iter() {
+        out.write_all(&c.to_le_bytes())?;
+    }
+    write_string(&mut out, VERTEX_LAYOUT)?;
+    write_u32(&mut out, mem::size_of::<Vertex>() as u32)?;
+    write_u32(&mut out, objects.len() as u32)?;
+    for object in objects {
+        let data = &object.mesh.data;
+        write_string(&mut out, &object.name)?;
+        write_u32(&mut out, topology_id(data.topology))?;
+        write_u32(&mut out, data.vertices.len() as u32)?;
+        write_u32(&mut out, data.indices.len() as u32)?;
+        let floats: &[f32] = bytemuck::cast_slice(&data.vertices);
+        for f in floats {
+            out.write_all(&f.to_le_bytes())?;
+        }
+        for index in &data.indices {
+            out.write_all(&index.to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+// index into Topology::ALL, which the reader looks it up in
+fn topology_id(topology: Topology) -> u32 {
+    Topology::ALL.iter().position(|&t| t == topology).unwrap() as u32
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u32(out: &mut impl Write, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_string(out: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(out, value.len() as u32)?;
+    out.write_all(value.as_bytes())
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// errors unless at least `needed` more bytes follow in a file `file_len` long
+fn ensure_remaining(input: &mut impl Seek, file_len: u64, needed: u64) -> io::Result<()> {
+    let position = input.stream_position()?;
+    if needed > file_len.saturating_sub(position) {
+        return Err(invalid("buffer dump is shorter than its header says"));
+    }
+    Ok(())
+}
+
+fn read_string(input: &mut (impl Read + Seek), file_len: u64) -> io::Result<String> {
+    let len = read_u32(input)? as usize;
+    ensure_remaining(input, file_len, len as u64)?;
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid("buffer dump string isn't utf-8"))
+}
@@ -0,0 +1,58 @@
+use cgmath::Vector3;
+use std::collections::HashMap;
+
+use super::{face_normal, smooth, MeshData};
+
+// an edge between exactly two triangles, with their face normals for creases and silhouettes
+#[derive(Clone, Copy, Debug)]
+pub struct SharedEdge {
+    pub edge: [u32; 2],
+    // unnormalized, as face_normal gives them
+    pub normals: [Vector3<f32>; 2],
+}
+
+// edges as pairs of vertex indices, sorted out by how many triangles share them
+#[derive(Clone, Debug, Default)]
+pub struct EdgeDiagnostics {
+    // used by a single triangle, a hole or an open border
+    pub boundary: Vec<[u32; 2]>,
+    // shared by more than two triangles
+    pub non_manifold: Vec<[u32; 2]>,
+    // the rest, see DisplayMode::FeatureEdges
+    pub shared: Vec<SharedEdge>,
+}
+
+impl EdgeDiagnostics {
+    pub fn new(data: &MeshData) -> Self {
+        // seams split vertices without splitting the surface, count on welded positions
+        let (group_of, _) = smooth::weld(data);
+        // how many triangles use each edge, and the normals of the first two
+        let mut faces: HashMap<(usize, usize), (u32, [u32; 2], [Vector3<f32>; 2])> = HashMap::new();
+        for tri in data.triangles() {
+            let corner = |i: usize| data.vertices[tri[i] as usize].position;
+            let normal = face_normal(corner(0), corner(1), corner(2));
+            for i in 0..3 {
+                let (a, b) = (tri[i], tri[(i + 1) % 3]);
+                let (ga, gb) = (group_of[a as usize], group_of[b as usize]);
+                if ga == gb {
+                    continue;
+                }
+                let key = (ga.min(gb), ga.max(gb));
+                let (count, _, normals) = faces.entry(key).or_insert((0, [a, b], [normal; 2]));
+                if *count < 2 {
+                    normals[*count as usize] = normal;
+                }
+                *count += 1;
+            }
+        }
+        let mut diagnostics = Self::default();
+        for &(count, edge, normals) in faces.values() {
+            match count {
+                1 => diagnostics.boundary.push(edge),
+                2 => diagnostics.shared.push(SharedEdge { edge, normals }),
+                _ => diagnostics.non_manifold.push(edge),
+            }
+        }
+        diagnostics
+    }
+}
@@ -0,0 +1,36 @@
+use std::{error::Error, fmt};
+use wgpu::RequestDeviceError;
+
+#[derive(Debug)]
+pub enum RendererInitError {
+    NoAdapter,
+    NoSurfaceFormat,
+    RequestDevice(RequestDeviceError),
+}
+
+impl fmt::Display for RendererInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererInitError::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            RendererInitError::NoSurfaceFormat => {
+                write!(f, "no surface format could be configured on this adapter")
+            }
+            RendererInitError::RequestDevice(err) => write!(f, "failed to request device: {}", err),
+        }
+    }
+}
+
+impl Error for RendererInitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RendererInitError::RequestDevice(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<RequestDeviceError> for RendererInitError {
+    fn from(err: RequestDeviceError) -> Self {
+        RendererInitError::RequestDevice(err)
+    }
+}
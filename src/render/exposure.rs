@@ -0,0 +1,157 @@
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::HdrTarget;
+
+// average luminance auto exposure aims for, middle grey
+pub const DEFAULT_EXPOSURE_KEY: f32 = 0.18;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureParams {
+    key: f32,
+    speed: f32,
+    dt: f32,
+    _padding: f32,
+}
+
+/*
+ * Measures the log average luminance of the frame in HdrTarget::resolved with a compute
+ * pass and eases the exposure towards the one bringing that average to `key`. The result
+ * stays on the GPU and is copied over the tonemap's exposure uniform, so the manual
+ * HdrTarget::exposure is only where it starts from.
+ */
+pub struct AutoExposure {
+    pub key: f32,
+    // how quickly it adapts, 1 - exp(-speed * dt) of the way to the wanted exposure per frame
+    pub speed: f32,
+    params_buffer: Buffer,
+    // the exposure so far, carried from frame to frame
+    state_buffer: Buffer,
+    bind_group: BindGroup,
+    pipeline: ComputePipeline,
+}
+
+impl AutoExposure {
+    pub fn new(device: &Device, hdr: &HdrTarget, speed: f32) -> Self {
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Auto Exposure Params Buffer"),
+            contents: bytemuck::bytes_of(&ExposureParams {
+                key: DEFAULT_EXPOSURE_KEY,
+                speed,
+                dt: 0.0,
+                _padding: 0.0,
+            }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let state_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Auto Exposure State Buffer"),
+            contents: bytemuck::bytes_of(&hdr.exposure),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Auto Exposure Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<ExposureParams>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<f32>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Auto Exposure Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: state_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&hdr.resolved.view),
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Auto Exposure Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Auto Exposure Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../exposure.wgsl"))),
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Auto Exposure Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "cs_exposure",
+        });
+
+        Self {
+            key: DEFAULT_EXPOSURE_KEY,
+            speed,
+            params_buffer,
+            state_buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    // `dt` is the simulation time the next frame moves on by, as passed to Renderer::update
+    pub fn write_params(&self, queue: &Queue, dt: f32) {
+        let params = ExposureParams {
+            key: self.key,
+            speed: self.speed,
+            dt,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    // after the scene is resolved and before the tonemap pass
+    pub fn adapt(&self, encoder: &mut CommandEncoder, hdr: &HdrTarget) {
+        {
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Auto Exposure Pass"),
+            });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, &self.bind_group, &[]);
+            cpass.dispatch(1, 1, 1);
+        }
+        hdr.override_exposure(encoder, &self.state_buffer);
+    }
+}
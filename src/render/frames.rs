@@ -0,0 +1,62 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread,
+};
+use wgpu::{Device, Maintain, Queue};
+
+pub const DEFAULT_MAX_FRAMES_IN_FLIGHT: u32 = 2;
+
+type WorkDone = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// the futures are checked by hand between device polls, nothing needs waking
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+/*
+ * Keeps the GPU from running more than `max` frames behind the CPU, so input shows up
+ * on screen sooner. Every submitted frame leaves a work done future behind, and once
+ * `max` of them are pending `wait` spins on device polls until the oldest resolves.
+ * Waiting for the whole queue instead would always drain it down to zero frames.
+ */
+pub struct FrameLimiter {
+    pub max: u32,
+    pending: RefCell<VecDeque<WorkDone>>,
+}
+
+impl FrameLimiter {
+    pub fn new(max: u32) -> Self {
+        Self {
+            max: max.max(1),
+            pending: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    // call before submitting a frame
+    pub fn wait(&self, device: &Device) {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mut pending = self.pending.borrow_mut();
+        while pending.len() >= self.max as usize {
+            let mut oldest = pending.pop_front().unwrap();
+            while oldest.as_mut().poll(&mut context).is_pending() {
+                device.poll(Maintain::Poll);
+                thread::yield_now();
+            }
+        }
+    }
+
+    // call right after submitting a frame
+    pub fn submitted(&self, queue: &Queue) {
+        self.pending
+            .borrow_mut()
+            .push_back(Box::pin(queue.on_submitted_work_done()));
+    }
+}
@@ -0,0 +1,285 @@
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3};
+use std::f32::consts::PI;
+
+use super::{Axis, LineData, LineVertex};
+
+const AXES: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+pub(super) const AXIS_COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.3, 0.5, 1.0, 1.0],
+];
+const RING_SEGMENTS: u32 = 64;
+// as fractions of the gizmo's size
+const TIP_SIZE: f32 = 0.08;
+const HIT_TOLERANCE: f32 = 0.05;
+// rings sit a little inside the handles so the two don't get in each other's way
+const RING_RADIUS: f32 = 0.8;
+// keeps a drag from scaling the scene away to nothing
+const MIN_SCALE: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+// a handle being dragged, with where the drag started
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+    axis: Axis,
+    center: Point3<f32>,
+    // distance along the axis, or the direction from the centre for rotations
+    start: Vector3<f32>,
+    transform: Matrix4<f32>,
+}
+
+/*
+ * Handles along the world axes through the model's centre, for moving, turning and
+ * stretching it with Renderer::set_model_transform. Everything is in render space without
+ * the pivot offset, the same space Renderer::pick works in, and sized in world units.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    // steps used while snapping: world units, an angle, and a scale factor
+    pub translate_snap: f32,
+    pub rotate_snap: Deg<f32>,
+    pub scale_snap: f32,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    pub fn new(mode: GizmoMode) -> Self {
+        Self {
+            mode,
+            translate_snap: 1.0,
+            rotate_snap: Deg(15.0),
+            scale_snap: 0.1,
+            drag: None,
+        }
+    }
+
+    pub fn dragged_axis(&self) -> Option<Axis> {
+        self.drag.map(|drag| drag.axis)
+    }
+
+    /*
+     * Arrows for translating, rings for rotating, and lines ending in a box for scaling,
+     * one per axis. The dragged one is drawn in `highlight`.
+     */
+    pub fn lines(&self, center: Point3<f32>, size: f32, highlight: [f32; 4]) -> LineData {
+        let mut lines = LineData::default();
+        for (i, &axis) in AXES.iter().enumerate() {
+            let color = match self.dragged_axis() {
+                Some(dragged) if dragged == axis => highlight,
+                _ => AXIS_COLORS[i],
+            };
+            let (u, v) = perpendiculars(axis);
+            let tip = center + axis.unit() * size;
+            let tip_size = size * TIP_SIZE;
+            let mut segments = Vec::new();
+            match self.mode {
+                GizmoMode::Translate => {
+                    segments.push((center, tip));
+                    let base = tip - axis.unit() * tip_size * 2.0;
+                    for &side in [u, -u, v, -v].iter() {
+                        segments.push((tip, base + side * tip_size));
+                    }
+                }
+                GizmoMode::Rotate => {
+                    let radius = size * RING_RADIUS;
+                    let point = |k: u32| {
+                        let angle = k as f32 / RING_SEGMENTS as f32 * 2.0 * PI;
+                        center + (u * angle.cos() + v * angle.sin()) * radius
+                    };
+                    for k in 0..RING_SEGMENTS {
+                        segments.push((point(k), point(k + 1)));
+                    }
+                }
+                GizmoMode::Scale => {
+                    segments.push((center, tip));
+                    let corners = [u + v, u - v, -u - v, -u + v];
+                    for (k, &corner) in corners.iter().enumerate() {
+                        let next = corners[(k + 1) % corners.len()];
+                        segments.push((tip + corner * tip_size, tip + next * tip_size));
+                    }
+                }
+            }
+            for (a, b) in segments {
+                let index = lines.vertices.len() as u32;
+                for &position in [a, b].iter() {
+                    lines.vertices.push(LineVertex {
+                        position: position.into(),
+                        color,
+                    });
+                }
+                lines.indices.extend_from_slice(&[index, index + 1]);
+            }
+        }
+        lines
+    }
+
+    // the handle a ray passes closest to, within a tolerance of hitting it
+    pub fn hit(
+        &self,
+        center: Point3<f32>,
+        size: f32,
+        ray: (Point3<f32>, Vector3<f32>),
+    ) -> Option<Axis> {
+        let (origin, direction) = ray;
+        let tolerance = size * HIT_TOLERANCE;
+        AXES.iter()
+            .filter_map(|&axis| {
+                let distance = match self.mode {
+                    GizmoMode::Translate | GizmoMode::Scale => {
+                        let (s, distance) = closest_on_axis(center, axis, origin, direction)?;
+                        Some(distance).filter(|_| (0.0..=size).contains(&s))
+                    }
+                    GizmoMode::Rotate => {
+                        let point = on_plane(center, axis, origin, direction)?;
+                        Some(((point - center).magnitude() - size * RING_RADIUS).abs())
+                    }
+                }?;
+                Some((axis, distance)).filter(|&(_, distance)| distance <= tolerance)
+            })
+            .fold(None, |closest: Option<(Axis, f32)>, hit| match closest {
+                Some(closest) if closest.1 <= hit.1 => Some(closest),
+                _ => Some(hit),
+            })
+            .map(|(axis, _)| axis)
+    }
+
+    // starts dragging whatever handle the ray hits, returns false when it misses them all
+    pub fn begin_drag(
+        &mut self,
+        center: Point3<f32>,
+        size: f32,
+        ray: (Point3<f32>, Vector3<f32>),
+        transform: Matrix4<f32>,
+    ) -> bool {
+        let (origin, direction) = ray;
+        let axis = match self.hit(center, size, ray) {
+            Some(axis) => axis,
+            None => return false,
+        };
+        let start = match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                closest_on_axis(center, axis, origin, direction).map(|(s, _)| axis.unit() * s)
+            }
+            GizmoMode::Rotate => on_plane(center, axis, origin, direction).map(|p| p - center),
+        };
+        self.drag = start.map(|start| Drag {
+            axis,
+            center,
+            start,
+            transform,
+        });
+        self.drag.is_some()
+    }
+
+    /*
+     * The model transform for the drag having reached `ray`, None when not dragging or
+     * the ray runs parallel to what's being dragged along.
+     */
+    pub fn drag_to(&self, ray: (Point3<f32>, Vector3<f32>), snap: bool) -> Option<Matrix4<f32>> {
+        let drag = self.drag?;
+        let (origin, direction) = ray;
+        let (axis, unit, center) = (drag.axis, drag.axis.unit(), drag.center);
+        let about_center = |m: Matrix4<f32>| {
+            Matrix4::from_translation(center.to_vec())
+                * m
+                * Matrix4::from_translation(-center.to_vec())
+        };
+        let change = match self.mode {
+            GizmoMode::Translate => {
+                let (s, _) = closest_on_axis(center, axis, origin, direction)?;
+                let distance = snapped(s - drag.start.dot(unit), self.translate_snap, snap);
+                Matrix4::from_translation(unit * distance)
+            }
+            GizmoMode::Rotate => {
+                let to = on_plane(center, axis, origin, direction)? - center;
+                let from = drag.start;
+                let angle = unit.dot(from.cross(to)).atan2(from.dot(to));
+                let step = Rad::from(self.rotate_snap).0;
+                let angle = snapped(angle, step, snap);
+                about_center(Matrix4::from_axis_angle(unit, Rad(angle)))
+            }
+            GizmoMode::Scale => {
+                let (s, _) = closest_on_axis(center, axis, origin, direction)?;
+                let start = drag.start.dot(unit);
+                if start.abs() < f32::EPSILON {
+                    return None;
+                }
+                let factor = snapped(s / start, self.scale_snap, snap).max(MIN_SCALE);
+                let mut scale = [1.0; 3];
+                scale[axis as usize] = factor;
+                about_center(Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2]))
+            }
+        };
+        Some(change * drag.transform)
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+}
+
+// two unit vectors spanning the plane the axis is the normal of
+fn perpendiculars(axis: Axis) -> (Vector3<f32>, Vector3<f32>) {
+    match axis {
+        Axis::X => (Vector3::unit_y(), Vector3::unit_z()),
+        Axis::Y => (Vector3::unit_z(), Vector3::unit_x()),
+        Axis::Z => (Vector3::unit_x(), Vector3::unit_y()),
+    }
+}
+
+/*
+ * How far along the axis through `center` the ray passes closest, and how close it gets.
+ * None when the two are parallel.
+ */
+fn closest_on_axis(
+    center: Point3<f32>,
+    axis: Axis,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<(f32, f32)> {
+    let unit = axis.unit();
+    let w = center - origin;
+    let b = unit.dot(direction);
+    let c = direction.dot(direction);
+    let denom = c - b * b;
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    let (d, e) = (unit.dot(w), direction.dot(w));
+    let s = (b * e - c * d) / denom;
+    let t = (e - b * d) / denom;
+    let distance = (w + unit * s - direction * t).magnitude();
+    Some((s, distance))
+}
+
+// where the ray crosses the plane through `center` facing along the axis
+fn on_plane(
+    center: Point3<f32>,
+    axis: Axis,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Point3<f32>> {
+    let unit = axis.unit();
+    let facing = direction.dot(unit);
+    if facing.abs() < 1e-6 {
+        return None;
+    }
+    let t = (center - origin).dot(unit) / facing;
+    Some(origin + direction * t).filter(|_| t >= 0.0)
+}
+
+fn snapped(value: f32, step: f32, snap: bool) -> f32 {
+    if snap && step > 0.0 {
+        (value / step).round() * step
+    } else {
+        value
+    }
+}
@@ -0,0 +1,181 @@
+use bytemuck::Zeroable;
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::{texture::DEPTH_FORMAT, LineStyle};
+
+// about how many of the finest lines shown fit between the eye and the target
+const GRID_CELLS: f32 = 10.0;
+// without LineStyle::grid_fade_distance, lines are gone this many view distances away
+const GRID_FADE_SCALE: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniforms {
+    transform: [[f32; 4]; 4],
+    inverse_transform: [[f32; 4]; 4],
+    eye: [f32; 4],
+    color: [f32; 4],
+    // x: ground height, y: fade distance, z: spacing of the finer lines, w: how far they've faded
+    params: [f32; 4],
+    // x: 1 to draw the axes in their gizmo colours
+    flags: [f32; 4],
+}
+
+/*
+ * A grid on the ground under the model that goes on to the horizon, drawn with a single
+ * triangle over the whole frame: each pixel's ray is intersected with the ground and the
+ * lines are worked out where it lands. The spacing steps up by powers of ten as the camera
+ * backs off, with the finer lines fading out before they get too dense, and lines fade
+ * with distance from the eye. Lives in the same untransformed space as the ground shadow.
+ */
+pub struct InfiniteGrid {
+    uniform_buffer: Buffer,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl InfiniteGrid {
+    pub fn new(device: &Device, target_format: TextureFormat, sample_count: u32) -> Self {
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            contents: bytemuck::bytes_of(&GridUniforms::zeroed()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Grid Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(mem::size_of::<GridUniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Grid Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline =
+            InfiniteGrid::create_pipeline(device, &bind_group_layout, target_format, sample_count);
+
+        Self {
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &Device,
+        bind_group_layout: &BindGroupLayout,
+        target_format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Grid Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../grid.wgsl"))),
+        });
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Grid Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_grid",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            // the shader writes the ground's depth, tested so the model hides the grid
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_grid",
+                targets: &[ColorTargetState {
+                    format: target_format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+        })
+    }
+
+    // for when the renderer's target changes, see Renderer::set_hdr
+    pub fn rebuild_pipeline(
+        &mut self,
+        device: &Device,
+        target_format: TextureFormat,
+        sample_count: u32,
+    ) {
+        self.pipeline = InfiniteGrid::create_pipeline(
+            device,
+            &self.bind_group_layout,
+            target_format,
+            sample_count,
+        );
+    }
+
+    /*
+     * `transform` is the scene's transform uniform, `eye` the camera in the same space as the
+     * grid and `distance` how far it is from what it looks at there, which picks the spacing.
+     */
+    pub fn write_uniforms(
+        &self,
+        queue: &Queue,
+        transform: Matrix4<f32>,
+        eye: Point3<f32>,
+        distance: f32,
+        ground: f32,
+        style: &LineStyle,
+    ) {
+        let base = style.grid_spacing.max(f32::EPSILON);
+        let level = (distance.max(f32::EPSILON) / (base * GRID_CELLS)).log10();
+        let spacing = base * 10f32.powf(level.floor());
+        let fade_distance = style
+            .grid_fade_distance
+            .unwrap_or(distance * GRID_FADE_SCALE)
+            .max(f32::EPSILON);
+        let uniforms = GridUniforms {
+            transform: transform.into(),
+            inverse_transform: transform.invert().unwrap_or_else(Matrix4::identity).into(),
+            eye: [eye.x, eye.y, eye.z, 1.0],
+            color: style.grid_color,
+            params: [ground, fade_distance, spacing, level - level.floor()],
+            flags: [style.grid_axis_highlight as u32 as f32, 0.0, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    pub fn draw<'a>(&'a self, rpass: &mut RenderPass<'a>) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,342 @@
+use bytemuck::Zeroable;
+use cgmath::{Matrix4, Vector3, Vector4, Zero};
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::{texture::DEPTH_FORMAT, Aabb, SceneObject, Topology, Vertex};
+
+const MASK_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+const MASK_RESOLUTION: u32 = 512;
+// in mask texels, how far the edge of the shadow gets spread out
+const BLUR_RADIUS: f32 = 6.0;
+// fraction of the shadow's extent left around it so the blur doesn't get cut off
+const GROUND_MARGIN: f32 = 0.1;
+// lights closer to horizontal than this cast shadows as if they weren't, so they stay
+// a few times the model's size instead of running off to the horizon
+const MIN_LIGHT_DESCENT: f32 = 0.25;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GroundUniforms {
+    transform: [[f32; 4]; 4],
+    mask_transform: [[f32; 4]; 4],
+    rect: [f32; 4],
+    // x: ground height, y: opacity, z: mask texel size, w: blur radius in texels
+    params: [f32; 4],
+}
+
+/*
+ * A soft shadow on the ground under the model, for when it should look like it's sitting
+ * on something without paying for the shadow map. The triangles are flattened onto the
+ * ground along the light into a coverage mask, which the ground quad then blurs and
+ * darkens the scene with. Lives in the same untransformed space as the grid, at the
+ * bottom of the scene bounds.
+ */
+pub struct GroundShadow {
+    pub enabled: bool,
+    pub opacity: f32,
+    mask_view: TextureView,
+    uniform_buffer: Buffer,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    mask_pipeline: RenderPipeline,
+    mask_strip_pipeline: RenderPipeline,
+    ground_pipeline: RenderPipeline,
+}
+
+impl GroundShadow {
+    pub fn new(device: &Device, target_format: TextureFormat, sample_count: u32) -> Self {
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Ground Shadow Uniform Buffer"),
+            contents: bytemuck::bytes_of(&GroundUniforms::zeroed()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Ground Shadow Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<GroundUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Ground Shadow Mask"),
+            size: Extent3d {
+                width: MASK_RESOLUTION,
+                height: MASK_RESOLUTION,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: MASK_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+        let mask_view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Ground Shadow Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Ground Shadow Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&mask_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Ground Shadow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = GroundShadow::shader(device);
+        let mask_pipeline =
+            GroundShadow::create_mask_pipeline(device, &layout, &shader, Topology::TriangleList);
+        let mask_strip_pipeline =
+            GroundShadow::create_mask_pipeline(device, &layout, &shader, Topology::TriangleStrip);
+        let ground_pipeline =
+            GroundShadow::create_ground_pipeline(device, &layout, target_format, sample_count);
+
+        Self {
+            enabled: false,
+            opacity: 0.5,
+            mask_view,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            mask_pipeline,
+            mask_strip_pipeline,
+            ground_pipeline,
+        }
+    }
+
+    fn shader(device: &Device) -> ShaderModule {
+        device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Ground Shadow Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../ground_shadow.wgsl"))),
+        })
+    }
+
+    fn create_mask_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        topology: Topology,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Ground Shadow Mask Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_mask",
+                buffers: &[Vertex::layout()],
+            },
+            // flattened, both sides of the model land on the ground
+            primitive: PrimitiveState {
+                topology: topology.primitive(),
+                strip_index_format: topology.strip_index_format(),
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: "fs_mask",
+                targets: &[MASK_FORMAT.into()],
+            }),
+        })
+    }
+
+    fn create_ground_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        target_format: TextureFormat,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        let shader = GroundShadow::shader(device);
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Ground Shadow Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_ground",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            // tested so the model hides the shadow, not written so it can't hide anything
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_ground",
+                targets: &[ColorTargetState {
+                    format: target_format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+        })
+    }
+
+    // for when the renderer's target changes, see Renderer::set_hdr
+    pub fn rebuild_pipeline(
+        &mut self,
+        device: &Device,
+        target_format: TextureFormat,
+        sample_count: u32,
+    ) {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Ground Shadow Pipeline Layout"),
+            bind_group_layouts: &[&self.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        self.ground_pipeline =
+            GroundShadow::create_ground_pipeline(device, &layout, target_format, sample_count);
+    }
+
+    /*
+     * `transform` is the scene's transform uniform, `direction` the light's. Nothing gets
+     * drawn for a light shining upwards.
+     */
+    pub fn write_uniforms(
+        &self,
+        queue: &Queue,
+        transform: Matrix4<f32>,
+        direction: Vector3<f32>,
+        bounds: &Aabb,
+    ) {
+        let ground = bounds.min.z;
+        let descent = (-direction.z).max(MIN_LIGHT_DESCENT);
+        let (sx, sy) = (direction.x / descent, direction.y / descent);
+        // slides every point down the light onto z = ground
+        let flatten = Matrix4::from_cols(
+            Vector4::unit_x(),
+            Vector4::unit_y(),
+            Vector4::new(sx, sy, 0.0, 0.0),
+            Vector4::new(-sx * ground, -sy * ground, ground, 1.0),
+        );
+        let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in bounds.corners().iter() {
+            let drop = corner.z - ground;
+            let (x, y) = (corner.x + sx * drop, corner.y + sy * drop);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let margin = (max_x - min_x).max(max_y - min_y).max(f32::EPSILON) * GROUND_MARGIN;
+        let (min_x, min_y, max_x, max_y) = (
+            min_x - margin,
+            min_y - margin,
+            max_x + margin,
+            max_y + margin,
+        );
+        // the rect onto the mask's clip space, depth doesn't matter without a depth target
+        let fit = Matrix4::from_cols(
+            Vector4::new(2.0 / (max_x - min_x), 0.0, 0.0, 0.0),
+            Vector4::new(0.0, 2.0 / (max_y - min_y), 0.0, 0.0),
+            Vector4::zero(),
+            Vector4::new(
+                -(max_x + min_x) / (max_x - min_x),
+                -(max_y + min_y) / (max_y - min_y),
+                0.5,
+                1.0,
+            ),
+        );
+        let opacity = if direction.z < 0.0 { self.opacity } else { 0.0 };
+        let uniforms = GroundUniforms {
+            transform: transform.into(),
+            mask_transform: (fit * flatten).into(),
+            rect: [min_x, min_y, max_x, max_y],
+            params: [ground, opacity, 1.0 / MASK_RESOLUTION as f32, BLUR_RADIUS],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    pub fn render_mask(&self, encoder: &mut CommandEncoder, objects: &[SceneObject]) {
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Ground Shadow Mask Pass"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: &self.mask_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        let casters = objects
+            .iter()
+            .filter(|object| object.mesh.data.topology.has_triangles());
+        for object in casters {
+            rpass.set_pipeline(match object.mesh.data.topology {
+                Topology::TriangleStrip => &self.mask_strip_pipeline,
+                _ => &self.mask_pipeline,
+            });
+            rpass.set_index_buffer(object.mesh.index_buffer.slice(..), object.mesh.index_format);
+            rpass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+            rpass.draw_indexed(0..object.mesh.index_count(), 0, 0..1);
+        }
+    }
+
+    pub fn draw<'a>(&'a self, rpass: &mut RenderPass<'a>) {
+        rpass.set_pipeline(&self.ground_pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::texture::Texture;
+
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+// every adapter has to support these for a renderable float format
+pub(super) const SUPPORTED_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniforms {
+    color_space: u32,
+    surface_srgb: u32,
+    exposure: f32,
+    _padding: u32,
+}
+
+/*
+ * Floating point colour target for the scene. The chain is fixed:
+ *
+ *   scene (multisampled HDR_FORMAT) -> resolve into a single-sample HDR_FORMAT texture
+ *     -> tonemap and colour space encode into the 8-bit surface
+ *
+ * Resolving happens as part of the scene passes, so the average is taken over linear
+ * values before anything gets compressed into the display range.
+ */
+pub struct HdrTarget {
+    pub sample_count: u32,
+    pub exposure: f32,
+    // None without MSAA, the passes then draw straight into `resolved`
+    pub multisampled: Option<Texture>,
+    pub resolved: Texture,
+    uniform_buffer: Buffer,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl HdrTarget {
+    pub fn new(
+        device: &Device,
+        surface_format: TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Result<Self, String> {
+        if !SUPPORTED_SAMPLE_COUNTS.contains(&sample_count) {
+            return Err(format!(
+                "unsupported sample count {}, expected one of {:?}",
+                sample_count, SUPPORTED_SAMPLE_COUNTS
+            ));
+        }
+        let multisampled = if sample_count > 1 {
+            Some(Texture::render_target(
+                device,
+                "HDR Multisampled Texture",
+                HDR_FORMAT,
+                width,
+                height,
+                sample_count,
+                TextureUsages::RENDER_ATTACHMENT,
+            ))
+        } else {
+            None
+        };
+        let resolved = Texture::render_target(
+            device,
+            "HDR Resolved Texture",
+            HDR_FORMAT,
+            width,
+            height,
+            1,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        );
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Tonemap Uniform Buffer"),
+            contents: bytemuck::bytes_of(&TonemapUniforms {
+                color_space: 0,
+                surface_srgb: 0,
+                exposure: 1.0,
+                _padding: 0,
+            }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Tonemap Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<TonemapUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = HdrTarget::create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &resolved.view,
+        );
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../tonemap.wgsl"))),
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_tonemap",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_tonemap",
+                targets: &[surface_format.into()],
+            }),
+        });
+
+        Ok(Self {
+            sample_count,
+            exposure: 1.0,
+            multisampled,
+            resolved,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        uniform_buffer: &Buffer,
+        source: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Tonemap Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(source),
+                },
+            ],
+        })
+    }
+
+    // for tonemapping something other than `resolved`, an HDR_FORMAT texture the same size
+    pub fn source_bind_group(&self, device: &Device, source: &TextureView) -> BindGroup {
+        HdrTarget::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            source,
+        )
+    }
+
+    // where the scene passes draw, and what they resolve into if that's a different texture
+    pub fn attachment(&self) -> (&TextureView, Option<&TextureView>) {
+        match &self.multisampled {
+            Some(multisampled) => (&multisampled.view, Some(&self.resolved.view)),
+            None => (&self.resolved.view, None),
+        }
+    }
+
+    pub fn write_uniforms(&self, queue: &Queue, color_space: u32, surface_srgb: bool) {
+        let uniforms = TonemapUniforms {
+            color_space,
+            surface_srgb: surface_srgb as u32,
+            exposure: self.exposure,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    // puts the f32 at the start of `source` in place of `exposure`, see AutoExposure
+    pub fn override_exposure(&self, encoder: &mut CommandEncoder, source: &Buffer) {
+        let offset = 2 * mem::size_of::<u32>() as BufferAddress;
+        let size = mem::size_of::<f32>() as BufferAddress;
+        encoder.copy_buffer_to_buffer(source, 0, &self.uniform_buffer, offset, size);
+    }
+
+    pub fn tonemap(&self, encoder: &mut CommandEncoder, target: &TextureView) {
+        self.tonemap_from(encoder, target, &self.bind_group);
+    }
+
+    // with the source bound by `source_bind_group`
+    pub fn tonemap_from(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        source: &BindGroup,
+    ) {
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, source, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,58 @@
+use std::mem;
+use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+// vertex buffer slot the per-vertex scalars are bound to, next to the Vertex buffer
+pub const SCALAR_SLOT: u32 = 1;
+
+// how DisplayMode::Heatmap turns a normalized scalar into a colour
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorRamp {
+    Viridis,
+    Jet,
+}
+
+impl ColorRamp {
+    // must match the COLOR_RAMP_* constants in shader.wgsl
+    pub fn shader_id(self) -> u32 {
+        match self {
+            ColorRamp::Viridis => 0,
+            ColorRamp::Jet => 1,
+        }
+    }
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        ColorRamp::Viridis
+    }
+}
+
+const SCALAR_ATTRIBUTES: [VertexAttribute; 1] = wgpu::vertex_attr_array![3 => Float32];
+
+// one f32 per vertex, in its own buffer so meshes without data don't pay for it in Vertex
+pub fn scalar_layout<'a>() -> VertexBufferLayout<'a> {
+    VertexBufferLayout {
+        array_stride: mem::size_of::<f32>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &SCALAR_ATTRIBUTES,
+    }
+}
+
+// maps the values onto [0, 1], a constant field ends up at 0
+pub fn normalize(values: &[f32]) -> Vec<f32> {
+    let finite = values.iter().copied().filter(|v| v.is_finite());
+    let (min, max) = finite.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    let range = max - min;
+    values
+        .iter()
+        .map(|&v| {
+            if v.is_finite() && range > 0.0 {
+                (v - min) / range
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
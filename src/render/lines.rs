@@ -0,0 +1,223 @@
+use cgmath::{Matrix3, Point3, Vector3};
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::{mesh::sort_key, texture::DEPTH_FORMAT, Aabb};
+
+pub const DEFAULT_LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    pub fn layout<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<LineVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// CPU-side line list geometry, every pair of indices is one segment
+#[derive(Clone, Debug, Default)]
+pub struct LineData {
+    pub vertices: Vec<LineVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl LineData {
+    pub fn segment_count(&self) -> usize {
+        self.indices.len() / 2
+    }
+
+    // bytes of the vertex and index buffers Lines::new allocates
+    pub fn gpu_memory(&self) -> u64 {
+        (self.vertices.len() * mem::size_of::<LineVertex>()
+            + self.indices.len() * mem::size_of::<u32>()) as u64
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|v| Point3::from(v.position)))
+    }
+
+    // joins consecutive points, as OBJ `l` elements and glTF line strips do
+    pub fn push_strip(&mut self, indices: &[u32]) {
+        for pair in indices.windows(2) {
+            self.indices.extend_from_slice(pair);
+        }
+    }
+
+    pub fn append(&mut self, other: LineData) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| index + offset));
+    }
+
+    // same idea as MeshData::canonicalize, vertices by position then colour, segments sorted
+    pub fn canonicalize(&mut self) {
+        let key = |v: &LineVertex| {
+            let mut key = [0; 7];
+            for (dst, &f) in key.iter_mut().zip(v.position.iter().chain(&v.color)) {
+                *dst = sort_key(f);
+            }
+            key
+        };
+        let mut order: Vec<usize> = (0..self.vertices.len()).collect();
+        order.sort_by_key(|&i| key(&self.vertices[i]));
+        let mut remap = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            remap[old] = new as u32;
+        }
+        self.vertices = order.iter().map(|&i| self.vertices[i]).collect();
+        let mut segments: Vec<[u32; 2]> = self
+            .indices
+            .chunks_exact(2)
+            .map(|pair| {
+                let (a, b) = (remap[pair[0] as usize], remap[pair[1] as usize]);
+                [a.min(b), a.max(b)]
+            })
+            .collect();
+        segments.sort_unstable();
+        self.indices = segments.concat();
+    }
+
+    // same as MeshData::change_basis, lines have no winding to keep
+    pub fn change_basis(&mut self, basis: Matrix3<f32>) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.position = (basis * Vector3::from(vertex.position)).into();
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vector3<f64>) {
+        for vertex in self.vertices.iter_mut() {
+            let [x, y, z] = vertex.position;
+            vertex.position = [
+                (x as f64 + offset.x) as f32,
+                (y as f64 + offset.y) as f32,
+                (z as f64 + offset.z) as f32,
+            ];
+        }
+    }
+}
+
+pub struct Lines {
+    pub name: String,
+    pub data: LineData,
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+}
+
+impl Lines {
+    pub fn new(device: &Device, name: &str, data: LineData) -> Self {
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Line Vertex Buffer"),
+            contents: bytemuck::cast_slice(&data.vertices),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Line Index Buffer"),
+            contents: bytemuck::cast_slice(&data.indices),
+            usage: BufferUsages::INDEX,
+        });
+
+        Self {
+            name: name.to_owned(),
+            data,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.data.indices.len() as u32
+    }
+
+    // moves the vertices in place keeping their colours and the segments, extra ones are ignored
+    pub fn update_positions<I: IntoIterator<Item = [f32; 3]>>(
+        &mut self,
+        queue: &Queue,
+        positions: I,
+    ) {
+        for (vertex, position) in self.data.vertices.iter_mut().zip(positions) {
+            vertex.position = position;
+        }
+        queue.write_buffer(
+            &self.vertex_buffer,
+            0,
+            bytemuck::cast_slice(&self.data.vertices),
+        );
+    }
+}
+
+/*
+ * Reads the camera and clip planes from the main uniform buffer, group 0. `on_top` lines
+ * ignore the depth buffer and leave it alone, for handles that mustn't disappear into the
+ * model.
+ */
+pub fn create_pipeline(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    format: TextureFormat,
+    sample_count: u32,
+    on_top: bool,
+) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Line Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(&ShaderModuleDescriptor {
+        label: Some("Line Shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../line.wgsl"))),
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Line Pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_line",
+            buffers: &[LineVertex::layout()],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::LineList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            clamp_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: !on_top,
+            depth_compare: if on_top {
+                CompareFunction::Always
+            } else {
+                CompareFunction::LessEqual
+            },
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_line",
+            targets: &[format.into()],
+        }),
+    })
+}
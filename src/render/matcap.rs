@@ -0,0 +1,23 @@
+use cgmath::{InnerSpace, Vector3};
+use image::{Rgba, RgbaImage};
+
+/*
+ * Procedurally lit grey sphere, used until the user supplies their own matcap.
+ * The image is indexed by the view-space normal's XY, so each texel holds the
+ * shading a surface facing that direction would receive.
+ */
+pub fn default_matcap(size: u32) -> RgbaImage {
+    let light = Vector3::new(-0.4f32, 0.5, 0.75).normalize();
+    let half_vector = (light + Vector3::unit_z()).normalize();
+    RgbaImage::from_fn(size, size, |x, y| {
+        let nx = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+        let ny = 1.0 - (y as f32 + 0.5) / size as f32 * 2.0;
+        let r2 = (nx * nx + ny * ny).min(1.0);
+        let normal = Vector3::new(nx, ny, (1.0 - r2).sqrt()).normalize();
+        let diffuse = normal.dot(light).max(0.0);
+        let specular = normal.dot(half_vector).max(0.0).powf(32.0);
+        let shade = (0.15 + 0.7 * diffuse + 0.25 * specular).min(1.0);
+        let value = (shade * 255.0) as u8;
+        Rgba([value, value, value, 255])
+    })
+}
@@ -0,0 +1,56 @@
+/*
+ * The scalar factors of a PBR metallic-roughness material, which is all of it the renderer
+ * keeps: there's no texture pipeline, so DisplayMode::Channel shows these as flat values.
+ * Loaders that know nothing about materials leave MeshData::material at None, which gets
+ * the glTF defaults.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialFactors {
+    // linear rgba
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    // linear rgb
+    pub emissive: [f32; 3],
+    // alpha mode MASK, fragments with less alpha than this are cut away. While multisampling
+    // the edge goes through alpha to coverage instead
+    pub alpha_cutoff: Option<f32>,
+}
+
+// a neutral matte grey for Renderer::set_material_override, the usual clay render
+pub const CLAY_MATERIAL: MaterialFactors = MaterialFactors {
+    base_color: [0.7, 0.68, 0.65, 1.0],
+    metallic: 0.0,
+    roughness: 0.6,
+    emissive: [0.0; 3],
+    alpha_cutoff: None,
+};
+
+impl MaterialFactors {
+    // the vec4s the shaders read the factors from: base colour, emissive, and
+    // metallic, roughness and alpha cutoff (negative without one)
+    pub(super) fn as_uniform(&self) -> [[f32; 4]; 3] {
+        [
+            self.base_color,
+            [self.emissive[0], self.emissive[1], self.emissive[2], 0.0],
+            [
+                self.metallic,
+                self.roughness,
+                self.alpha_cutoff.unwrap_or(-1.0),
+                0.0,
+            ],
+        ]
+    }
+}
+
+impl Default for MaterialFactors {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: [0.0; 3],
+            alpha_cutoff: None,
+        }
+    }
+}
@@ -0,0 +1,836 @@
+use cgmath::{InnerSpace, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use std::{iter, mem};
+use wgpu::{
+    BindGroupLayout, Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, IndexFormat,
+    PrimitiveTopology, Queue,
+};
+
+use super::{vertex_cache, winding, Aabb, MaterialFactors, Morph, Vertex, MAX_MORPH_TARGETS};
+
+// separates strips within one index buffer
+pub const RESTART_INDEX: u32 = u32::MAX;
+
+// how the indices make primitives, every object is drawn with the pipeline for its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Topology {
+    TriangleList,
+    // strips may be joined with RESTART_INDEX
+    TriangleStrip,
+    LineList,
+    // same as TriangleStrip
+    LineStrip,
+    PointList,
+}
+
+impl Topology {
+    pub const ALL: [Topology; 5] = [
+        Topology::TriangleList,
+        Topology::TriangleStrip,
+        Topology::LineList,
+        Topology::LineStrip,
+        Topology::PointList,
+    ];
+
+    pub fn primitive(self) -> PrimitiveTopology {
+        match self {
+            Topology::TriangleList => PrimitiveTopology::TriangleList,
+            Topology::TriangleStrip => PrimitiveTopology::TriangleStrip,
+            Topology::LineList => PrimitiveTopology::LineList,
+            Topology::LineStrip => PrimitiveTopology::LineStrip,
+            Topology::PointList => PrimitiveTopology::PointList,
+        }
+    }
+
+    // only strip pipelines may specify this, and it's what enables primitive restart
+    pub fn strip_index_format(self) -> Option<IndexFormat> {
+        match self {
+            Topology::TriangleStrip | Topology::LineStrip => Some(IndexFormat::Uint32),
+            Topology::TriangleList | Topology::LineList | Topology::PointList => None,
+        }
+    }
+
+    // lines and points have no surface, so no triangles, normals to compute or shadows
+    pub fn has_triangles(self) -> bool {
+        matches!(self, Topology::TriangleList | Topology::TriangleStrip)
+    }
+
+    // what `MeshData::to_list` turns this into
+    fn list(self) -> Self {
+        match self {
+            Topology::TriangleStrip => Topology::TriangleList,
+            Topology::LineStrip => Topology::LineList,
+            other => other,
+        }
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::TriangleList
+    }
+}
+
+// CPU-side triangle geometry, as produced by the loaders
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub topology: Topology,
+    // per target, a position delta for every vertex
+    pub morph_targets: Vec<Vec<[f32; 3]>>,
+    pub morph_weights: Vec<f32>,
+    // appended geometry keeps the first material, there's one per draw
+    pub material: Option<MaterialFactors>,
+}
+
+// orders floats the way they compare, with -0 before 0 and NaNs at the ends
+pub(crate) fn sort_key(f: f32) -> u32 {
+    let bits = f.to_bits();
+    if bits >> 31 == 1 {
+        !bits
+    } else {
+        bits | 1 << 31
+    }
+}
+
+pub fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Vector3<f32> {
+    let (a, b, c) = (Vector3::from(a), Vector3::from(b), Vector3::from(c));
+    (b - a).cross(c - a)
+}
+
+// Möller–Trumbore, distance along the ray to the triangle, either side facing
+pub(super) fn intersect_triangle(
+    [a, b, c]: [Point3<f32>; 3],
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<f32> {
+    let (edge1, edge2) = (b - a, c - a);
+    let p = direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) / det;
+    let q = to_origin.cross(edge1);
+    let v = direction.dot(q) / det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some(edge2.dot(q) / det).filter(|&t| t >= 0.0)
+}
+
+impl MeshData {
+    pub fn triangle_count(&self) -> usize {
+        match self.topology {
+            Topology::TriangleList => self.indices.len() / 3,
+            Topology::TriangleStrip => self
+                .indices
+                .split(|&index| index == RESTART_INDEX)
+                .map(|strip| strip.len().saturating_sub(2))
+                .sum(),
+            Topology::LineList | Topology::LineStrip | Topology::PointList => 0,
+        }
+    }
+
+    // bytes of the morph delta storage buffer Mesh::new binds, one vec4 per vertex per target
+    pub fn morph_delta_size(&self) -> u64 {
+        let targets = self.morph_targets.len().min(MAX_MORPH_TARGETS);
+        ((targets * self.vertices.len()).max(1) * mem::size_of::<[f32; 4]>()) as u64
+    }
+
+    // about how many bytes of GPU buffers Mesh::new allocates for this
+    pub fn gpu_memory(&self) -> u64 {
+        // the vertices, then the scalar and AO buffers alongside them
+        let per_vertex = mem::size_of::<Vertex>() + 2 * mem::size_of::<f32>();
+        let per_index = match index_format(self) {
+            IndexFormat::Uint16 => mem::size_of::<u16>(),
+            IndexFormat::Uint32 => mem::size_of::<u32>(),
+        };
+        (self.vertices.len() * per_vertex + self.indices.len() * per_index) as u64
+            + self.morph_delta_size()
+    }
+
+    // every triangle as three indices, wound the same way regardless of topology
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        match self.topology {
+            Topology::TriangleList => self
+                .indices
+                .chunks_exact(3)
+                .map(|tri| [tri[0], tri[1], tri[2]])
+                .collect(),
+            Topology::TriangleStrip => self
+                .indices
+                .split(|&index| index == RESTART_INDEX)
+                .flat_map(|strip| {
+                    // every other triangle in a strip has its winding flipped
+                    strip.windows(3).enumerate().map(|(i, tri)| {
+                        if i % 2 == 0 {
+                            [tri[0], tri[1], tri[2]]
+                        } else {
+                            [tri[1], tri[0], tri[2]]
+                        }
+                    })
+                })
+                .collect(),
+            Topology::LineList | Topology::LineStrip | Topology::PointList => Vec::new(),
+        }
+    }
+
+    // the triangles' corners through `transform`, which may scale them unevenly
+    fn transformed_triangles(&self, transform: Matrix4<f32>) -> Vec<[Vector3<f64>; 3]> {
+        let position = |index: u32| {
+            let p = transform.transform_point(Point3::from(self.vertices[index as usize].position));
+            Vector3::new(p.x as f64, p.y as f64, p.z as f64)
+        };
+        self.triangles()
+            .into_iter()
+            .map(|[a, b, c]| [position(a), position(b), position(c)])
+            .collect()
+    }
+
+    pub fn surface_area(&self, transform: Matrix4<f32>) -> f32 {
+        let area: f64 = self
+            .transformed_triangles(transform)
+            .iter()
+            .map(|[a, b, c]| (b - a).cross(c - a).magnitude() / 2.0)
+            .sum();
+        area as f32
+    }
+
+    /*
+     * Sum of the tetrahedra between each triangle and the origin, positive for outward
+     * facing counter-clockwise winding. Only the volume enclosed when the surface is
+     * closed, see EdgeDiagnostics.
+     */
+    pub fn signed_volume(&self, transform: Matrix4<f32>) -> f32 {
+        let volume: f64 = self
+            .transformed_triangles(transform)
+            .iter()
+            .map(|[a, b, c]| a.dot(b.cross(*c)) / 6.0)
+            .sum();
+        volume as f32
+    }
+
+    // distance along the ray to the closest triangle it hits, either side facing
+    pub fn intersect_ray(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        self.intersect_ray_triangle(origin, direction)
+            .map(|(t, _)| t)
+    }
+
+    // like `intersect_ray`, along with which of `triangles` got hit
+    pub fn intersect_ray_triangle(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(f32, usize)> {
+        let position = |index: u32| Point3::from(self.vertices[index as usize].position);
+        self.triangles()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, [a, b, c])| {
+                intersect_triangle([position(a), position(b), position(c)], origin, direction)
+                    .map(|t| (t, i))
+            })
+            .fold(None, |closest: Option<(f32, usize)>, hit| match closest {
+                Some(closest) if closest.0 <= hit.0 => Some(closest),
+                _ => Some(hit),
+            })
+    }
+
+    pub fn to_triangle_list(&mut self) {
+        if self.topology == Topology::TriangleStrip {
+            self.indices = self.triangles().concat();
+            self.topology = Topology::TriangleList;
+        }
+    }
+
+    // strips of either kind become lists of the same primitive
+    pub fn to_list(&mut self) {
+        match self.topology {
+            Topology::TriangleStrip => self.to_triangle_list(),
+            Topology::LineStrip => {
+                let mut indices = Vec::new();
+                for strip in self.indices.split(|&index| index == RESTART_INDEX) {
+                    for pair in strip.windows(2) {
+                        indices.extend_from_slice(pair);
+                    }
+                }
+                self.indices = indices;
+                self.topology = Topology::LineList;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for &index in &self.indices {
+            if index == RESTART_INDEX {
+                if self.topology.strip_index_format().is_none() {
+                    return Err(format!("restart index in a {:?}", self.topology));
+                }
+            } else if index as usize >= self.vertices.len() {
+                return Err(format!(
+                    "index {} out of range for {} vertices",
+                    index,
+                    self.vertices.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|v| Point3::from(v.position)))
+    }
+
+    // geometry of different primitive types can't be combined, `other` is dropped then
+    pub fn append(&mut self, mut other: MeshData) {
+        if self.indices.is_empty() {
+            self.topology = other.topology;
+        } else if self.topology != other.topology {
+            if self.topology.list() != other.topology.list() {
+                log::warn!(
+                    "can't append {:?} to {:?}, skipping it",
+                    other.topology,
+                    self.topology
+                );
+                return;
+            }
+            self.to_list();
+            other.to_list();
+        }
+        if self.material.is_none() {
+            self.material = other.material;
+        }
+        let offset = self.vertices.len() as u32;
+        // targets are matched up by index, vertices missing from either side don't move
+        let target_count = self.morph_targets.len().max(other.morph_targets.len());
+        self.morph_targets
+            .resize(target_count, vec![[0.0; 3]; self.vertices.len()]);
+        for (i, target) in self.morph_targets.iter_mut().enumerate() {
+            match other.morph_targets.get(i) {
+                Some(deltas) => target.extend_from_slice(deltas),
+                None => target.extend(iter::repeat([0.0; 3]).take(other.vertices.len())),
+            }
+        }
+        for i in self.morph_weights.len()..target_count {
+            let weight = other.morph_weights.get(i).copied().unwrap_or(0.0);
+            self.morph_weights.push(weight);
+        }
+        self.vertices.extend(other.vertices);
+        if self.topology.strip_index_format().is_some() && !self.indices.is_empty() {
+            self.indices.push(RESTART_INDEX);
+        }
+        self.indices.extend(other.indices.into_iter().map(|index| {
+            if index == RESTART_INDEX {
+                index
+            } else {
+                index + offset
+            }
+        }));
+    }
+
+    /*
+     * Reorders the triangles so vertices get reused while they're still in the GPU's
+     * post-transform cache, returning the ACMR before and after. Only triangle lists are
+     * touched, None for anything else.
+     */
+    pub fn optimize_vertex_cache(&mut self) -> Option<(f32, f32)> {
+        if self.topology != Topology::TriangleList {
+            return None;
+        }
+        let before = vertex_cache::acmr(&self.indices, vertex_cache::ACMR_CACHE_SIZE);
+        self.indices = vertex_cache::optimize(&self.indices, self.vertices.len());
+        let after = vertex_cache::acmr(&self.indices, vertex_cache::ACMR_CACHE_SIZE);
+        Some((before, after))
+    }
+
+    /*
+     * Flips triangles wound against their neighbours so each connected piece of surface
+     * winds one way, returning how many got flipped. Strips become a list when any do.
+     */
+    pub fn repair_winding(&mut self) -> usize {
+        let flips = winding::flips(self);
+        let count = flips.iter().filter(|&&flip| flip).count();
+        if count == 0 {
+            return 0;
+        }
+        self.to_triangle_list();
+        for (triangle, _) in self
+            .indices
+            .chunks_exact_mut(3)
+            .zip(flips)
+            .filter(|(_, flip)| *flip)
+        {
+            triangle.swap(1, 2);
+        }
+        count
+    }
+
+    /*
+     * Puts vertices in lexicographic order of position, then normal, then uv, and list
+     * triangles in order of their indices, so the same geometry always ends up in the
+     * same buffers however the file listed it. Winding is kept and strips stay in order.
+     */
+    pub fn canonicalize(&mut self) {
+        let key = |v: &Vertex| {
+            let mut key = [0; 8];
+            let floats = v.position.iter().chain(&v.normal).chain(&v.uv);
+            for (dst, &f) in key.iter_mut().zip(floats) {
+                *dst = sort_key(f);
+            }
+            key
+        };
+        let mut order: Vec<usize> = (0..self.vertices.len()).collect();
+        order.sort_by_key(|&i| key(&self.vertices[i]));
+        let mut remap = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            remap[old] = new as u32;
+        }
+        self.vertices = order.iter().map(|&i| self.vertices[i]).collect();
+        for target in self.morph_targets.iter_mut() {
+            *target = order.iter().map(|&i| target[i]).collect();
+        }
+        for index in self.indices.iter_mut() {
+            if *index != RESTART_INDEX {
+                *index = remap[*index as usize];
+            }
+        }
+        if self.topology == Topology::TriangleList {
+            let mut triangles: Vec<[u32; 3]> = self
+                .triangles()
+                .into_iter()
+                .map(|[a, b, c]| {
+                    // rotating keeps the winding, the smallest index goes first
+                    if a <= b && a <= c {
+                        [a, b, c]
+                    } else if b <= c {
+                        [b, c, a]
+                    } else {
+                        [c, a, b]
+                    }
+                })
+                .collect();
+            triangles.sort_unstable();
+            self.indices = triangles.concat();
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vector3<f64>) {
+        for vertex in self.vertices.iter_mut() {
+            let [x, y, z] = vertex.position;
+            vertex.position = [
+                (x as f64 + offset.x) as f32,
+                (y as f64 + offset.y) as f32,
+                (z as f64 + offset.z) as f32,
+            ];
+        }
+    }
+
+    /*
+     * Applies a linear change of basis to positions, normals and morph deltas. `basis`
+     * has to be orthogonal, and when it mirrors the winding is flipped so faces keep
+     * pointing the way their normals do.
+     */
+    pub fn change_basis(&mut self, basis: Matrix3<f32>) {
+        let apply = |v: [f32; 3]| -> [f32; 3] { (basis * Vector3::from(v)).into() };
+        for vertex in self.vertices.iter_mut() {
+            vertex.position = apply(vertex.position);
+            vertex.normal = apply(vertex.normal);
+        }
+        for target in self.morph_targets.iter_mut() {
+            for delta in target.iter_mut() {
+                *delta = apply(*delta);
+            }
+        }
+        if basis.determinant() < 0.0 {
+            self.flip_winding();
+        }
+    }
+
+    pub fn flip_winding(&mut self) {
+        match self.topology {
+            Topology::TriangleList => {
+                for triangle in self.indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+            // repeating each strip's first index shifts every triangle's parity by one
+            Topology::TriangleStrip => {
+                let mut indices = Vec::with_capacity(self.indices.len() * 2);
+                for (i, strip) in self
+                    .indices
+                    .split(|&index| index == RESTART_INDEX)
+                    .enumerate()
+                {
+                    if i > 0 {
+                        indices.push(RESTART_INDEX);
+                    }
+                    if let Some(&first) = strip.first() {
+                        indices.push(first);
+                    }
+                    indices.extend_from_slice(strip);
+                }
+                self.indices = indices;
+            }
+            Topology::LineList | Topology::LineStrip | Topology::PointList => {}
+        }
+    }
+
+    // smooth normals, weighted by face area since the cross product isn't normalized
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::new(0.0f32, 0.0, 0.0); self.vertices.len()];
+        for tri in self.triangles() {
+            let normal = face_normal(
+                self.vertices[tri[0] as usize].position,
+                self.vertices[tri[1] as usize].position,
+                self.vertices[tri[2] as usize].position,
+            );
+            for &index in &tri {
+                normals[index as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            if normal.magnitude2() > 0.0 {
+                vertex.normal = normal.normalize().into();
+            }
+        }
+    }
+}
+
+/*
+ * 16 bit indices when every vertex fits, half the memory of 32 bit ones. Strips stay 32 bit
+ * since their pipelines are built for RESTART_INDEX, see Topology::strip_index_format.
+ */
+fn index_format(data: &MeshData) -> IndexFormat {
+    let fits = data.vertices.len() <= u16::MAX as usize;
+    match data.topology.strip_index_format() {
+        None if fits => IndexFormat::Uint16,
+        _ => IndexFormat::Uint32,
+    }
+}
+
+// buffer writes go in 4 byte steps, so an odd count gets a padding index
+fn narrow(indices: &[u32]) -> Vec<u16> {
+    let mut narrowed: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+    if narrowed.len() % 2 == 1 {
+        narrowed.push(0);
+    }
+    narrowed
+}
+
+fn index_buffer(
+    device: &Device,
+    queue: &Queue,
+    format: IndexFormat,
+    capacity: usize,
+    indices: &[u32],
+) -> Buffer {
+    let (label, usage) = ("Index Buffer", BufferUsages::INDEX);
+    match format {
+        // rounded up to whole pairs for the padding index
+        IndexFormat::Uint16 => {
+            let capacity = capacity + capacity % 2;
+            buffer_with_capacity(device, queue, label, usage, capacity, &narrow(indices))
+        }
+        IndexFormat::Uint32 => buffer_with_capacity(device, queue, label, usage, capacity, indices),
+    }
+}
+
+// room for `capacity` elements of T, the first ones filled from `contents`
+fn buffer_with_capacity<T: bytemuck::Pod>(
+    device: &Device,
+    queue: &Queue,
+    label: &str,
+    usage: BufferUsages,
+    capacity: usize,
+    contents: &[T],
+) -> Buffer {
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: (capacity.max(1) * mem::size_of::<T>()) as BufferAddress,
+        usage: usage | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    queue.write_buffer(&buffer, 0, bytemuck::cast_slice(contents));
+    buffer
+}
+
+// a vertex buffer of one f32 per vertex, all set to `value`
+fn per_vertex_buffer(
+    device: &Device,
+    queue: &Queue,
+    label: &str,
+    capacity: usize,
+    value: f32,
+) -> Buffer {
+    buffer_with_capacity(
+        device,
+        queue,
+        label,
+        BufferUsages::VERTEX,
+        capacity,
+        &vec![value; capacity],
+    )
+}
+
+/*
+ * GPU copy of a MeshData. The buffers can hold more than `data` currently uses, so
+ * edits through `update_vertices`/`update_indices` are written in place and only
+ * reallocate, to the next power of two, once they outgrow the capacity.
+ */
+pub struct Mesh {
+    pub data: MeshData,
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    // what `index_buffer` holds, picked per object from its vertex count
+    pub index_format: IndexFormat,
+    // one f32 per vertex at heatmap::SCALAR_SLOT, zeroed until data is set
+    pub scalar_buffer: Buffer,
+    // one f32 per vertex at ao::AO_SLOT, unoccluded until baked
+    pub ao_buffer: Buffer,
+    pub vertex_capacity: usize,
+    pub index_capacity: usize,
+    pub morph: Morph,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        morph_layout: &BindGroupLayout,
+        data: MeshData,
+    ) -> Self {
+        let (vertex_capacity, index_capacity) = (data.vertices.len(), data.indices.len());
+        let vertex_buffer = buffer_with_capacity(
+            device,
+            queue,
+            "Vertex Buffer",
+            BufferUsages::VERTEX,
+            vertex_capacity,
+            &data.vertices,
+        );
+        let index_format = index_format(&data);
+        let index_buffer = index_buffer(device, queue, index_format, index_capacity, &data.indices);
+        let scalar_buffer = per_vertex_buffer(device, queue, "Scalar Buffer", vertex_capacity, 0.0);
+        let ao_buffer = per_vertex_buffer(device, queue, "AO Buffer", vertex_capacity, 1.0);
+
+        let morph = Morph::new(device, morph_layout, &data);
+
+        Self {
+            data,
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            scalar_buffer,
+            ao_buffer,
+            vertex_capacity,
+            index_capacity,
+            morph,
+        }
+    }
+
+    /*
+     * Overwrites vertices from `offset` on, appending whatever runs past the end. Returns
+     * whether the buffers had to be reallocated, which also resets the scalars and the AO.
+     */
+    pub fn update_vertices(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        offset: usize,
+        vertices: &[Vertex],
+    ) -> Result<bool, String> {
+        let len = self.data.vertices.len();
+        if offset > len {
+            return Err(format!("vertex offset {} past the end ({})", offset, len));
+        }
+        let end = offset + vertices.len();
+        // the morph deltas are laid out by vertex count, see shader.wgsl
+        if end > len && !self.data.morph_targets.is_empty() {
+            return Err("can't add vertices to a mesh with morph targets".to_owned());
+        }
+        let overlap = (len - offset).min(vertices.len());
+        self.data.vertices[offset..offset + overlap].copy_from_slice(&vertices[..overlap]);
+        self.data.vertices.extend_from_slice(&vertices[overlap..]);
+        // grown past what 16 bits can index
+        if index_format(&self.data) != self.index_format {
+            self.index_format = index_format(&self.data);
+            self.index_buffer = index_buffer(
+                device,
+                queue,
+                self.index_format,
+                self.index_capacity,
+                &self.data.indices,
+            );
+        }
+        if end <= self.vertex_capacity {
+            let start = (offset * mem::size_of::<Vertex>()) as BufferAddress;
+            queue.write_buffer(&self.vertex_buffer, start, bytemuck::cast_slice(vertices));
+            return Ok(false);
+        }
+        self.vertex_capacity = end.next_power_of_two();
+        self.vertex_buffer = buffer_with_capacity(
+            device,
+            queue,
+            "Vertex Buffer",
+            BufferUsages::VERTEX,
+            self.vertex_capacity,
+            &self.data.vertices,
+        );
+        let capacity = self.vertex_capacity;
+        self.scalar_buffer = per_vertex_buffer(device, queue, "Scalar Buffer", capacity, 0.0);
+        self.ao_buffer = per_vertex_buffer(device, queue, "AO Buffer", capacity, 1.0);
+        Ok(true)
+    }
+
+    // same as `update_vertices` for indices, which have to refer to existing vertices
+    pub fn update_indices(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        offset: usize,
+        indices: &[u32],
+    ) -> Result<bool, String> {
+        let len = self.data.indices.len();
+        if offset > len {
+            return Err(format!("index offset {} past the end ({})", offset, len));
+        }
+        let restart_allowed = self.data.topology.strip_index_format().is_some();
+        let vertex_count = self.data.vertices.len();
+        let invalid = indices.iter().find(|&&index| {
+            index as usize >= vertex_count && !(restart_allowed && index == RESTART_INDEX)
+        });
+        if let Some(index) = invalid {
+            return Err(format!(
+                "index {} out of range for {} vertices",
+                index, vertex_count
+            ));
+        }
+        let end = offset + indices.len();
+        let overlap = (len - offset).min(indices.len());
+        self.data.indices[offset..offset + overlap].copy_from_slice(&indices[..overlap]);
+        self.data.indices.extend_from_slice(&indices[overlap..]);
+        if end <= self.index_capacity {
+            match self.index_format {
+                // from a whole pair on, with the index after the update to fill the last one
+                IndexFormat::Uint16 => {
+                    let (start, stop) = (offset - offset % 2, end + end % 2);
+                    let indices = &self.data.indices[start..stop.min(self.data.indices.len())];
+                    let start = (start * mem::size_of::<u16>()) as BufferAddress;
+                    queue.write_buffer(
+                        &self.index_buffer,
+                        start,
+                        bytemuck::cast_slice(&narrow(indices)),
+                    );
+                }
+                IndexFormat::Uint32 => {
+                    let start = (offset * mem::size_of::<u32>()) as BufferAddress;
+                    queue.write_buffer(&self.index_buffer, start, bytemuck::cast_slice(indices));
+                }
+            }
+            return Ok(false);
+        }
+        self.index_capacity = end.next_power_of_two();
+        self.index_buffer = index_buffer(
+            device,
+            queue,
+            self.index_format,
+            self.index_capacity,
+            &self.data.indices,
+        );
+        Ok(true)
+    }
+
+    // `scalars` has to hold exactly one value per vertex
+    pub fn set_scalars(&self, queue: &Queue, scalars: &[f32]) {
+        debug_assert_eq!(scalars.len(), self.data.vertices.len());
+        queue.write_buffer(&self.scalar_buffer, 0, bytemuck::cast_slice(scalars));
+    }
+
+    // same as `set_scalars`, 1 is unoccluded
+    pub fn set_ao(&self, queue: &Queue, ao: &[f32]) {
+        debug_assert_eq!(ao.len(), self.data.vertices.len());
+        queue.write_buffer(&self.ao_buffer, 0, bytemuck::cast_slice(ao));
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.data.indices.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the unit quad in the xy plane as two counter-clockwise triangles, vertices in `order`
+    fn quad(order: [usize; 4]) -> MeshData {
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let slot = |corner: usize| order.iter().position(|&i| i == corner).unwrap() as u32;
+        MeshData {
+            vertices: order
+                .iter()
+                .map(|&i| Vertex {
+                    position: corners[i],
+                    normal: [0.0, 0.0, 1.0],
+                    uv: [0.0, 0.0],
+                })
+                .collect(),
+            indices: [0, 1, 2, 0, 2, 3]
+                .iter()
+                .map(|&corner| slot(corner))
+                .collect(),
+            topology: Topology::TriangleList,
+            ..Default::default()
+        }
+    }
+
+    fn positions(data: &MeshData) -> Vec<[f32; 3]> {
+        data.vertices.iter().map(|v| v.position).collect()
+    }
+
+    #[test]
+    fn canonicalize_ignores_input_order() {
+        let mut a = quad([2, 0, 3, 1]);
+        let mut b = quad([1, 3, 0, 2]);
+        a.canonicalize();
+        b.canonicalize();
+        assert_eq!(positions(&a), positions(&b));
+        assert_eq!(a.indices, b.indices);
+        // sorted by x then y, triangles rotated to start at their smallest index
+        assert_eq!(
+            positions(&a),
+            vec![
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0]
+            ]
+        );
+        assert_eq!(a.indices, vec![0, 2, 3, 0, 3, 1]);
+    }
+
+    #[test]
+    fn canonicalize_keeps_winding() {
+        let mut data = quad([3, 1, 0, 2]);
+        data.canonicalize();
+        let position = |i: u32| data.vertices[i as usize].position;
+        for [a, b, c] in data.triangles() {
+            assert!(face_normal(position(a), position(b), position(c)).z > 0.0);
+        }
+    }
+
+    #[test]
+    fn sort_key_orders_like_floats() {
+        let floats = [-2.0, -0.5, -0.0, 0.0, 0.5, 2.0];
+        for pair in floats.windows(2) {
+            assert!(sort_key(pair[0]) < sort_key(pair[1]));
+        }
+    }
+}
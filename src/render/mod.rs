@@ -1,2 +1,86 @@
+mod accumulation;
+mod ao;
+mod background;
+mod bounds;
+mod bvh;
+mod camera;
+mod capabilities;
+mod capture;
+mod clip;
+mod color_space;
+mod custom;
+mod display_mode;
+mod dump;
+mod edges;
+mod error;
+mod exposure;
+mod frames;
+mod gizmo;
+mod grid;
+mod ground_shadow;
+mod hdr;
+mod heatmap;
+mod lines;
+mod matcap;
+mod material;
+mod mesh;
+mod morph;
+mod opacity;
+mod output;
+mod overlay;
+mod pass;
+mod post;
+mod preset;
 mod renderer;
-pub use renderer::Renderer;
+mod scene;
+mod shadow;
+mod skeleton;
+mod smooth;
+mod stats;
+mod subdivide;
+mod surface;
+mod svg;
+mod texture;
+mod timing;
+mod vertex;
+mod vertex_cache;
+mod winding;
+pub use accumulation::Accumulation;
+pub use background::{Background, BackgroundFit};
+pub use bounds::Aabb;
+pub use camera::{Camera, CameraDef, CameraProjection};
+pub use capabilities::Capabilities;
+pub use capture::DiffReport;
+pub use clip::{Axis, Plane, Slice, MAX_CLIP_PLANES};
+pub use color_space::ColorSpace;
+pub use custom::{CustomUniforms, CUSTOM_UNIFORM_GROUP};
+pub use display_mode::{
+    DisplayMode, MaterialChannel, NormalSpace, DEFAULT_CREASE_ANGLE, DEFAULT_FLAT_COLOR,
+};
+pub use dump::BufferDump;
+pub use edges::{EdgeDiagnostics, SharedEdge};
+pub use error::RendererInitError;
+pub use exposure::{AutoExposure, DEFAULT_EXPOSURE_KEY};
+pub use frames::{FrameLimiter, DEFAULT_MAX_FRAMES_IN_FLIGHT};
+pub use gizmo::{Gizmo, GizmoMode};
+pub use grid::InfiniteGrid;
+pub use ground_shadow::GroundShadow;
+pub use hdr::{HdrTarget, HDR_FORMAT};
+pub use heatmap::ColorRamp;
+pub use lines::{LineData, LineVertex, Lines, DEFAULT_LINE_COLOR};
+pub use material::{MaterialFactors, CLAY_MATERIAL};
+pub use mesh::{face_normal, Mesh, MeshData, Topology, RESTART_INDEX};
+pub use morph::{Morph, MAX_MORPH_TARGETS};
+pub use opacity::{OpacityPass, MIN_WINDOW_OPACITY};
+pub use output::OutputTarget;
+pub use overlay::LineStyle;
+pub use pass::FramePass;
+pub use post::{PostContext, PostProcess};
+pub use preset::{CameraState, Preset, RenderSetup, ViewPreset};
+pub use renderer::{Renderer, DEFAULT_ADAPTER_ATTEMPTS, DEFAULT_TIMEOUT_WARNING_THRESHOLD};
+pub use scene::{KeptScenes, SceneInfo, SceneObject, StashedScene};
+pub use shadow::ShadowMap;
+pub use skeleton::{Joint, Skeleton};
+pub use stats::Stats;
+pub use timing::{GpuTimer, GpuTimings};
+pub use vertex::Vertex;
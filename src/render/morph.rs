@@ -0,0 +1,186 @@
+use std::mem;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::{MaterialFactors, MeshData};
+
+pub const MAX_MORPH_TARGETS: usize = 8;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MorphUniforms {
+    weights: [[f32; 4]; MAX_MORPH_TARGETS / 4],
+    target_count: u32,
+    vertex_count: u32,
+    depth_offset: f32,
+    _padding: u32,
+    base_color: [f32; 4],
+    // rgb, a unused
+    emissive: [f32; 4],
+    // x: metallic, y: roughness
+    material: [f32; 4],
+}
+
+/*
+ * Per-mesh morph target state. Position deltas live in a storage buffer laid out
+ * target-major (all of target 0's deltas, then target 1's, ...) and are summed by
+ * weight in the vertex shader. Meshes without targets still get a bind group so
+ * every draw can use the same pipeline. Being the one per-mesh uniform, it also
+ * carries the layer depth offset, see Renderer::set_layer_priority, and the material
+ * factors DisplayMode::Channel shows.
+ */
+pub struct Morph {
+    pub weights: Vec<f32>,
+    // subtracted from the clip space depth, positive pulls the mesh towards the camera
+    pub depth_offset: f32,
+    pub material: MaterialFactors,
+    target_count: u32,
+    vertex_count: u32,
+    uniform_buffer: Buffer,
+    _delta_buffer: Buffer,
+    pub bind_group: BindGroup,
+}
+
+impl Morph {
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Morph Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<MorphUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(16),
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn new(device: &Device, layout: &BindGroupLayout, data: &MeshData) -> Self {
+        let targets = &data.morph_targets[..data.morph_targets.len().min(MAX_MORPH_TARGETS)];
+        if targets.len() < data.morph_targets.len() {
+            log::warn!(
+                "mesh has {} morph targets, only the first {} are used",
+                data.morph_targets.len(),
+                MAX_MORPH_TARGETS
+            );
+        }
+        let mut deltas: Vec<[f32; 4]> = targets
+            .iter()
+            .flat_map(|target| target.iter().map(|d| [d[0], d[1], d[2], 0.0]))
+            .collect();
+        if deltas.is_empty() {
+            // zero-sized bindings aren't allowed
+            deltas.push([0.0; 4]);
+        }
+        let delta_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Morph Delta Buffer"),
+            contents: bytemuck::cast_slice(&deltas),
+            usage: BufferUsages::STORAGE,
+        });
+        let mut morph_weights = data.morph_weights.clone();
+        morph_weights.resize(targets.len(), 0.0);
+        let target_count = targets.len() as u32;
+        let vertex_count = data.vertices.len() as u32;
+        let material = data.material.unwrap_or_default();
+        let uniforms = Morph::uniforms(&morph_weights, target_count, vertex_count, 0.0, &material);
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Morph Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Morph Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: delta_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            weights: morph_weights,
+            depth_offset: 0.0,
+            material,
+            target_count,
+            vertex_count,
+            uniform_buffer,
+            _delta_buffer: delta_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn target_count(&self) -> usize {
+        self.target_count as usize
+    }
+
+    // extra weights are ignored, missing ones are treated as zero
+    pub fn set_weights(&mut self, queue: &Queue, weights: &[f32]) {
+        for (i, weight) in self.weights.iter_mut().enumerate() {
+            *weight = weights.get(i).copied().unwrap_or(0.0);
+        }
+        self.write_uniforms(queue);
+    }
+
+    pub fn set_depth_offset(&mut self, queue: &Queue, offset: f32) {
+        self.depth_offset = offset;
+        self.write_uniforms(queue);
+    }
+
+    fn write_uniforms(&self, queue: &Queue) {
+        let uniforms = Morph::uniforms(
+            &self.weights,
+            self.target_count,
+            self.vertex_count,
+            self.depth_offset,
+            &self.material,
+        );
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    fn uniforms(
+        weights: &[f32],
+        target_count: u32,
+        vertex_count: u32,
+        depth_offset: f32,
+        material: &MaterialFactors,
+    ) -> MorphUniforms {
+        let mut packed = [[0.0; 4]; MAX_MORPH_TARGETS / 4];
+        for (i, &weight) in weights.iter().enumerate() {
+            packed[i / 4][i % 4] = weight;
+        }
+        let [base_color, emissive, material] = material.as_uniform();
+        MorphUniforms {
+            weights: packed,
+            target_count,
+            vertex_count,
+            depth_offset,
+            _padding: 0,
+            base_color,
+            emissive,
+            material,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+use wgpu::*;
+
+use super::{PostContext, PostProcess};
+
+// below this the window is hard to find again, let alone click
+pub const MIN_WINDOW_OPACITY: f32 = 0.1;
+
+// the frame scaled by the blend constant, colour and alpha alike
+const SCALE_BY_CONSTANT: BlendComponent = BlendComponent {
+    src_factor: BlendFactor::Constant,
+    dst_factor: BlendFactor::Zero,
+    operation: BlendOperation::Add,
+};
+
+/*
+ * Fades the finished frame by multiplying it with the window opacity, the last effect
+ * the renderer runs. The frame is premultiplied, so scaling all four channels keeps it
+ * that way.
+ */
+pub struct OpacityPass {
+    pub opacity: f32,
+    bind_group_layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl OpacityPass {
+    pub fn new(device: &Device, surface_format: TextureFormat) -> Self {
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Opacity Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../opacity.wgsl"))),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Opacity Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Opacity Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Opacity Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_opacity",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_opacity",
+                targets: &[ColorTargetState {
+                    format: surface_format,
+                    blend: Some(BlendState {
+                        color: SCALE_BY_CONSTANT,
+                        alpha: SCALE_BY_CONSTANT,
+                    }),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+        });
+        Self {
+            opacity: 1.0,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
+
+impl PostProcess for OpacityPass {
+    fn label(&self) -> &str {
+        "Opacity Pass"
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+        ctx: &PostContext,
+    ) {
+        let bind_group = ctx.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Opacity Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(input),
+            }],
+        });
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Opacity Pass"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        let opacity = self.opacity as f64;
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.set_blend_constant(Color {
+            r: opacity,
+            g: opacity,
+            b: opacity,
+            a: opacity,
+        });
+        rpass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+use wgpu::*;
+
+use super::texture::{self, Texture};
+
+/*
+ * A fixed size colour target in the surface format that frames render into in place of
+ * the surface, then get scaled into the window keeping their aspect ratio, with black
+ * bars filling the rest.
+ */
+pub struct OutputTarget {
+    pub size: (u32, u32),
+    pub color: Texture,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl OutputTarget {
+    pub fn new(device: &Device, format: TextureFormat, size: (u32, u32)) -> Self {
+        let color = Texture::render_target(
+            device,
+            "Output Texture",
+            format,
+            size.0,
+            size.1,
+            1,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        );
+        let sampler = texture::sampler(device, AddressMode::ClampToEdge);
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Output Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Output Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&color.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Output Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Output Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../blit.wgsl"))),
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Output Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_blit",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_blit",
+                targets: &[format.into()],
+            }),
+        });
+
+        Self {
+            size,
+            color,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    // x, y, width and height of the output within a `window` sized one, centered
+    pub fn letterbox(&self, window: (u32, u32)) -> (f32, f32, f32, f32) {
+        let (width, height) = (self.size.0 as f32, self.size.1 as f32);
+        let (window_width, window_height) = (window.0 as f32, window.1 as f32);
+        let scale = (window_width / width).min(window_height / height);
+        let (scaled_width, scaled_height) = (width * scale, height * scale);
+        (
+            (window_width - scaled_width) / 2.0,
+            (window_height - scaled_height) / 2.0,
+            scaled_width,
+            scaled_height,
+        )
+    }
+
+    // where a point in the window lands on the output, both measured from the top left
+    pub fn window_to_output(&self, window: (u32, u32), point: (f32, f32)) -> (f32, f32) {
+        let (x, y, width, height) = self.letterbox(window);
+        (
+            (point.0 - x) / width * self.size.0 as f32,
+            (point.1 - y) / height * self.size.1 as f32,
+        )
+    }
+
+    // scales `color` into `target`, a `window` sized view in the same format
+    pub fn blit(&self, device: &Device, target: &TextureView, window: (u32, u32)) -> CommandBuffer {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Output Encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Output Pass"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            let (x, y, width, height) = self.letterbox(window);
+            rpass.set_viewport(x, y, width, height, 0.0, 1.0);
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &self.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+        encoder.finish()
+    }
+}
@@ -0,0 +1,186 @@
+use cgmath::{Deg, InnerSpace, Point3, Rad, Vector3};
+use std::collections::HashSet;
+
+use super::{gizmo::AXIS_COLORS, Axis, EdgeDiagnostics, LineData, LineVertex, MeshData, Skeleton};
+
+// joint markers' size as a fraction of the scene's bounding radius
+pub const JOINT_MARKER_SCALE: f32 = 0.01;
+
+// appearance of the lines the renderer generates itself, loaded lines keep their own colours
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+    pub wireframe_color: [f32; 4],
+    pub grid_color: [f32; 4],
+    pub boundary_edge_color: [f32; 4],
+    pub non_manifold_edge_color: [f32; 4],
+    pub bone_color: [f32; 4],
+    pub joint_color: [f32; 4],
+    // outline of the triangle picked with Renderer::select_triangle
+    pub selection_color: [f32; 4],
+    // see DisplayMode::FeatureEdges
+    pub feature_edge_color: [f32; 4],
+    // world units between grid lines seen from ten of them away, zooming steps it by tens
+    pub grid_spacing: f32,
+    // how far from the eye grid lines fade out, None scales it with the camera's distance
+    pub grid_fade_distance: Option<f32>,
+    // the grid's lines through the origin in the x and y axis colours
+    pub grid_axis_highlight: bool,
+    // wgpu only rasterizes single pixel lines, anything else is kept for when that changes
+    pub line_width: f32,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            wireframe_color: [0.0, 0.0, 0.0, 1.0],
+            grid_color: [0.5, 0.5, 0.5, 1.0],
+            boundary_edge_color: [1.0, 0.8, 0.0, 1.0],
+            non_manifold_edge_color: [1.0, 0.0, 1.0, 1.0],
+            bone_color: [0.0, 0.8, 1.0, 1.0],
+            joint_color: [1.0, 0.3, 0.0, 1.0],
+            selection_color: [1.0, 1.0, 0.0, 1.0],
+            feature_edge_color: [1.0, 1.0, 1.0, 1.0],
+            grid_spacing: 1.0,
+            grid_fade_distance: None,
+            grid_axis_highlight: true,
+            line_width: 1.0,
+        }
+    }
+}
+
+// the mesh's vertices as line vertices, for drawing a subset of its edges
+fn mesh_lines(mesh: &MeshData, color: [f32; 4]) -> LineData {
+    LineData {
+        vertices: mesh
+            .vertices
+            .iter()
+            .map(|v| LineVertex {
+                position: v.position,
+                color,
+            })
+            .collect(),
+        indices: Vec::new(),
+    }
+}
+
+pub fn edges(mesh: &MeshData, edges: &[[u32; 2]], color: [f32; 4]) -> LineData {
+    let mut lines = mesh_lines(mesh, color);
+    lines.indices = edges.concat();
+    lines
+}
+
+// every edge of every triangle once, shared edges aren't doubled up
+pub fn wireframe(mesh: &MeshData, color: [f32; 4]) -> LineData {
+    let mut edges = HashSet::new();
+    let mut lines = mesh_lines(mesh, color);
+    for [a, b, c] in mesh.triangles() {
+        for &(i, j) in [(a, b), (b, c), (c, a)].iter() {
+            if edges.insert((i.min(j), i.max(j))) {
+                lines.indices.extend_from_slice(&[i, j]);
+            }
+        }
+    }
+    lines
+}
+
+/*
+ * Boundary and non-manifold edges, creases turning further than `crease_angle`, and
+ * silhouettes, where one face of the edge looks towards the viewer and the other away.
+ * `towards_viewer` gives the direction to the viewer from a point in the mesh's own space.
+ */
+pub fn feature_edges(
+    mesh: &MeshData,
+    edges: &EdgeDiagnostics,
+    crease_angle: f32,
+    towards_viewer: impl Fn(Point3<f32>) -> Vector3<f32>,
+    color: [f32; 4],
+) -> LineData {
+    let mut lines = mesh_lines(mesh, color);
+    lines.indices = edges.boundary.concat();
+    lines.indices.extend(edges.non_manifold.concat());
+    let crease = Rad::from(Deg(crease_angle)).0.cos();
+    for shared in &edges.shared {
+        let [a, b] = shared.edge;
+        let position = |i: u32| Point3::from(mesh.vertices[i as usize].position);
+        let view = towards_viewer(position(a) + (position(b) - position(a)) / 2.0);
+        let [n0, n1] = shared.normals;
+        let silhouette = (n0.dot(view) > 0.0) != (n1.dot(view) > 0.0);
+        if silhouette || n0.normalize().dot(n1.normalize()) < crease {
+            lines.indices.extend_from_slice(&[a, b]);
+        }
+    }
+    lines
+}
+
+pub fn triangle(mesh: &MeshData, [a, b, c]: [u32; 3], color: [f32; 4]) -> LineData {
+    let mut lines = mesh_lines(mesh, color);
+    lines.indices = vec![a, b, b, c, c, a];
+    lines
+}
+
+/*
+ * The three axes `size` long from `center`, with an arrowhead on whichever is up so it
+ * stands out from the other two, which only go half as far.
+ */
+pub fn up_axis_indicator(center: Point3<f32>, size: f32, up: Axis) -> LineData {
+    let mut lines = LineData::default();
+    let mut push = |from: Point3<f32>, to: Point3<f32>, color: [f32; 4]| {
+        let base = lines.vertices.len() as u32;
+        lines.vertices.push(LineVertex {
+            position: from.into(),
+            color,
+        });
+        lines.vertices.push(LineVertex {
+            position: to.into(),
+            color,
+        });
+        lines.indices.extend_from_slice(&[base, base + 1]);
+    };
+    for (i, &axis) in [Axis::X, Axis::Y, Axis::Z].iter().enumerate() {
+        if axis != up {
+            push(center, center + axis.unit() * size * 0.5, AXIS_COLORS[i]);
+            continue;
+        }
+        let tip = center + axis.unit() * size;
+        push(center, tip, AXIS_COLORS[i]);
+        let base = tip - axis.unit() * size * 0.25;
+        // the other two axes, so the head shows from any direction
+        let (u, v) = match axis {
+            Axis::X => (Vector3::unit_y(), Vector3::unit_z()),
+            Axis::Y => (Vector3::unit_z(), Vector3::unit_x()),
+            Axis::Z => (Vector3::unit_x(), Vector3::unit_y()),
+        };
+        for &side in [u, -u, v, -v].iter() {
+            push(tip, base + side * size * 0.1, AXIS_COLORS[i]);
+        }
+    }
+    lines
+}
+
+// a line per bone, and a little three axis cross `marker_size` across on every joint
+pub fn skeleton(skeleton: &Skeleton, marker_size: f32, style: &LineStyle) -> LineData {
+    let mut lines = LineData::default();
+    let mut push = |from: Point3<f32>, to: Point3<f32>, color: [f32; 4]| {
+        let base = lines.vertices.len() as u32;
+        lines.vertices.push(LineVertex {
+            position: from.into(),
+            color,
+        });
+        lines.vertices.push(LineVertex {
+            position: to.into(),
+            color,
+        });
+        lines.indices.extend_from_slice(&[base, base + 1]);
+    };
+    for (parent, child) in skeleton.bones() {
+        push(parent, child, style.bone_color);
+    }
+    let half = marker_size / 2.0;
+    for joint in &skeleton.joints {
+        for &axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()].iter() {
+            let p = joint.position;
+            push(p - axis * half, p + axis * half, style.joint_color);
+        }
+    }
+    lines
+}
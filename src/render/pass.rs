@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use wgpu::*;
+
+use super::{
+    ao::AO_SLOT, heatmap::SCALAR_SLOT, DisplayMode, Renderer, SceneObject, Topology,
+    CUSTOM_UNIFORM_GROUP,
+};
+
+/*
+ * One render pass into the frame's colour and depth targets. `Renderer::draw` runs the
+ * scene first, which clears both targets, then every added pass in order, each loading
+ * what came before so they composite on top.
+ */
+pub trait FramePass {
+    fn label(&self) -> &str;
+    fn draw<'a>(&'a self, renderer: &'a Renderer, rpass: &mut RenderPass<'a>);
+}
+
+/*
+ * Starts a pass into the frame's targets, clearing them to `clear` or loading what's there.
+ * Doesn't need the renderer, so the encoding threads can call it too.
+ */
+pub(super) fn begin<'a>(
+    encoder: &'a mut CommandEncoder,
+    label: &str,
+    (view, resolve_target): (&'a TextureView, Option<&'a TextureView>),
+    depth: &'a TextureView,
+    clear: Option<Color>,
+) -> RenderPass<'a> {
+    encoder.begin_render_pass(&RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: Operations {
+                load: clear.map_or(LoadOp::Load, LoadOp::Clear),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+            view: depth,
+            depth_ops: Some(Operations {
+                load: clear.map_or(LoadOp::Load, |_| LoadOp::Clear(1.0)),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+    })
+}
+
+// what drawing objects needs from the renderer, all of it safe to share between threads
+pub(super) struct ObjectBatch<'a> {
+    pub pipelines: &'a HashMap<Topology, RenderPipeline>,
+    pub coverage_pipelines: &'a HashMap<Topology, RenderPipeline>,
+    pub bind_group: &'a BindGroup,
+    pub custom_bind_group: &'a BindGroup,
+}
+
+impl<'a> ObjectBatch<'a> {
+    pub fn new(renderer: &'a Renderer) -> Self {
+        // feature edges only want the surface's depth, for hiding the edges behind it
+        let (pipelines, coverage_pipelines) = match renderer.display_mode {
+            DisplayMode::FeatureEdges(_) => (
+                &renderer.depth_only_pipelines,
+                &renderer.depth_only_pipelines,
+            ),
+            _ => (&renderer.pipelines, &renderer.coverage_pipelines),
+        };
+        Self {
+            pipelines,
+            coverage_pipelines,
+            bind_group: renderer.bind_group.as_ref().unwrap(),
+            custom_bind_group: &renderer.custom_uniforms.bind_group,
+        }
+    }
+
+    pub fn draw<I>(&self, objects: I, rpass: &mut RenderPass<'a>)
+    where
+        I: IntoIterator<Item = &'a SceneObject>,
+    {
+        rpass.push_debug_group("preparing data for drawing...");
+        rpass.set_bind_group(0, self.bind_group, &[]);
+        rpass.set_bind_group(CUSTOM_UNIFORM_GROUP, self.custom_bind_group, &[]);
+        rpass.pop_debug_group();
+        for object in objects {
+            rpass.insert_debug_marker(&object.name);
+            let topology = &object.mesh.data.topology;
+            let coverage = object
+                .alpha_cutoff()
+                .and_then(|_| self.coverage_pipelines.get(topology));
+            let pipeline = match coverage.or_else(|| self.pipelines.get(topology)) {
+                Some(pipeline) => pipeline,
+                None => continue,
+            };
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(1, &object.mesh.morph.bind_group, &[]);
+            rpass.set_index_buffer(object.mesh.index_buffer.slice(..), object.mesh.index_format);
+            rpass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+            rpass.set_vertex_buffer(SCALAR_SLOT, object.mesh.scalar_buffer.slice(..));
+            rpass.set_vertex_buffer(AO_SLOT, object.mesh.ao_buffer.slice(..));
+            rpass.draw_indexed(0..object.mesh.index_count(), 0, 0..1);
+        }
+    }
+}
+
+fn draw_background<'a>(renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+    if let (Some(background), Some(pipeline)) =
+        (&renderer.background, &renderer.background_pipeline)
+    {
+        rpass.insert_debug_marker("Background");
+        background.draw(pipeline, rpass);
+    }
+}
+
+// after the objects so they hide it, and under the ground shadow
+fn draw_grid<'a>(renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+    if let Some(grid) = renderer.drawn_grid() {
+        rpass.insert_debug_marker("Grid");
+        grid.draw(rpass);
+    }
+}
+
+// after the objects so they hide it
+fn draw_ground_shadow<'a>(renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+    if let Some(ground) = renderer.drawn_ground_shadow() {
+        rpass.insert_debug_marker("Ground Shadow");
+        ground.draw(rpass);
+    }
+}
+
+fn draw_lines<'a>(renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+    let has_lines = !renderer.lines.is_empty()
+        || !renderer.overlays.is_empty()
+        || !renderer.wireframe_lines.is_empty()
+        || !renderer.feature_edge_lines.is_empty();
+    if let (Some(pipeline), true) = (&renderer.line_pipeline, has_lines) {
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, renderer.bind_group.as_ref().unwrap(), &[]);
+        for lines in renderer
+            .lines
+            .iter()
+            .chain(renderer.wireframe_lines.iter())
+            .chain(renderer.overlays.iter())
+            .chain(renderer.feature_edge_lines.iter())
+        {
+            rpass.insert_debug_marker(&lines.name);
+            rpass.set_index_buffer(lines.index_buffer.slice(..), IndexFormat::Uint32);
+            rpass.set_vertex_buffer(0, lines.vertex_buffer.slice(..));
+            rpass.draw_indexed(0..lines.index_count(), 0, 0..1);
+        }
+    }
+}
+
+// last, over everything else, along with the up axis indicator
+fn draw_gizmo<'a>(renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+    let pipeline = match &renderer.gizmo_pipeline {
+        Some(pipeline) => pipeline,
+        None => return,
+    };
+    rpass.set_pipeline(pipeline);
+    rpass.set_bind_group(0, renderer.bind_group.as_ref().unwrap(), &[]);
+    for lines in renderer
+        .gizmo_lines
+        .iter()
+        .chain(renderer.up_indicator.iter())
+    {
+        rpass.insert_debug_marker(&lines.name);
+        rpass.set_index_buffer(lines.index_buffer.slice(..), IndexFormat::Uint32);
+        rpass.set_vertex_buffer(0, lines.vertex_buffer.slice(..));
+        rpass.draw_indexed(0..lines.index_count(), 0, 0..1);
+    }
+}
+
+// the loaded objects with the main pipelines
+pub struct ScenePass;
+
+impl FramePass for ScenePass {
+    fn label(&self) -> &str {
+        "Scene Pass"
+    }
+
+    fn draw<'a>(&'a self, renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+        draw_background(renderer, rpass);
+        ObjectBatch::new(renderer).draw(renderer.draw_order(), rpass);
+        draw_grid(renderer, rpass);
+        draw_ground_shadow(renderer, rpass);
+        draw_lines(renderer, rpass);
+        draw_gizmo(renderer, rpass);
+    }
+}
+
+/*
+ * The scene split up for recording on several threads: the background clears the targets,
+ * the objects are drawn by ObjectBatch in their own command buffers, and then the lines.
+ */
+pub struct BackgroundPass;
+
+impl FramePass for BackgroundPass {
+    fn label(&self) -> &str {
+        "Background Pass"
+    }
+
+    fn draw<'a>(&'a self, renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+        draw_background(renderer, rpass);
+    }
+}
+
+pub struct LinePass;
+
+impl FramePass for LinePass {
+    fn label(&self) -> &str {
+        "Line Pass"
+    }
+
+    fn draw<'a>(&'a self, renderer: &'a Renderer, rpass: &mut RenderPass<'a>) {
+        draw_grid(renderer, rpass);
+        draw_ground_shadow(renderer, rpass);
+        draw_lines(renderer, rpass);
+        draw_gizmo(renderer, rpass);
+    }
+}
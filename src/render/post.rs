@@ -0,0 +1,84 @@
+use wgpu::*;
+
+use super::texture::Texture;
+
+// what every effect in the chain gets to build resources with
+pub struct PostContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    // of both the input and the output, the surface format
+    pub format: TextureFormat,
+    pub size: (u32, u32),
+}
+
+/*
+ * A full frame effect run by the renderer after the frame is finished and tonemapped,
+ * see `Renderer::add_post_process`. `input` holds the frame so far and can be sampled,
+ * the effect has to cover every pixel of `output` since nothing else is drawn there.
+ * Both are single sampled, `PostContext::size` big and in `PostContext::format`.
+ */
+pub trait PostProcess {
+    fn label(&self) -> &str;
+    fn apply(
+        &self,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+        ctx: &PostContext,
+    );
+}
+
+// the two textures effects take turns reading from and writing to
+pub(super) struct PostTargets {
+    pub size: (u32, u32),
+    pub textures: [Texture; 2],
+}
+
+impl PostTargets {
+    pub fn new(device: &Device, format: TextureFormat, size: (u32, u32)) -> Self {
+        let texture = |label: &str| {
+            Texture::render_target(
+                device,
+                label,
+                format,
+                size.0,
+                size.1,
+                1,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            )
+        };
+        Self {
+            size,
+            textures: [
+                texture("Post Process Texture A"),
+                texture("Post Process Texture B"),
+            ],
+        }
+    }
+
+    // where the frame goes before the first effect reads it
+    pub fn frame(&self) -> &TextureView {
+        &self.textures[0].view
+    }
+
+    // every effect in order, the last one writing into `target`
+    pub fn run(
+        &self,
+        effects: &[&dyn PostProcess],
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        ctx: &PostContext,
+    ) {
+        for (i, effect) in effects.iter().enumerate() {
+            let input = &self.textures[i % 2].view;
+            let output = if i + 1 == effects.len() {
+                target
+            } else {
+                &self.textures[(i + 1) % 2].view
+            };
+            encoder.push_debug_group(effect.label());
+            effect.apply(encoder, input, output, ctx);
+            encoder.pop_debug_group();
+        }
+    }
+}
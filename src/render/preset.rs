@@ -0,0 +1,120 @@
+use cgmath::{Deg, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{Camera, ColorSpace, DisplayMode, NormalSpace};
+
+// named combinations of view settings, see Renderer::apply_preset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    // orthographic front view, shaded with the wireframe on top, no grid, framed tightly
+    TechnicalDrawing,
+}
+
+// where the camera is and how it's pointed, the rest of Camera is left to the renderer
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    // vertical field of view in degrees
+    pub fovy: f32,
+}
+
+impl CameraState {
+    pub fn of(camera: &Camera) -> Self {
+        Self {
+            eye: camera.eye.into(),
+            target: camera.target.into(),
+            up: camera.up.into(),
+            fovy: camera.fovy.0,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.eye = Point3::from(self.eye);
+        camera.target = Point3::from(self.target);
+        camera.up = Vector3::from(self.up);
+        camera.fovy = Deg(self.fovy);
+    }
+}
+
+/*
+ * How a model likes to be presented, kept as JSON in a sidecar next to it so the view
+ * comes back the same every time the model is opened. See Renderer::view_preset.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ViewPreset {
+    // column major, like cgmath's conversion to arrays
+    pub model_transform: [[f32; 4]; 4],
+    pub camera_state: CameraState,
+    pub display_mode: DisplayMode,
+    // rgba
+    pub clear_color: [f64; 4],
+}
+
+/*
+ * The renderer's global configuration, none of it tied to a model, for making every
+ * screenshot on every machine look the same. See Renderer::export_setup.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RenderSetup {
+    pub camera_state: CameraState,
+    // half height of the orthographic view, perspective while None
+    pub ortho_height: Option<f32>,
+    pub light_direction: [f32; 3],
+    // shadow map resolution, None while shadows are off
+    pub shadows: Option<u32>,
+    // opacity of the ground shadow, None while it's off
+    pub ground_shadow: Option<f32>,
+    // rgba
+    pub clear_color: [f64; 4],
+    pub display_mode: DisplayMode,
+    pub normal_space: NormalSpace,
+    pub color_space: ColorSpace,
+    // samples of the HDR target, None renders straight to the surface
+    pub hdr_samples: Option<u32>,
+    // tonemap exposure, only used with HDR on
+    pub exposure: f32,
+}
+
+impl RenderSetup {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, json)
+    }
+}
+
+impl ViewPreset {
+    // `model.gltf` gets `model.view.json`
+    pub fn sidecar_path(model: &Path) -> PathBuf {
+        model.with_extension("view.json")
+    }
+
+    // a missing sidecar is None, one that doesn't parse is an error
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, json)
+    }
+}
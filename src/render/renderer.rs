@@ -1,91 +1,2546 @@
-use std::{borrow::Cow, mem};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fs, io, iter, mem,
+    path::{Path, PathBuf},
+    slice,
+    sync::mpsc::{Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     *,
 };
 use winit::window::Window;
 
-#[rustfmt::skip]
-const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.0,
-    0.0, 0.0, 0.5, 1.0,
-);
-pub struct Renderer {
-    pub instance: Instance,
-    pub surface: Surface,
-    pub surface_config: SurfaceConfiguration,
-    pub adapter: Adapter,
-    pub device: Device,
-    pub queue: Queue,
-    pub active_pipeline: Option<RenderPipeline>,
-    pub vertex_buffer: Option<Buffer>,
-    pub index_buffer: Option<Buffer>,
-    pub index_count: usize,
-    pub bind_group: Option<BindGroup>,
-}
+use crate::loader::{self, Conversion, LoadError, LoadedModel};
+
+use super::{
+    ao,
+    capture::{self, DiffReport},
+    dump,
+    frames::{FrameLimiter, DEFAULT_MAX_FRAMES_IN_FLIGHT},
+    heatmap, lines, matcap, mesh,
+    opacity::{OpacityPass, MIN_WINDOW_OPACITY},
+    overlay,
+    pass::{self, BackgroundPass, FramePass, LinePass, ObjectBatch, ScenePass},
+    post::PostTargets,
+    scene, smooth, subdivide, surface, svg,
+    texture::{self, Texture, DEPTH_FORMAT},
+    Aabb, Accumulation, AutoExposure, Axis, Background, BackgroundFit, Camera, CameraDef,
+    CameraState, Capabilities, ColorRamp, ColorSpace, CustomUniforms, DisplayMode, Gizmo,
+    GizmoMode, GpuTimer, GroundShadow, HdrTarget, InfiniteGrid, KeptScenes, LineData, LineStyle,
+    Lines, MaterialFactors, Mesh, MeshData, Morph, NormalSpace, OutputTarget, Plane, PostContext,
+    PostProcess, Preset, RenderSetup, RendererInitError, SceneInfo, SceneObject, ShadowMap,
+    Skeleton, Slice, StashedScene, Stats, Topology, Vertex, ViewPreset, HDR_FORMAT,
+    MAX_CLIP_PLANES,
+};
+
+const MATCAP_SIZE: u32 = 256;
+// compared against the light-space depth on top of the shadow pass' own bias
+const SHADOW_BIAS: f32 = 0.001;
+// scenes centred further than this from the origin get rebased, see `add_object`
+const REBASE_DISTANCE: f32 = 10_000.0;
+// some drivers and VMs only hand out an adapter on a second try
+pub const DEFAULT_ADAPTER_ATTEMPTS: u32 = 3;
+// timeouts in a row acquiring the surface texture before `draw` suggests another present mode
+pub const DEFAULT_TIMEOUT_WARNING_THRESHOLD: u32 = 10;
+const ADAPTER_RETRY_DELAY: Duration = Duration::from_millis(250);
+// a bit over a hundred steps of the Depth32Float buffer where its precision is coarsest, near 1
+const DEFAULT_LAYER_EPSILON: f32 = 1e-5;
+// how far the gizmo's handles reach, relative to the scene's bounding radius
+const GIZMO_SCALE: f32 = 1.25;
+// in pixels, how far the up axis indicator sits from the bottom left corner and how long it is
+const UP_INDICATOR_INSET: f32 = 48.0;
+const UP_INDICATOR_SIZE: f32 = 32.0;
+// scenes with more objects than this get them recorded on several threads
+const OBJECTS_PER_BATCH: usize = 64;
+// seconds the view has to hold still before progressive rendering goes back to full quality
+const PROGRESSIVE_IDLE_TIME: f32 = 0.25;
+const DEFAULT_CLEAR_COLOR: Color = Color {
+    r: 0.1,
+    g: 0.1,
+    b: 0.6,
+    a: 1.0,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    transform: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    clip_planes: [[f32; 4]; MAX_CLIP_PLANES],
+    display_mode: u32,
+    time: f32,
+    color_space: u32,
+    // 1 when the surface applies the sRGB curve itself on write
+    surface_srgb: u32,
+    light_view_proj: [[f32; 4]; 4],
+    light_direction: [f32; 4],
+    // x: enabled, y: shadow map texel size, z: depth bias
+    shadow_params: [f32; 4],
+    color_ramp: u32,
+    // negative while slicing is off
+    slice_half_width: f32,
+    normal_space: u32,
+    vertex_ao: u32,
+    // xyz: slab normal, w: centre of the slab along it
+    slice: [f32; 4],
+    // rgb: flat colour for back faces, a: 1 while it's used
+    backface_color: [f32; 4],
+    flat_color: [f32; 4],
+    alpha_to_coverage: u32,
+    // 1 while the override_ factors replace every object's material
+    material_override: u32,
+    _padding: [u32; 2],
+    // see MaterialFactors::as_uniform
+    override_base_color: [f32; 4],
+    override_emissive: [f32; 4],
+    override_material: [f32; 4],
+    // x: near, y: far, z: 1 for orthographic, w: 1 when depth is 0..1, see Camera::clip_range
+    depth_range: [f32; 4],
+}
+
+pub struct Renderer {
+    pub instance: Instance,
+    pub surface: Surface,
+    pub surface_config: SurfaceConfiguration,
+    pub adapter: Adapter,
+    pub device: Device,
+    pub queue: Queue,
+    // the main pipeline for every topology in the scene, built as objects need them
+    pub pipelines: HashMap<Topology, RenderPipeline>,
+    // alpha to coverage variants for objects with an alpha cutoff, only while multisampling
+    pub coverage_pipelines: HashMap<Topology, RenderPipeline>,
+    // variants writing only depth, so feature edges get hidden without the surface showing
+    pub depth_only_pipelines: HashMap<Topology, RenderPipeline>,
+    pub objects: Vec<SceneObject>,
+    pub lines: Vec<Lines>,
+    pub line_pipeline: Option<RenderPipeline>,
+    // see `set_gizmo`, drawn over everything with its own line pipeline
+    pub gizmo: Option<Gizmo>,
+    pub gizmo_lines: Option<Lines>,
+    pub gizmo_pipeline: Option<RenderPipeline>,
+    // which axis the camera keeps up, see `set_up_axis`
+    pub up_axis: Axis,
+    // drawn in the corner like the gizmo, rebuilt by `update` whenever the view moves
+    pub up_indicator_visible: bool,
+    pub up_indicator: Option<Lines>,
+    // one per object in order while wireframe_visible, moved in place by the vertex animator
+    pub wireframe_lines: Vec<Lines>,
+    // diagnostics, regenerated from the scene rather than loaded
+    pub overlays: Vec<Lines>,
+    // silhouettes and creases while in DisplayMode::FeatureEdges, rebuilt as the view moves
+    pub feature_edge_lines: Vec<Lines>,
+    pub line_style: LineStyle,
+    pub grid_visible: bool,
+    // built the first time it's shown, see `show_grid`
+    pub grid: Option<InfiniteGrid>,
+    pub wireframe_visible: bool,
+    // boundary and non-manifold edges, see EdgeDiagnostics
+    pub edge_diagnostics_visible: bool,
+    // rigs from the loaded files, drawn as an overlay while skeleton_visible
+    pub skeletons: Vec<Skeleton>,
+    pub skeleton_visible: bool,
+    pub camera: Camera,
+    pub gltf_cameras: Vec<CameraDef>,
+    // where the render space origin sits in model coordinates
+    pub origin: Vector3<f64>,
+    // index into gltf_cameras, None while using the framing camera
+    pub active_gltf_camera: Option<usize>,
+    // object and triangle index, see `select_triangle`
+    pub selected_triangle: Option<(usize, usize)>,
+    pub isolate_selection: bool,
+    // draw nearest objects first, see `set_opaque_sorting`
+    pub opaque_sorting: bool,
+    // the selected triangle on its own, drawn instead of the objects while isolating
+    pub isolated: Option<SceneObject>,
+    // the scenes of the glTF file on show, see `set_gltf_scenes`
+    pub gltf_scenes: Vec<SceneInfo>,
+    pub active_gltf_scene: Option<usize>,
+    // what `clear_scene` took down last, see `swap_previous_scene`
+    pub previous_scene: Option<StashedScene>,
+    // bytes of GPU memory models may take up, see `check_memory`
+    pub memory_budget: Option<u64>,
+    // what streamed files go through before upload, see `load_streaming`
+    pub load_conversion: Conversion,
+    // moves the whole scene so the chosen pivot lands on the camera target, see `set_pivot`
+    pub pivot_offset: Vector3<f32>,
+    // applied to the whole scene when drawing, like the pivot, see `set_model_transform`
+    pub model_transform: Matrix4<f32>,
+    pub bind_group: Option<BindGroup>,
+    pub uniform_buffer: Option<Buffer>,
+    pub clip_planes: [Option<Plane>; MAX_CLIP_PLANES],
+    pub slice: Option<Slice>,
+    pub display_mode: DisplayMode,
+    // shades every object with this instead of its own material, see `set_material_override`
+    pub material_override: Option<MaterialFactors>,
+    pub normal_space: NormalSpace,
+    // shade with the occlusion from `bake_vertex_ao`
+    pub vertex_ao_visible: bool,
+    pub bind_group_layout: Option<BindGroupLayout>,
+    pub matcap: Texture,
+    // kept so the texture can be re-uploaded when the colour space changes
+    pub matcap_image: image::RgbaImage,
+    pub color_space: ColorSpace,
+    pub color_ramp: ColorRamp,
+    // normalized, in the order of the objects' vertices one after the other
+    pub vertex_scalars: Option<Vec<f32>>,
+    // for the matcap and the background, always ClampToEdge
+    pub sampler: Sampler,
+    pub depth_texture: Texture,
+    // what `render_into` is drawing into, stands in for the surface size while set
+    pub target_size: Option<(u32, u32)>,
+    // frames render at this size and get letterboxed into the window, see `set_output_resolution`
+    pub output: Option<OutputTarget>,
+    // the scene goes through a float target and a tonemap pass when set, see `set_hdr`
+    pub hdr: Option<HdrTarget>,
+    // temporal accumulation while the view holds still, see `set_taa_accumulation`
+    pub taa: Option<Accumulation>,
+    // drives the tonemap exposure from the frame's brightness, see `set_auto_exposure`
+    pub auto_exposure: Option<AutoExposure>,
+    pub window_opacity: f32,
+    // built the first time the opacity drops below 1
+    pub opacity_pass: Option<OpacityPass>,
+    pub depth_bias: DepthBiasState,
+    // None draws both sides of every triangle
+    pub cull_mode: Option<Face>,
+    pub backface_color: Option<Color>,
+    // depth one step of layer priority is worth, see `set_layer_priority`
+    pub layer_epsilon: f32,
+    pub pipeline_layout: Option<PipelineLayout>,
+    pub shader: Option<ShaderModule>,
+    pub morph_bind_group_layout: BindGroupLayout,
+    pub custom_bind_group_layout: BindGroupLayout,
+    pub custom_uniforms: CustomUniforms,
+    // direction the light travels in, world space
+    pub light_direction: Vector3<f32>,
+    pub shadow: ShadowMap,
+    // built the first time it's turned on, see `set_ground_shadow`
+    pub ground_shadow: Option<GroundShadow>,
+    pub gpu_timer: Option<GpuTimer>,
+    pub frame_limiter: FrameLimiter,
+    // see DEFAULT_TIMEOUT_WARNING_THRESHOLD, 0 never warns
+    pub timeout_warning_threshold: u32,
+    // frames `draw` gave up on because the surface texture timed out
+    skipped_frames: Cell<u64>,
+    timeouts_in_a_row: Cell<u32>,
+    pub clear_color: Color,
+    // kept so the texture can be re-uploaded when the target changes, like matcap_image
+    pub background_image: Option<image::RgbaImage>,
+    pub background_fit: BackgroundFit,
+    pub background: Option<Background>,
+    pub background_bind_group_layout: BindGroupLayout,
+    pub background_pipeline: Option<RenderPipeline>,
+    // drawn after the scene, see FramePass
+    pub passes: Vec<Box<dyn FramePass>>,
+    // run in order on the finished frame, see `add_post_process`
+    pub post_processes: Vec<Box<dyn PostProcess>>,
+    // only there while the chain has anything in it
+    post_targets: Option<PostTargets>,
+    // simulation time in seconds, advanced by `update` rather than read from the clock
+    pub elapsed: f32,
+    // deforms the objects every update, see `set_vertex_animator`
+    vertex_animator: Option<Box<dyn FnMut(&mut [Vertex], f32)>>,
+    // each object's vertices from before the animator touched them, with the object's
+    // revision they were taken at
+    animation_rest: Vec<(u32, Vec<Vertex>)>,
+    // see `set_animated_normals`
+    pub animated_normals: bool,
+    // skip the expensive passes while the view moves, see `set_progressive`
+    pub progressive: bool,
+    // simulation time the view has gone unchanged, and what it was last update
+    still_time: f32,
+    last_view_proj: Matrix4<f32>,
+    // files still arriving from `load_streaming`, the chunks received so far and whether the
+    // camera follows them
+    pub streams: Vec<(
+        PathBuf,
+        usize,
+        bool,
+        Receiver<Result<LoadedModel, LoadError>>,
+    )>,
+    // what went wrong streaming them, until `take_load_errors` collects it
+    pub load_errors: Vec<(PathBuf, LoadError)>,
+}
+
+/*
+ * A wgpu renderer that supports drawing custom models with custom shaders / materials.
+ * Axis: camera looks in -Z, +Y is up and +X is right.
+ *
+ *        ^ +Y
+ *        |
+ *        |
+ *        |
+ *        +----------> X+
+ *       /
+ *     /
+ *  |/_ Z+
+ *
+ */
+
+impl Renderer {
+    pub fn new(window: &Window) -> Self {
+        Self::with_adapter_attempts(window, DEFAULT_ADAPTER_ATTEMPTS)
+    }
+
+    pub fn with_adapter_attempts(window: &Window, attempts: u32) -> Self {
+        pollster::block_on(Self::new_async_with_attempts(window, attempts))
+            .expect("Failed to initialise renderer.")
+    }
+
+    // for callers already running an executor, this avoids nesting pollster inside it
+    pub async fn new_async(window: &Window) -> Result<Self, RendererInitError> {
+        Self::new_async_with_attempts(window, DEFAULT_ADAPTER_ATTEMPTS).await
+    }
+
+    /*
+     * Asks for an adapter up to `attempts` times before giving up with NoAdapter.
+     * The delay between attempts blocks the calling thread, there's no async timer
+     * to wait on without pulling in a runtime.
+     */
+    pub async fn new_async_with_attempts(
+        window: &Window,
+        attempts: u32,
+    ) -> Result<Self, RendererInitError> {
+        let instance = Instance::new(Backends::PRIMARY);
+        let surface = unsafe { instance.create_surface(window) };
+        let mut adapter = None;
+        for attempt in 1..=attempts.max(1) {
+            adapter = instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: PowerPreference::HighPerformance,
+                    force_fallback_adapter: false,
+                    compatible_surface: Some(&surface),
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+            log::warn!("no adapter on attempt {} of {}", attempt, attempts.max(1));
+            if attempt < attempts {
+                thread::sleep(ADAPTER_RETRY_DELAY);
+            }
+        }
+        let adapter = adapter.ok_or(RendererInitError::NoAdapter)?;
+        let window_size = window.inner_size();
+        let (device, queue) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    label: Some("Device"),
+                    // optional, GPU timings are just left out without it and line polygon
+                    // modes are only there for custom pipelines
+                    features: adapter.features()
+                        & (Features::TIMESTAMP_QUERY | Features::NON_FILL_POLYGON_MODE),
+                    limits: Limits::default(),
+                },
+                None,
+            )
+            .await?;
+        let surface_config = surface::configure(
+            &surface,
+            &adapter,
+            &device,
+            TextureUsages::RENDER_ATTACHMENT,
+            window_size.width,
+            window_size.height,
+        )
+        .await
+        .ok_or(RendererInitError::NoSurfaceFormat)?;
+        let color_space = ColorSpace::default();
+        let matcap_image = matcap::default_matcap(MATCAP_SIZE);
+        let matcap = Texture::from_rgba(
+            &device,
+            &queue,
+            "Matcap Texture",
+            &matcap_image,
+            color_space.texture_format(),
+        );
+        let sampler = texture::sampler(&device, AddressMode::ClampToEdge);
+        let morph_bind_group_layout = Morph::bind_group_layout(&device);
+        let background_bind_group_layout = Background::bind_group_layout(&device);
+        let custom_bind_group_layout = CustomUniforms::bind_group_layout(&device);
+        let custom_uniforms = CustomUniforms::new(&device, &custom_bind_group_layout, &[]);
+        // a placeholder until shadows are turned on
+        let shadow = ShadowMap::new(&device, &morph_bind_group_layout, 1);
+        let depth_texture = Texture::depth(&device, surface_config.width, surface_config.height, 1);
+
+        Ok(Self {
+            instance,
+            surface,
+            surface_config,
+            adapter,
+            device,
+            queue,
+            pipelines: HashMap::new(),
+            coverage_pipelines: HashMap::new(),
+            depth_only_pipelines: HashMap::new(),
+            objects: Vec::new(),
+            lines: Vec::new(),
+            line_pipeline: None,
+            gizmo: None,
+            gizmo_lines: None,
+            gizmo_pipeline: None,
+            up_axis: Axis::Z,
+            up_indicator_visible: true,
+            up_indicator: None,
+            wireframe_lines: Vec::new(),
+            overlays: Vec::new(),
+            feature_edge_lines: Vec::new(),
+            line_style: LineStyle::default(),
+            grid_visible: false,
+            grid: None,
+            wireframe_visible: false,
+            edge_diagnostics_visible: false,
+            skeletons: Vec::new(),
+            skeleton_visible: false,
+            camera: Camera::default(),
+            gltf_cameras: Vec::new(),
+            origin: Vector3::new(0.0, 0.0, 0.0),
+            active_gltf_camera: None,
+            selected_triangle: None,
+            isolate_selection: false,
+            opaque_sorting: false,
+            isolated: None,
+            gltf_scenes: Vec::new(),
+            active_gltf_scene: None,
+            previous_scene: None,
+            memory_budget: None,
+            load_conversion: Conversion::default(),
+            pivot_offset: Vector3::new(0.0, 0.0, 0.0),
+            model_transform: Matrix4::identity(),
+            bind_group: None,
+            uniform_buffer: None,
+            clip_planes: [None; MAX_CLIP_PLANES],
+            slice: None,
+            display_mode: DisplayMode::default(),
+            material_override: None,
+            normal_space: NormalSpace::default(),
+            vertex_ao_visible: false,
+            bind_group_layout: None,
+            matcap,
+            matcap_image,
+            color_space,
+            color_ramp: ColorRamp::default(),
+            vertex_scalars: None,
+            sampler,
+            depth_texture,
+            target_size: None,
+            output: None,
+            hdr: None,
+            auto_exposure: None,
+            taa: None,
+            window_opacity: 1.0,
+            opacity_pass: None,
+            depth_bias: DepthBiasState::default(),
+            cull_mode: Some(Face::Back),
+            backface_color: None,
+            layer_epsilon: DEFAULT_LAYER_EPSILON,
+            pipeline_layout: None,
+            shader: None,
+            morph_bind_group_layout,
+            custom_bind_group_layout,
+            custom_uniforms,
+            light_direction: Vector3::new(-0.3, -0.6, -0.75).normalize(),
+            shadow,
+            ground_shadow: None,
+            gpu_timer: GpuTimer::new(&device, &queue),
+            frame_limiter: FrameLimiter::new(DEFAULT_MAX_FRAMES_IN_FLIGHT),
+            timeout_warning_threshold: DEFAULT_TIMEOUT_WARNING_THRESHOLD,
+            skipped_frames: Cell::new(0),
+            timeouts_in_a_row: Cell::new(0),
+            clear_color: DEFAULT_CLEAR_COLOR,
+            background_image: None,
+            background_fit: BackgroundFit::default(),
+            background: None,
+            background_bind_group_layout,
+            background_pipeline: None,
+            passes: Vec::new(),
+            post_processes: Vec::new(),
+            post_targets: None,
+            elapsed: 0.0,
+            vertex_animator: None,
+            animation_rest: Vec::new(),
+            animated_normals: false,
+            progressive: false,
+            still_time: 0.0,
+            last_view_proj: Matrix4::identity(),
+            streams: Vec::new(),
+            load_errors: Vec::new(),
+        })
+    }
+
+    // what this device supports, to check before turning on anything that depends on it
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::new(&self.adapter, self.device.features())
+    }
+
+    pub fn stats(&self, frame_time_ms: f32) -> Stats {
+        let info = self.adapter.get_info();
+        Stats {
+            adapter: info.name,
+            backend: info.backend,
+            surface_format: self.surface_config.format,
+            resolution: self.frame_size(),
+            sample_count: self.sample_count(),
+            frame_time_ms,
+            triangles: self
+                .objects
+                .iter()
+                .map(|object| object.mesh.data.triangle_count())
+                .sum(),
+            surface_area: self.surface_area(),
+            volume: self.volume(),
+            draw_calls: (self.objects.len() + self.lines.len()) as u32,
+            display_mode: self.display_mode,
+            gpu_timings: self.gpu_timer.as_ref().and_then(GpuTimer::last),
+            skipped_frames: self.skipped_frames.get(),
+        }
+    }
+
+    // of every object's triangles, scaled by the model transform
+    pub fn surface_area(&self) -> f32 {
+        self.objects
+            .iter()
+            .map(|object| object.mesh.data.surface_area(self.model_transform))
+            .sum()
+    }
+
+    /*
+     * Enclosed by the objects' triangles through the model transform, None when any of
+     * them has boundary or non-manifold edges since an open surface doesn't enclose
+     * anything. Inside out meshes count the same as the right way round.
+     */
+    pub fn volume(&self) -> Option<f32> {
+        let closed = self
+            .objects
+            .iter()
+            .all(|object| object.edges.boundary.is_empty() && object.edges.non_manifold.is_empty());
+        if !closed || self.objects.is_empty() {
+            return None;
+        }
+        let volume: f32 = self
+            .objects
+            .iter()
+            .map(|object| object.mesh.data.signed_volume(self.model_transform))
+            .sum();
+        Some(volume.abs())
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+        self.animate_vertices();
+        let was_full_quality = self.full_quality();
+        let view_proj = self.view_projection();
+        if view_proj == self.last_view_proj {
+            self.still_time += dt;
+        } else {
+            self.still_time = 0.0;
+            self.last_view_proj = view_proj;
+            if let Some(taa) = &self.taa {
+                taa.reset();
+            }
+            self.rebuild_up_indicator();
+            self.rebuild_feature_edges();
+        }
+        // progressive rendering just brought the shadows back, which the history lacks
+        if self.full_quality() && !was_full_quality {
+            if let Some(taa) = &self.taa {
+                taa.reset();
+            }
+        }
+        if let Some(auto) = &self.auto_exposure {
+            auto.write_params(&self.queue, dt);
+        }
+        self.poll_streams();
+        self.write_uniforms();
+    }
+
+    /*
+     * While on, shadows are left out whenever the camera, pivot or model transform changed
+     * within the last PROGRESSIVE_IDLE_TIME of simulation time, so orbiting stays smooth, and
+     * come back once the view holds still. Motion is picked up by `update`.
+     */
+    pub fn set_progressive(&mut self, enabled: bool) {
+        self.progressive = enabled;
+        self.write_uniforms();
+    }
+
+    fn shadows_drawn(&self) -> bool {
+        self.shadow.enabled && self.full_quality()
+    }
+
+    // false while progressive rendering is on and the view is moving
+    pub fn full_quality(&self) -> bool {
+        !self.progressive || self.still_time >= PROGRESSIVE_IDLE_TIME
+    }
+
+    // everything between the meshes and clip space, the transform uniform
+    fn view_projection(&self) -> Matrix4<f32> {
+        self.camera.view_projection_matrix(self.aspect_ratio())
+            * Matrix4::from_translation(self.pivot_offset)
+            * self.model_transform
+    }
+
+    // returns false when the adapter doesn't support timestamp queries
+    pub fn set_gpu_timing(&mut self, enabled: bool) -> bool {
+        match &mut self.gpu_timer {
+            Some(timer) => {
+                timer.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /*
+     * wgpu doesn't expose the surface's composite alpha mode yet, so whether the
+     * window actually shows through depends on the platform compositor. Colors
+     * leave the shader with alpha 1, which is already premultiplied.
+     */
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.clear_color = if transparent {
+            Color::TRANSPARENT
+        } else {
+            DEFAULT_CLEAR_COLOR
+        };
+    }
+
+    /*
+     * Fades the whole frame, clamped to MIN_WINDOW_OPACITY so the window stays visible.
+     * Only shows through on a window created transparent, on an opaque one the frame just
+     * gets darker. winit can't change that or blur behind the window after creation.
+     */
+    pub fn set_window_opacity(&mut self, opacity: f32) {
+        self.window_opacity = opacity.max(MIN_WINDOW_OPACITY).min(1.0);
+        if self.window_opacity < 1.0 && self.opacity_pass.is_none() {
+            self.opacity_pass = Some(OpacityPass::new(&self.device, self.surface_config.format));
+        }
+        if let Some(opacity) = &mut self.opacity_pass {
+            opacity.opacity = self.window_opacity;
+        }
+        self.rebuild_post_targets();
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn FramePass>) {
+        self.passes.push(pass);
+    }
+
+    /*
+     * Appends an effect to the chain run on every frame once it's been drawn and, with HDR
+     * on, tonemapped. Each effect reads what the one before wrote, through two textures
+     * the renderer keeps in the surface format. The last writes into the surface, and the
+     * window opacity fade always comes after the added effects.
+     */
+    pub fn add_post_process(&mut self, effect: Box<dyn PostProcess>) {
+        self.post_processes.push(effect);
+        self.rebuild_post_targets();
+    }
+
+    fn post_chain(&self) -> Vec<&dyn PostProcess> {
+        let mut chain: Vec<&dyn PostProcess> = self
+            .post_processes
+            .iter()
+            .map(|effect| effect.as_ref())
+            .collect();
+        if let (true, Some(opacity)) = (self.window_opacity < 1.0, &self.opacity_pass) {
+            chain.push(opacity);
+        }
+        chain
+    }
+
+    // sized like what the frame is drawn into, which `render_into` may change for a call
+    fn rebuild_post_targets(&mut self) {
+        if self.post_chain().is_empty() {
+            self.post_targets = None;
+            return;
+        }
+        let size = self.target_size.unwrap_or_else(|| self.frame_size());
+        if self
+            .post_targets
+            .as_ref()
+            .map_or(true, |targets| targets.size != size)
+        {
+            let format = self.surface_config.format;
+            self.post_targets = Some(PostTargets::new(&self.device, format, size));
+        }
+    }
+
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        self.display_mode = mode;
+        self.rebuild_depth_only_pipelines();
+        self.rebuild_feature_edges();
+        self.write_uniforms();
+    }
+
+    /*
+     * Silhouettes depend on where the camera is, so they're worked out again whenever the
+     * view changes. Faces are tested in each mesh's own space, with the eye taken back
+     * through the pivot and model transform, which keeps which side faces the eye.
+     */
+    fn rebuild_feature_edges(&mut self) {
+        let crease_angle = match self.display_mode {
+            DisplayMode::FeatureEdges(angle) => angle,
+            _ => {
+                self.feature_edge_lines.clear();
+                return;
+            }
+        };
+        let world = Matrix4::from_translation(self.pivot_offset) * self.model_transform;
+        let inverse = world.invert().unwrap_or_else(Matrix4::identity);
+        let eye = inverse.transform_point(self.camera.eye);
+        let eye_direction = inverse.transform_vector(self.camera.eye - self.camera.target);
+        let ortho = self.camera.ortho_height.is_some();
+        let towards_viewer = |point: Point3<f32>| if ortho { eye_direction } else { eye - point };
+        let color = self.line_style.feature_edge_color;
+        self.feature_edge_lines = self
+            .objects
+            .iter()
+            .map(|object| {
+                let data = &object.mesh.data;
+                let lines = overlay::feature_edges(
+                    data,
+                    &object.edges,
+                    crease_angle,
+                    towards_viewer,
+                    color,
+                );
+                let name = format!("{} (feature edges)", object.name);
+                Lines::new(&self.device, &name, lines)
+            })
+            .collect();
+    }
+
+    fn rebuild_depth_only_pipelines(&mut self) {
+        if !matches!(self.display_mode, DisplayMode::FeatureEdges(_)) {
+            self.depth_only_pipelines.clear();
+            return;
+        }
+        let topologies: HashSet<Topology> = self
+            .objects
+            .iter()
+            .map(|object| object.mesh.data.topology)
+            .collect();
+        self.depth_only_pipelines = topologies
+            .into_iter()
+            .filter_map(|topology| {
+                Some((
+                    topology,
+                    self.create_pipeline(topology, false, ColorWrites::empty())?,
+                ))
+            })
+            .collect();
+    }
+
+    /*
+     * Shades everything with one material, CLAY_MATERIAL for the usual clay render, so the
+     * form can be judged without the authored materials getting in the way. The default
+     * display mode lights the override's base colour, the channel modes show its factors.
+     * The loaded materials stay as they were and come back with None. The override is
+     * opaque: its alpha cutoff is ignored, and so are the objects' own.
+     */
+    pub fn set_material_override(&mut self, material: Option<MaterialFactors>) {
+        self.material_override = material;
+        self.write_uniforms();
+    }
+
+    // how many submitted frames the GPU may still be working on before `draw` blocks
+    pub fn set_max_frames_in_flight(&mut self, max: u32) {
+        self.frame_limiter = FrameLimiter::new(max);
+    }
+
+    // off presents frames as soon as they're done, tearing included
+    pub fn set_vsync(&mut self, enabled: bool) {
+        self.surface_config.present_mode = if enabled {
+            PresentMode::Fifo
+        } else {
+            PresentMode::Immediate
+        };
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
+    pub fn set_normal_space(&mut self, space: NormalSpace) {
+        self.normal_space = space;
+        self.write_uniforms();
+    }
+
+    /*
+     * `Some(samples)` renders the scene into an HDR_FORMAT target with that many samples,
+     * resolves it and tonemaps into the surface. Passes added with `add_pass` draw into
+     * the same target, so their pipelines need `target_format` and `sample_count`.
+     */
+    pub fn set_hdr(&mut self, sample_count: Option<u32>) -> Result<(), String> {
+        let (width, height) = self.frame_size();
+        self.hdr = match sample_count {
+            Some(count) => Some(HdrTarget::new(
+                &self.device,
+                self.surface_config.format,
+                width,
+                height,
+                count,
+            )?),
+            None => None,
+        };
+        self.depth_texture = Texture::depth(&self.device, width, height, self.sample_count());
+        self.upload_background();
+        self.rebuild_pipeline();
+        // it reads the old target, and goes away along with HDR
+        let taa_samples = self.taa.take().map(|taa| taa.samples);
+        let exposure_speed = self.auto_exposure.take().map(|auto| auto.speed);
+        if self.hdr.is_some() {
+            self.set_taa_accumulation(taa_samples)?;
+            self.set_auto_exposure(exposure_speed.is_some(), exposure_speed.unwrap_or(0.0))?;
+        }
+        self.write_uniforms();
+        Ok(())
+    }
+
+    /*
+     * Averages up to `samples` frames rendered with sub-pixel jitter while the view holds
+     * still, converging on a supersampled image. Moving the camera starts it over, anything
+     * else that changes the picture should call `reset_taa_accumulation`. Needs `set_hdr`
+     * for the float target to accumulate from, None or 0 turns it off.
+     */
+    pub fn set_taa_accumulation(&mut self, samples: Option<u32>) -> Result<(), String> {
+        let (width, height) = self.frame_size();
+        self.taa = match (samples.filter(|&samples| samples > 0), &self.hdr) {
+            (None, _) => None,
+            (Some(_), None) => return Err("temporal accumulation needs HDR on".to_owned()),
+            (Some(samples), Some(hdr)) => {
+                Some(Accumulation::new(&self.device, hdr, width, height, samples))
+            }
+        };
+        self.write_uniforms();
+        Ok(())
+    }
+
+    /*
+     * Calls `animator` on every update with a copy of each object's vertices as they were
+     * before it was set, and the simulation time, then writes the result over the object
+     * through `update_vertices`. Starting from the same vertices every time, a function of
+     * time like a wave doesn't build up. Edits through `update_vertices` go into those
+     * starting vertices, and an object whose mesh gets replaced, as smoothing does, starts
+     * over from the new one. Normals are left alone unless `set_animated_normals` is on.
+     */
+    pub fn set_vertex_animator(&mut self, animator: Box<dyn FnMut(&mut [Vertex], f32)>) {
+        self.clear_vertex_animator();
+        self.vertex_animator = Some(animator);
+    }
+
+    // puts the vertices back the way they were before the animator moved them
+    pub fn clear_vertex_animator(&mut self) {
+        if self.vertex_animator.take().is_none() {
+            return;
+        }
+        self.restore_rest_pose();
+        self.animation_rest.clear();
+        self.rebuild_overlays();
+    }
+
+    // writes the vertices the animator started from back over every object it still applies to
+    fn restore_rest_pose(&mut self) {
+        for (object, (revision, vertices)) in self.objects.iter_mut().zip(&self.animation_rest) {
+            if *revision != object.revision || vertices.len() != object.mesh.data.vertices.len() {
+                continue;
+            }
+            if let Err(err) = object
+                .mesh
+                .update_vertices(&self.device, &self.queue, 0, vertices)
+            {
+                log::warn!("can't restore {}: {}", object.name, err);
+            }
+            object.bounds = object.mesh.data.bounds();
+        }
+    }
+
+    // smooth normals recomputed from the animated positions every update, on top of moving them
+    pub fn set_animated_normals(&mut self, enabled: bool) {
+        self.animated_normals = enabled;
+    }
+
+    fn animate_vertices(&mut self) {
+        let animator = match &mut self.vertex_animator {
+            Some(animator) => animator,
+            None => return,
+        };
+        self.animation_rest.truncate(self.objects.len());
+        for (i, object) in self.objects.iter_mut().enumerate() {
+            // objects added since, or whose mesh got replaced by smoothing and the like
+            let stale = self.animation_rest.get(i).map(|(revision, rest)| {
+                *revision != object.revision || rest.len() != object.mesh.data.vertices.len()
+            });
+            let current = || (object.revision, object.mesh.data.vertices.clone());
+            match stale {
+                Some(false) => {}
+                Some(true) => self.animation_rest[i] = current(),
+                None => self.animation_rest.push(current()),
+            }
+            let mut vertices = self.animation_rest[i].1.clone();
+            animator(&mut vertices, self.elapsed);
+            if self.animated_normals {
+                let data = &mut object.mesh.data;
+                data.vertices.copy_from_slice(&vertices);
+                data.compute_normals();
+                vertices.copy_from_slice(&data.vertices);
+            }
+            if let Err(err) = object
+                .mesh
+                .update_vertices(&self.device, &self.queue, 0, &vertices)
+            {
+                log::warn!("can't animate {}: {}", object.name, err);
+            }
+            object.bounds = object.mesh.data.bounds();
+        }
+        // the history would smear the old positions over the new ones
+        if let Some(taa) = &self.taa {
+            taa.reset();
+        }
+        for (lines, object) in self.wireframe_lines.iter_mut().zip(&self.objects) {
+            let positions = object.mesh.data.vertices.iter().map(|v| v.position);
+            lines.update_positions(&self.queue, positions);
+        }
+        if self.edge_diagnostics_visible || self.selected_triangle.is_some() {
+            self.rebuild_diagnostic_overlays();
+        } else {
+            self.rebuild_feature_edges();
+        }
+    }
+
+    /*
+     * Adapts the exposure to the log average luminance of each frame, easing towards the
+     * exposure that brings it to AutoExposure::key by 1 - exp(-speed * dt) per update, so
+     * higher speeds adapt faster. Starts from the manual HdrTarget::exposure and needs
+     * `set_hdr`, like temporal accumulation.
+     */
+    pub fn set_auto_exposure(&mut self, enabled: bool, speed: f32) -> Result<(), String> {
+        self.auto_exposure = match (enabled, &self.hdr) {
+            (false, _) => None,
+            (true, None) => return Err("auto exposure needs HDR on".to_owned()),
+            (true, Some(hdr)) => Some(AutoExposure::new(&self.device, hdr, speed.max(0.0))),
+        };
+        Ok(())
+    }
+
+    pub fn reset_taa_accumulation(&mut self) {
+        if let Some(taa) = &self.taa {
+            taa.reset();
+        }
+        self.write_uniforms();
+    }
+
+    // accumulating or showing what was accumulated, the view hasn't moved since last frame
+    fn taa_active(&self) -> Option<&Accumulation> {
+        self.taa.as_ref().filter(|_| self.still_time > 0.0)
+    }
+
+    // `view_projection` moved by the accumulation's sub-pixel offset while it's accumulating
+    fn jittered_view_projection(&self) -> Matrix4<f32> {
+        let view_proj = self.view_projection();
+        match self.taa_active().filter(|taa| !taa.converged()) {
+            Some(taa) => {
+                let (x, y) = taa.jitter();
+                let (width, height) = self.frame_size();
+                let offset = Vector3::new(x * 2.0 / width as f32, y * 2.0 / height as f32, 0.0);
+                Matrix4::from_translation(offset) * view_proj
+            }
+            None => view_proj,
+        }
+    }
+
+    // colour format the frame passes render into
+    pub fn target_format(&self) -> TextureFormat {
+        match self.hdr {
+            Some(_) => HDR_FORMAT,
+            None => self.surface_config.format,
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.hdr.as_ref().map_or(1, |hdr| hdr.sample_count)
+    }
+
+    // drawn behind the scene, filling the window according to `fit`
+    pub fn set_background_image(&mut self, image: &image::RgbaImage, fit: BackgroundFit) {
+        self.background_image = Some(image.clone());
+        self.background_fit = fit;
+        self.upload_background();
+        self.write_uniforms();
+    }
+
+    pub fn clear_background_image(&mut self) {
+        self.background_image = None;
+        self.background = None;
+    }
+
+    fn upload_background(&mut self) {
+        let target_srgb = self.hdr.is_some() || self.surface_config.format.describe().srgb;
+        self.background = self.background_image.as_ref().map(|image| {
+            Background::new(
+                &self.device,
+                &self.queue,
+                &self.background_bind_group_layout,
+                &self.sampler,
+                image,
+                target_srgb,
+                self.background_fit,
+            )
+        });
+    }
+
+    pub fn set_matcap(&mut self, image: &image::RgbaImage) {
+        self.matcap_image = image.clone();
+        self.upload_matcap();
+    }
+
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+        self.upload_matcap();
+        self.write_uniforms();
+    }
+
+    /*
+     * One value per vertex for DisplayMode::Heatmap, covering every object in the order
+     * they were added. Values are normalized over their own range before upload.
+     */
+    pub fn set_vertex_scalars(&mut self, values: &[f32], ramp: ColorRamp) -> Result<(), String> {
+        let vertex_count: usize = self
+            .objects
+            .iter()
+            .map(|object| object.mesh.data.vertices.len())
+            .sum();
+        if values.len() != vertex_count {
+            return Err(format!(
+                "got {} scalars for {} vertices",
+                values.len(),
+                vertex_count
+            ));
+        }
+        self.vertex_scalars = Some(heatmap::normalize(values));
+        self.color_ramp = ramp;
+        self.upload_scalars();
+        self.write_uniforms();
+        Ok(())
+    }
+
+    // objects added after the scalars were set have none and stay at zero
+    fn upload_scalars(&self) {
+        let scalars = match &self.vertex_scalars {
+            Some(scalars) => scalars,
+            None => return,
+        };
+        let mut offset = 0;
+        for object in &self.objects {
+            // counted as loaded, a subdivided object doesn't take any
+            let loaded = object.original.as_ref().unwrap_or(&object.mesh.data);
+            let count = loaded.vertices.len();
+            if let (Some(slice), true) = (
+                scalars.get(offset..offset + count),
+                count == object.mesh.data.vertices.len(),
+            ) {
+                object.mesh.set_scalars(&self.queue, slice);
+            }
+            offset += count;
+        }
+    }
+
+    fn upload_matcap(&mut self) {
+        self.matcap = Texture::from_rgba(
+            &self.device,
+            &self.queue,
+            "Matcap Texture",
+            &self.matcap_image,
+            self.color_space.texture_format(),
+        );
+        self.rebuild_bind_group();
+    }
+
+    /*
+     * Edits an object's geometry in place, `index` counting objects in the order they
+     * were added. Writes stay within the existing buffers until they outgrow them.
+     * Positions are in model coordinates and get moved by the origin like `add_object`'s.
+     * Edge diagnostics keep describing the mesh as it was added.
+     */
+    pub fn update_vertices(
+        &mut self,
+        index: usize,
+        offset: usize,
+        vertices: &[Vertex],
+    ) -> Result<(), String> {
+        let object = self
+            .objects
+            .get_mut(index)
+            .ok_or_else(|| format!("no object {}", index))?;
+        let vertex_count = object.mesh.data.vertices.len();
+        let origin = self.origin;
+        let vertices: Vec<Vertex> = vertices
+            .iter()
+            .map(|vertex| {
+                let [x, y, z] = vertex.position;
+                Vertex {
+                    position: [
+                        (x as f64 - origin.x) as f32,
+                        (y as f64 - origin.y) as f32,
+                        (z as f64 - origin.z) as f32,
+                    ],
+                    ..*vertex
+                }
+            })
+            .collect();
+        let vertices = &vertices[..];
+        object
+            .mesh
+            .update_vertices(&self.device, &self.queue, offset, vertices)?;
+        object.bounds = object.mesh.data.bounds();
+        // a reallocation only happens on growth, so this also covers the zeroed scalar buffer
+        if object.mesh.data.vertices.len() != vertex_count && self.vertex_scalars.is_some() {
+            log::warn!("vertex count changed, the vertex scalars no longer line up");
+            self.vertex_scalars = None;
+        }
+        if object.mesh.data.vertices.len() != vertex_count {
+            object.ao = None;
+        }
+        // while animating the edit becomes part of what the animator starts from
+        if let Some((revision, rest)) = self.animation_rest.get_mut(index) {
+            if *revision == object.revision && rest.len() == vertex_count {
+                let overlap = (vertex_count - offset).min(vertices.len());
+                rest[offset..offset + overlap].copy_from_slice(&vertices[..overlap]);
+                rest.extend_from_slice(&vertices[overlap..]);
+            }
+        }
+        // the selection still means the same triangle, but the isolated copy has moved
+        self.rebuild_isolated();
+        self.rebuild_overlays();
+        Ok(())
+    }
+
+    pub fn update_indices(
+        &mut self,
+        index: usize,
+        offset: usize,
+        indices: &[u32],
+    ) -> Result<(), String> {
+        let object = self
+            .objects
+            .get_mut(index)
+            .ok_or_else(|| format!("no object {}", index))?;
+        object
+            .mesh
+            .update_indices(&self.device, &self.queue, offset, indices)?;
+        if self
+            .selected_triangle
+            .map_or(false, |(object, _)| object == index)
+        {
+            self.clear_selection();
+        }
+        self.rebuild_overlays();
+        Ok(())
+    }
+
+    // v1 only drives a single morphing mesh: the first one in the scene that has targets
+    pub fn set_morph_weights(&mut self, weights: &[f32]) {
+        match self
+            .objects
+            .iter_mut()
+            .find(|object| object.mesh.morph.target_count() > 0)
+        {
+            Some(object) => object.mesh.morph.set_weights(&self.queue, weights),
+            None => log::warn!("no mesh in the scene has morph targets"),
+        }
+    }
+
+    pub fn set_shadows(&mut self, enabled: bool, resolution: u32) {
+        self.shadow.enabled = enabled;
+        if enabled && self.shadow.resize(&self.device, resolution) {
+            self.rebuild_bind_group();
+        }
+        self.write_uniforms();
+    }
+
+    /*
+     * A blurred shadow on the ground under the model at `opacity`, which stays on with the
+     * shadow map off and is a lot cheaper. Only triangles cast it, without their morphs.
+     */
+    pub fn set_ground_shadow(&mut self, enabled: bool, opacity: f32) {
+        if enabled && self.ground_shadow.is_none() {
+            self.ground_shadow = Some(GroundShadow::new(
+                &self.device,
+                self.target_format(),
+                self.sample_count(),
+            ));
+        }
+        if let Some(ground) = &mut self.ground_shadow {
+            ground.enabled = enabled;
+            ground.opacity = opacity.max(0.0).min(1.0);
+        }
+        self.write_uniforms();
+    }
+
+    pub fn set_cull_mode(&mut self, mode: Option<Face>) {
+        self.cull_mode = mode;
+        self.rebuild_pipeline();
+    }
+
+    // back faces only get drawn with culling off, see `set_cull_mode`
+    pub fn set_backface_color(&mut self, color: Option<Color>) {
+        self.backface_color = color;
+        self.write_uniforms();
+    }
+
+    // polygon offset applied to everything drawn by the main pipeline, for z-fighting
+    pub fn set_depth_bias(&mut self, constant: i32, slope: f32) {
+        self.depth_bias = DepthBiasState {
+            constant,
+            slope_scale: slope,
+            clamp: 0.0,
+        };
+        self.rebuild_pipeline();
+    }
+
+    /*
+     * Settles z-fighting between coplanar faces of different objects, like a label on a
+     * surface: the higher priority one is pulled towards the camera by priority times
+     * `layer_epsilon` of depth. Unlike the depth bias it's per object and leaves the
+     * pipeline alone. `submesh` indexes `objects`.
+     */
+    pub fn set_layer_priority(&mut self, submesh: usize, priority: i32) {
+        match self.objects.get_mut(submesh) {
+            Some(object) => {
+                object.layer_priority = priority;
+                self.upload_layer_offsets();
+            }
+            None => log::warn!("no object {} to set the layer priority of", submesh),
+        }
+    }
+
+    pub fn set_layer_epsilon(&mut self, epsilon: f32) {
+        self.layer_epsilon = epsilon.max(0.0);
+        self.upload_layer_offsets();
+    }
+
+    /*
+     * Ray casts `samples` directions per vertex against the whole scene on the CPU and
+     * shades with the result from then on. Costs once what SSAO would every frame, but
+     * only suits static geometry: moving vertices later doesn't update it.
+     */
+    pub fn bake_vertex_ao(&mut self, samples: u32) -> Duration {
+        let start = Instant::now();
+        let baked = ao::bake(&self.objects, samples);
+        for (object, ao) in self.objects.iter_mut().zip(baked) {
+            object.mesh.set_ao(&self.queue, &ao);
+            object.ao = Some(ao);
+        }
+        let elapsed = start.elapsed();
+        log::info!(
+            "baked vertex AO with {} samples in {:.1}ms",
+            samples,
+            elapsed.as_secs_f64() * 1000.0
+        );
+        self.show_vertex_ao(true);
+        elapsed
+    }
+
+    pub fn show_vertex_ao(&mut self, visible: bool) {
+        self.vertex_ao_visible = visible;
+        self.write_uniforms();
+    }
+
+    // replaced meshes start out unoccluded, like `upload_scalars`
+    fn upload_ao(&self) {
+        for object in &self.objects {
+            if let Some(ao) = &object.ao {
+                if ao.len() == object.mesh.data.vertices.len() {
+                    object.mesh.set_ao(&self.queue, ao);
+                }
+            }
+        }
+    }
+
+    // needed again whenever an object's mesh, and with it its morph uniforms, is replaced
+    fn upload_layer_offsets(&mut self) {
+        for object in self.objects.iter_mut() {
+            let offset = object.layer_priority as f32 * self.layer_epsilon;
+            object.mesh.morph.set_depth_offset(&self.queue, offset);
+        }
+    }
+
+    pub fn set_clip_plane(&mut self, index: usize, plane: Option<Plane>) {
+        assert!(index < MAX_CLIP_PLANES, "clip plane index out of range");
+        self.clip_planes[index] = plane;
+        self.write_uniforms();
+    }
+
+    // discards everything outside the slab, on top of the clip planes
+    pub fn set_slice(&mut self, slice: Option<Slice>) {
+        self.slice = slice;
+        self.write_uniforms();
+    }
+
+    // about how many bytes of buffers the scene and the one stashed by `clear_scene` hold
+    pub fn gpu_memory(&self) -> u64 {
+        self.kept_memory(KeptScenes::Both)
+    }
+
+    fn kept_memory(&self, kept: KeptScenes) -> u64 {
+        let scene_memory = |objects: &[SceneObject], lines: &[Lines]| -> u64 {
+            let objects: u64 = objects
+                .iter()
+                .map(|object| object.mesh.data.gpu_memory())
+                .sum();
+            let lines: u64 = lines.iter().map(|lines| lines.data.gpu_memory()).sum();
+            objects + lines
+        };
+        let current = scene_memory(&self.objects, &self.lines);
+        let previous = self.previous_scene.as_ref().map_or(0, |previous| {
+            scene_memory(&previous.objects, &previous.lines)
+        });
+        match kept {
+            KeptScenes::Both => current + previous,
+            KeptScenes::Current => current,
+            KeptScenes::None => 0,
+        }
+    }
+
+    /*
+     * wgpu doesn't say how much memory the GPU has, so there's only a budget to go over
+     * once one is set, e.g. from what the driver reports. None leaves just the adapter's
+     * limits to check against.
+     */
+    pub fn set_memory_budget(&mut self, budget: Option<u64>) {
+        self.memory_budget = budget;
+    }
+
+    /*
+     * Before uploading `model`, whether it fits next to the scenes `kept` says stay around
+     * for the upload: an error with the estimate when it would go over the memory budget,
+     * or when a morph delta buffer is bigger than the adapter can bind, instead of failing
+     * halfway through the upload. Logs the estimate either way.
+     */
+    pub fn check_memory(&self, model: &LoadedModel, kept: KeptScenes) -> Result<(), LoadError> {
+        let max_binding = self.device.limits().max_storage_buffer_binding_size as u64;
+        let largest_deltas = [&model.mesh, &model.strips, &model.points]
+            .iter()
+            .map(|data| data.morph_delta_size())
+            .max()
+            .unwrap_or(0);
+        let (estimate, in_use) = (model.gpu_memory(), self.kept_memory(kept));
+        match self.memory_budget {
+            Some(budget) => log::info!(
+                "model needs about {:.1} MiB of GPU memory, {:.1} MiB in use of a {:.1} MiB budget",
+                loader::mebibytes(estimate),
+                loader::mebibytes(in_use),
+                loader::mebibytes(budget)
+            ),
+            None => log::info!(
+                "model needs about {:.1} MiB of GPU memory, {:.1} MiB in use",
+                loader::mebibytes(estimate),
+                loader::mebibytes(in_use)
+            ),
+        }
+        if largest_deltas > max_binding {
+            return Err(LoadError::InsufficientMemory {
+                required: largest_deltas,
+                available: max_binding,
+            });
+        }
+        match self.memory_budget {
+            Some(budget) if in_use + estimate > budget => Err(LoadError::InsufficientMemory {
+                required: estimate,
+                available: budget.saturating_sub(in_use),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /*
+     * Models far from the origin are shifted back to it before upload, so the matrices and
+     * positions the GPU sees stay small and single precision doesn't jitter. Everything in
+     * the renderer, including the camera and clip planes, works relative to `origin`.
+     */
+    pub fn add_object(&mut self, name: &str, mut data: MeshData) {
+        if let Err(err) = data.validate() {
+            log::error!("skipping {}: {}", name, err);
+            return;
+        }
+        self.rebase_for(data.bounds());
+        data.translate(-self.origin);
+        let mesh = Mesh::new(
+            &self.device,
+            &self.queue,
+            &self.morph_bind_group_layout,
+            data,
+        );
+        let object = SceneObject::new(name, mesh);
+        log::info!(
+            "{}: {} boundary edges, {} non-manifold edges",
+            name,
+            object.edges.boundary.len(),
+            object.edges.non_manifold.len()
+        );
+        let topology = object.mesh.data.topology;
+        if !self.pipelines.contains_key(&topology) {
+            if let Some(pipeline) = self.create_pipeline(topology, false, ColorWrites::ALL) {
+                self.pipelines.insert(topology, pipeline);
+            }
+        }
+        if object.alpha_cutoff().is_some()
+            && self.sample_count() > 1
+            && !self.coverage_pipelines.contains_key(&topology)
+        {
+            if let Some(pipeline) = self.create_pipeline(topology, true, ColorWrites::ALL) {
+                self.coverage_pipelines.insert(topology, pipeline);
+            }
+        }
+        self.objects.push(object);
+        self.rebuild_depth_only_pipelines();
+        self.rebuild_overlays();
+    }
+
+    // drawn with the line pipeline after the meshes, unlit and without shadows
+    pub fn add_lines(&mut self, name: &str, mut data: LineData) {
+        let vertex_count = data.vertices.len();
+        if data
+            .indices
+            .iter()
+            .any(|&index| index as usize >= vertex_count)
+        {
+            log::error!("skipping {}: line index out of range", name);
+            return;
+        }
+        self.rebase_for(data.bounds());
+        data.translate(-self.origin);
+        self.lines.push(Lines::new(&self.device, name, data));
+        self.rebuild_overlays();
+    }
+
+    // drawn by InfiniteGrid on the ground under the scene, out to where it fades away
+    pub fn show_grid(&mut self, visible: bool) {
+        if visible && self.grid.is_none() {
+            self.grid = Some(InfiniteGrid::new(
+                &self.device,
+                self.target_format(),
+                self.sample_count(),
+            ));
+        }
+        self.grid_visible = visible;
+        self.write_uniforms();
+    }
+
+    pub fn show_wireframe(&mut self, visible: bool) {
+        self.wireframe_visible = visible;
+        self.rebuild_overlays();
+    }
+
+    pub fn show_edge_diagnostics(&mut self, visible: bool) {
+        self.edge_diagnostics_visible = visible;
+        self.rebuild_overlays();
+    }
+
+    // bones and joints show through the line pipeline, so surfaces in front hide them
+    pub fn show_skeleton(&mut self, visible: bool) {
+        self.skeleton_visible = visible;
+        self.rebuild_overlays();
+    }
+
+    pub fn set_wireframe_color(&mut self, color: [f32; 4]) {
+        self.line_style.wireframe_color = color;
+        self.rebuild_overlays();
+    }
+
+    pub fn set_grid_color(&mut self, color: [f32; 4]) {
+        self.line_style.grid_color = color;
+        self.write_uniforms();
+    }
+
+    pub fn set_grid_spacing(&mut self, spacing: f32) {
+        self.line_style.grid_spacing = spacing;
+        self.write_uniforms();
+    }
+
+    pub fn set_grid_fade_distance(&mut self, distance: Option<f32>) {
+        self.line_style.grid_fade_distance = distance;
+        self.write_uniforms();
+    }
+
+    pub fn set_grid_axis_highlight(&mut self, highlight: bool) {
+        self.line_style.grid_axis_highlight = highlight;
+        self.write_uniforms();
+    }
+
+    pub fn set_line_width(&mut self, width: f32) {
+        if width != 1.0 {
+            log::warn!(
+                "line width {} requested, lines are drawn 1 pixel wide",
+                width
+            );
+        }
+        self.line_style.line_width = width;
+    }
+
+    // colours live in the vertices, so a style change means regenerating the geometry
+    fn rebuild_overlays(&mut self) {
+        self.wireframe_lines.clear();
+        if self.wireframe_visible {
+            for object in &self.objects {
+                let data = overlay::wireframe(&object.mesh.data, self.line_style.wireframe_color);
+                let name = format!("{} (wireframe)", object.name);
+                self.wireframe_lines
+                    .push(Lines::new(&self.device, &name, data));
+            }
+        }
+        self.rebuild_diagnostic_overlays();
+    }
+
+    // everything `rebuild_overlays` covers except the wireframe
+    fn rebuild_diagnostic_overlays(&mut self) {
+        let mut overlays = Vec::new();
+        if self.edge_diagnostics_visible {
+            let style = &self.line_style;
+            for object in &self.objects {
+                let data = &object.mesh.data;
+                let mut lines =
+                    overlay::edges(data, &object.edges.boundary, style.boundary_edge_color);
+                lines.append(overlay::edges(
+                    data,
+                    &object.edges.non_manifold,
+                    style.non_manifold_edge_color,
+                ));
+                if lines.indices.is_empty() {
+                    continue;
+                }
+                let name = format!("{} (edge diagnostics)", object.name);
+                overlays.push(Lines::new(&self.device, &name, lines));
+            }
+        }
+        if let (true, Some(bounds)) = (self.skeleton_visible, self.scene_bounds()) {
+            let marker_size = bounds.radius() * overlay::JOINT_MARKER_SCALE;
+            for skeleton in &self.skeletons {
+                let data = overlay::skeleton(skeleton, marker_size, &self.line_style);
+                let name = match &skeleton.name {
+                    Some(name) => format!("{} (skeleton)", name),
+                    None => String::from("Skeleton"),
+                };
+                overlays.push(Lines::new(&self.device, &name, data));
+            }
+        }
+        // a selection left over from geometry that has since changed is just not drawn
+        let selection = self.selected_triangle.and_then(|(object, triangle)| {
+            let data = &self.objects.get(object)?.mesh.data;
+            Some((data, *data.triangles().get(triangle)?))
+        });
+        if let Some((data, corners)) = selection {
+            let lines = overlay::triangle(data, corners, self.line_style.selection_color);
+            overlays.push(Lines::new(&self.device, "Selection", lines));
+        }
+        self.overlays = overlays;
+        self.rebuild_feature_edges();
+        self.rebuild_gizmo();
+    }
+
+    /*
+     * Shows handles for moving, rotating or scaling the scene through the model transform,
+     * None hides them. The snapping steps carry over between modes, translations snap to
+     * the grid spacing to begin with.
+     */
+    pub fn set_gizmo(&mut self, mode: Option<GizmoMode>) {
+        let grid_spacing = self.line_style.grid_spacing;
+        self.gizmo = mode.map(|mode| {
+            let mut gizmo = self.gizmo.unwrap_or_else(|| {
+                let mut gizmo = Gizmo::new(mode);
+                gizmo.translate_snap = grid_spacing;
+                gizmo
+            });
+            gizmo.mode = mode;
+            gizmo.end_drag();
+            gizmo
+        });
+        self.rebuild_gizmo();
+    }
+
+    /*
+     * Orbits and `look_from_home` around this axis from now on, the loaded geometry stays
+     * where it is. Turns the camera to the home view for the axis and frames the scene.
+     */
+    pub fn set_up_axis(&mut self, axis: Axis) {
+        self.up_axis = axis;
+        self.camera.look_from_home(axis);
+        self.frame_scene();
+        self.rebuild_up_indicator();
+    }
+
+    pub fn set_up_indicator_visible(&mut self, visible: bool) {
+        self.up_indicator_visible = visible;
+        self.rebuild_up_indicator();
+    }
+
+    /*
+     * Placed on the ray through its corner of the window halfway through the clip range,
+     * and sized from how far apart neighbouring pixels' rays are there.
+     */
+    fn rebuild_up_indicator(&mut self) {
+        if !self.up_indicator_visible {
+            self.up_indicator = None;
+            return;
+        }
+        let height = self.frame_size().1 as f32;
+        let corner = (UP_INDICATOR_INSET, height - UP_INDICATOR_INSET);
+        let (near, far) = self.camera.clip_range();
+        let depth = (near + far) / 2.0;
+        let along = |(origin, direction): (Point3<f32>, Vector3<f32>)| origin + direction * depth;
+        let center = along(self.frame_ray(corner));
+        let next = along(self.frame_ray((corner.0 + 1.0, corner.1)));
+        let size = (next - center).magnitude() * UP_INDICATOR_SIZE;
+        let mut data = overlay::up_axis_indicator(center, size, self.up_axis);
+        // drawn through the model transform like the gizmo, so undo it
+        let inverse = self
+            .model_transform
+            .invert()
+            .unwrap_or_else(Matrix4::identity);
+        for vertex in data.vertices.iter_mut() {
+            let position = inverse.transform_point(Point3::from(vertex.position));
+            vertex.position = position.into();
+        }
+        self.up_indicator = Some(Lines::new(&self.device, "Up Axis Indicator", data));
+    }
+
+    // where the gizmo sits and how far its handles reach, in the space `screen_ray` uses
+    fn gizmo_frame(&self) -> Option<(Point3<f32>, f32)> {
+        let bounds = self.scene_bounds()?;
+        let center = self.model_transform.transform_point(bounds.center());
+        Some((center, (bounds.radius() * GIZMO_SCALE).max(f32::EPSILON)))
+    }
+
+    fn rebuild_gizmo(&mut self) {
+        let gizmo_lines = match (&self.gizmo, self.gizmo_frame()) {
+            (Some(gizmo), Some((center, size))) => {
+                let mut data = gizmo.lines(center, size, self.line_style.selection_color);
+                // drawn through the model transform like every other line, so undo it
+                let inverse = self
+                    .model_transform
+                    .invert()
+                    .unwrap_or_else(Matrix4::identity);
+                for vertex in data.vertices.iter_mut() {
+                    let position = inverse.transform_point(Point3::from(vertex.position));
+                    vertex.position = position.into();
+                }
+                Some(Lines::new(&self.device, "Gizmo", data))
+            }
+            _ => None,
+        };
+        self.gizmo_lines = gizmo_lines;
+    }
+
+    // grabs the gizmo handle under a pixel, returns false when there's none there
+    pub fn begin_gizmo_drag(&mut self, screen: (f32, f32)) -> bool {
+        let ray = self.screen_ray(screen);
+        let transform = self.model_transform;
+        let grabbed = match (self.gizmo_frame(), self.gizmo.as_mut()) {
+            (Some((center, size)), Some(gizmo)) => gizmo.begin_drag(center, size, ray, transform),
+            _ => false,
+        };
+        if grabbed {
+            self.rebuild_gizmo();
+        }
+        grabbed
+    }
+
+    // follows the cursor with the grabbed handle, in the gizmo's snapping steps if `snap`
+    pub fn drag_gizmo(&mut self, screen: (f32, f32), snap: bool) {
+        let ray = self.screen_ray(screen);
+        if let Some(transform) = self.gizmo.and_then(|gizmo| gizmo.drag_to(ray, snap)) {
+            self.set_model_transform(transform);
+        }
+    }
+
+    pub fn end_gizmo_drag(&mut self) {
+        if let Some(gizmo) = self.gizmo.as_mut() {
+            gizmo.end_drag();
+        }
+        self.rebuild_gizmo();
+    }
+
+    pub fn gizmo_dragging(&self) -> bool {
+        self.gizmo
+            .map_or(false, |gizmo| gizmo.dragged_axis().is_some())
+    }
+
+    // the first geometry added decides the origin for everything after it
+    fn rebase_for(&mut self, bounds: Option<Aabb>) {
+        if !self.objects.is_empty() || !self.lines.is_empty() {
+            return;
+        }
+        if let Some(center) = bounds.map(|bounds| bounds.center()) {
+            if center.to_vec().magnitude() > REBASE_DISTANCE {
+                self.origin = center.to_vec().cast().unwrap();
+                log::info!("rebasing the scene to {:?}", self.origin);
+            }
+        }
+    }
+
+    pub fn scene_bounds(&self) -> Option<Aabb> {
+        let line_bounds = self.lines.iter().filter_map(|lines| lines.data.bounds());
+        scene::combined_bounds(&self.objects)
+            .into_iter()
+            .chain(line_bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /*
+     * The model shows up progressively as `update` picks up chunks, each converted by
+     * `load_conversion` and checked against the memory budget like a whole file would be.
+     * The scene is framed on the first chunk and again once the file is done.
+     */
+    pub fn load_streaming(&mut self, path: &Path) -> Result<(), LoadError> {
+        let receiver = loader::load_streaming(path)?;
+        self.streams.push((path.to_path_buf(), 0, true, receiver));
+        Ok(())
+    }
+
+    // `load_streaming` leaving the camera where it is, for a view that's already set up
+    pub fn load_streaming_in_place(&mut self, path: &Path) -> Result<(), LoadError> {
+        let receiver = loader::load_streaming(path)?;
+        self.streams.push((path.to_path_buf(), 0, false, receiver));
+        Ok(())
+    }
+
+    pub fn set_load_conversion(&mut self, conversion: Conversion) {
+        self.load_conversion = conversion;
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        !self.streams.is_empty()
+    }
+
+    // the errors streamed files have run into since the last call, see `load_streaming`
+    pub fn take_load_errors(&mut self) -> Vec<(PathBuf, LoadError)> {
+        mem::take(&mut self.load_errors)
+    }
+
+    fn poll_streams(&mut self) {
+        let mut reframe = false;
+        let mut i = 0;
+        while i < self.streams.len() {
+            let (path, mut count, follow, receiver) = {
+                let (path, count, follow, receiver) = &self.streams[i];
+                (path.clone(), *count, *follow, receiver)
+            };
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let mut arrived = Vec::new();
+            let open = loop {
+                match receiver.try_recv() {
+                    Ok(Ok(chunk)) => arrived.push(chunk),
+                    Ok(Err(err)) => {
+                        log::error!("failed to stream {}: {}", name, err);
+                        self.load_errors.push((path.clone(), err));
+                    }
+                    Err(TryRecvError::Empty) => break true,
+                    Err(TryRecvError::Disconnected) => break false,
+                }
+            };
+            // reframing on every chunk would make the camera jump around while loading
+            reframe |= follow && ((count == 0 && !arrived.is_empty()) || !open);
+            let mut over_budget = false;
+            for mut chunk in arrived {
+                chunk.prepare(&self.load_conversion, &path);
+                if let Err(err) = self.check_memory(&chunk, KeptScenes::Both) {
+                    log::error!("stopped streaming {}: {}", name, err);
+                    self.load_errors.push((path.clone(), err));
+                    over_budget = true;
+                    break;
+                }
+                self.add_streamed(&format!("{}#{}", name, count), chunk);
+                count += 1;
+            }
+            if open && !over_budget {
+                self.streams[i].1 = count;
+                i += 1;
+            } else {
+                // dropping the receiver tells the parser nobody is listening anymore
+                log::info!("finished streaming {} ({} chunks)", name, count);
+                self.streams.remove(i);
+            }
+        }
+        if reframe {
+            self.frame_scene();
+        }
+    }
+
+    // one piece of a streamed file, like the viewer adds a whole one
+    fn add_streamed(&mut self, name: &str, model: LoadedModel) {
+        if !model.mesh.indices.is_empty() {
+            self.add_object(name, model.mesh);
+        }
+        if !model.strips.indices.is_empty() {
+            self.add_object(&format!("{} (strips)", name), model.strips);
+        }
+        if !model.points.indices.is_empty() {
+            self.add_object(&format!("{} (points)", name), model.points);
+        }
+        if !model.lines.indices.is_empty() {
+            self.add_lines(&format!("{} (lines)", name), model.lines);
+        }
+        self.add_cameras(model.cameras);
+        self.add_skeletons(model.skeletons);
+    }
+
+    /*
+     * Replaces the built-in shader. The source has to provide vs_main and fs_main
+     * with the same vertex inputs, and can read groups 0 and 1 like shader.wgsl does
+     * plus whatever was passed to `set_custom_uniforms` at CUSTOM_UNIFORM_GROUP. A source
+     * that doesn't compile or doesn't fit the pipelines is an error, and the shader that
+     * was in use stays.
+     */
+    pub fn set_shader(&mut self, source: &str) -> Result<(), String> {
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let shader = self.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Custom Shader"),
+            source: ShaderSource::Wgsl(Cow::Owned(source.to_owned())),
+        });
+        let previous = self.shader.replace(shader);
+        self.rebuild_pipeline();
+        match pollster::block_on(self.device.pop_error_scope()) {
+            None => Ok(()),
+            Some(err) => {
+                self.shader = previous;
+                self.rebuild_pipeline();
+                Err(format!("the shader was rejected: {}", err))
+            }
+        }
+    }
+
+    // cheap to call every frame as long as the size stays the same
+    pub fn set_custom_uniforms(&mut self, data: &[u8]) {
+        if !self.custom_uniforms.write(&self.queue, data) {
+            self.custom_uniforms =
+                CustomUniforms::new(&self.device, &self.custom_bind_group_layout, data);
+        }
+    }
 
-/*
- * A wgpu renderer that supports drawing custom models with custom shaders / materials.
- * Axis: camera looks in -Z, +Y is up and +X is right.
- *
- *        ^ +Y
- *        |
- *        |
- *        |
- *        +----------> X+
- *       /
- *     /
- *  |/_ Z+
- *
- */
+    // the scene as currently shown: smoothing and the model transform are included, morph
+    // targets are left out
+    pub fn export_obj(&self, path: &Path) -> io::Result<()> {
+        let world = Matrix4::from_translation(self.pivot_offset) * self.model_transform;
+        loader::save_obj(path, &self.objects, &self.lines, world, self.origin)
+    }
 
-impl Renderer {
-    pub fn new(window: &Window) -> Self {
-        let instance = Instance::new(Backends::PRIMARY);
-        let surface = unsafe { instance.create_surface(window) };
-        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
-            power_preference: PowerPreference::HighPerformance,
-            force_fallback_adapter: false,
-            compatible_surface: Some(&surface),
-        }))
-        .unwrap();
-        let window_size = window.inner_size();
-        let surface_config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
-            width: window_size.width,
-            height: window_size.height,
-            present_mode: PresentMode::Fifo,
+    // always filters the geometry as loaded, so repeated calls don't compound
+    pub fn smooth_mesh(&mut self, iterations: u32, lambda: f32) {
+        // filtering an animated frame would make it the new loaded geometry
+        self.restore_rest_pose();
+        for object in self.objects.iter_mut() {
+            let original = object
+                .original
+                .take()
+                .unwrap_or_else(|| object.mesh.data.clone());
+            let smoothed = smooth::laplacian(&original, iterations, lambda);
+            let mesh = Mesh::new(
+                &self.device,
+                &self.queue,
+                &self.morph_bind_group_layout,
+                smoothed,
+            );
+            object.replace_mesh(mesh);
+            object.original = Some(original);
+        }
+        self.clear_selection();
+        self.upload_scalars();
+        self.upload_ao();
+        self.upload_layer_offsets();
+        self.rebuild_overlays();
+    }
+
+    /*
+     * Loop subdivision of the geometry as loaded, replacing any smoothing like smoothing
+     * replaces it, undone with `restore_mesh`. Returns the triangle count before and
+     * after, which grows fourfold per iteration. Vertex scalars and baked occlusion belong
+     * to the loaded vertices, so they're left off until the mesh is restored.
+     */
+    pub fn subdivide(&mut self, iterations: u32) -> (usize, usize) {
+        self.restore_rest_pose();
+        let (mut before, mut after) = (0, 0);
+        for object in self.objects.iter_mut() {
+            let original = object
+                .original
+                .take()
+                .unwrap_or_else(|| object.mesh.data.clone());
+            let subdivided = subdivide::loop_subdivide(&original, iterations);
+            before += original.triangle_count();
+            after += subdivided.triangle_count();
+            let mesh = Mesh::new(
+                &self.device,
+                &self.queue,
+                &self.morph_bind_group_layout,
+                subdivided,
+            );
+            object.replace_mesh(mesh);
+            object.original = Some(original);
+        }
+        self.clear_selection();
+        log::info!(
+            "subdivided {} times: {} triangles, up from {}",
+            iterations,
+            after,
+            before
+        );
+        self.rebuild_pipeline();
+        self.upload_scalars();
+        self.upload_ao();
+        self.upload_layer_offsets();
+        self.rebuild_overlays();
+        (before, after)
+    }
+
+    pub fn restore_mesh(&mut self) {
+        for object in self.objects.iter_mut() {
+            if let Some(original) = object.original.take() {
+                let mesh = Mesh::new(
+                    &self.device,
+                    &self.queue,
+                    &self.morph_bind_group_layout,
+                    original,
+                );
+                object.replace_mesh(mesh);
+            }
+        }
+        self.clear_selection();
+        self.rebuild_pipeline();
+        self.upload_scalars();
+        self.upload_ao();
+        self.upload_layer_offsets();
+        self.rebuild_overlays();
+    }
+
+    // smoothed or subdivided, whichever was applied last, see `restore_mesh`
+    pub fn is_smoothed(&self) -> bool {
+        self.objects.iter().any(|object| object.original.is_some())
+    }
+
+    // authored cameras are in model coordinates, like the meshes
+    pub fn add_cameras<I: IntoIterator<Item = CameraDef>>(&mut self, cameras: I) {
+        let offset = -self.origin.cast::<f32>().unwrap();
+        self.gltf_cameras.extend(cameras.into_iter().map(|mut def| {
+            def.eye += offset;
+            def
+        }));
+    }
+
+    // in model coordinates like add_cameras, they're moved along with the scene origin
+    pub fn add_skeletons<I: IntoIterator<Item = Skeleton>>(&mut self, skeletons: I) {
+        let offset = -self.origin.cast::<f32>().unwrap();
+        self.skeletons
+            .extend(skeletons.into_iter().map(|mut skeleton| {
+                skeleton.translate(offset);
+                skeleton
+            }));
+        self.rebuild_overlays();
+    }
+
+    pub fn gltf_cameras(&self) -> &[CameraDef] {
+        &self.gltf_cameras
+    }
+
+    pub fn gltf_scenes(&self) -> &[SceneInfo] {
+        &self.gltf_scenes
+    }
+
+    // what the loaded file has to offer, `active` being the one whose geometry was added
+    pub fn set_gltf_scenes(&mut self, scenes: Vec<SceneInfo>, active: Option<usize>) {
+        self.gltf_scenes = scenes;
+        self.active_gltf_scene = active;
+    }
+
+    /*
+     * Takes down every object, line set, authored camera and skeleton, for swapping in
+     * different geometry, and keeps them as the previous scene. The next thing added picks
+     * the origin again. Settings like the display mode, clip planes and the camera are left
+     * alone, so the new geometry shows up in the same view.
+     */
+    pub fn clear_scene(&mut self) {
+        let stashed = self.take_scene();
+        self.previous_scene = Some(stashed);
+        self.origin = Vector3::new(0.0, 0.0, 0.0);
+        self.rebuild_overlays();
+    }
+
+    /*
+     * Flips between the scene and the one `clear_scene` last took down, without touching
+     * the camera, for blinking between two versions of a model. Returns false when there's
+     * nothing to flip to.
+     */
+    pub fn swap_previous_scene(&mut self) -> bool {
+        let previous = match self.previous_scene.take() {
+            Some(previous) => previous,
+            None => return false,
+        };
+        let current = self.take_scene();
+        self.objects = previous.objects;
+        self.lines = previous.lines;
+        self.gltf_cameras = previous.gltf_cameras;
+        self.skeletons = previous.skeletons;
+        self.origin = previous.origin;
+        self.previous_scene = Some(current);
+        self.rebuild_overlays();
+        true
+    }
+
+    // empties the scene, selections and per-vertex data only make sense for what was there
+    fn take_scene(&mut self) -> StashedScene {
+        self.active_gltf_camera = None;
+        self.vertex_scalars = None;
+        self.selected_triangle = None;
+        self.isolated = None;
+        // the next scene's objects get animated from where they are
+        self.animation_rest.clear();
+        StashedScene {
+            objects: mem::take(&mut self.objects),
+            lines: mem::take(&mut self.lines),
+            gltf_cameras: mem::take(&mut self.gltf_cameras),
+            skeletons: mem::take(&mut self.skeletons),
+            origin: self.origin,
+        }
+    }
+
+    // steps through the authored cameras, returning to the framing camera after the last
+    pub fn cycle_gltf_camera(&mut self) {
+        if self.gltf_cameras.is_empty() {
+            return;
+        }
+        self.active_gltf_camera = match self.active_gltf_camera {
+            None => Some(0),
+            Some(index) if index + 1 < self.gltf_cameras.len() => Some(index + 1),
+            Some(_) => None,
+        };
+        match self.active_gltf_camera {
+            Some(index) => {
+                let def = self.gltf_cameras[index].clone();
+                log::info!(
+                    "camera {}: {}",
+                    index,
+                    def.name.as_deref().unwrap_or("unnamed")
+                );
+                self.camera.apply_def(&def);
+                // authored cameras are placed relative to the unshifted scene
+                self.pivot_offset = Vector3::new(0.0, 0.0, 0.0);
+                self.write_uniforms();
+            }
+            None => {
+                // back to the default direction, keeping the user's toggles
+                self.camera = Camera {
+                    depth_correction: self.camera.depth_correction,
+                    adaptive_near: self.camera.adaptive_near,
+                    snap_angle: self.camera.snap_angle,
+                    orbit_sensitivity: self.camera.orbit_sensitivity,
+                    pan_sensitivity: self.camera.pan_sensitivity,
+                    zoom_sensitivity: self.camera.zoom_sensitivity,
+                    invert_x: self.camera.invert_x,
+                    invert_y: self.camera.invert_y,
+                    ..Camera::default()
+                };
+                self.camera.look_from_home(self.up_axis);
+                self.frame_scene();
+            }
+        }
+    }
+
+    // points the camera at the combined bounds of everything in the scene
+    pub fn frame_scene(&mut self) {
+        if let Some(bounds) = self.scene_bounds() {
+            self.pivot_offset = Vector3::new(0.0, 0.0, 0.0);
+            self.camera.frame(&bounds);
+            self.write_uniforms();
+        }
+    }
+
+    /*
+     * Closest point on the scene's meshes under a pixel, measured from the top left of the
+     * window. Returned in render space without the pivot offset, ready for `set_pivot`.
+     * Picks cast a ray on the CPU rather than reading back a render target, through exactly
+     * the position given and without the TAA jitter, so they come out the same at any
+     * sample count: whatever that single ray hits first, much like reading sample 0.
+     */
+    pub fn pick(&self, screen: (f32, f32)) -> Option<Point3<f32>> {
+        self.ray_hit(screen).map(|(point, _, _)| point)
+    }
+
+    // which object and which of its triangles are under a pixel, see `pick`
+    pub fn pick_triangle(&self, screen: (f32, f32)) -> Option<(usize, usize)> {
+        self.ray_hit(screen)
+            .map(|(_, object, triangle)| (object, triangle))
+    }
+
+    // ray through a pixel in render space without the pivot offset, where picks land
+    fn screen_ray(&self, screen: (f32, f32)) -> (Point3<f32>, Vector3<f32>) {
+        let window = (self.surface_config.width, self.surface_config.height);
+        match &self.output {
+            Some(output) => self.frame_ray(output.window_to_output(window, screen)),
+            None => self.frame_ray(screen),
+        }
+    }
+
+    // like `screen_ray`, with `pixel` on the frame rather than the window
+    fn frame_ray(&self, pixel: (f32, f32)) -> (Point3<f32>, Vector3<f32>) {
+        let (origin, direction) = self.camera.ray_from_screen(pixel, self.frame_size());
+        (origin - self.pivot_offset, direction)
+    }
+
+    // the closest hit with the object and triangle index, for `pick` and `pick_triangle`
+    fn ray_hit(&self, screen: (f32, f32)) -> Option<(Point3<f32>, usize, usize)> {
+        let (origin, direction) = self.screen_ray(screen);
+        // the meshes are tested untransformed, the hit goes back through the model transform
+        let inverse = self.model_transform.invert()?;
+        let (model_origin, model_direction) = (
+            inverse.transform_point(origin),
+            inverse.transform_vector(direction),
+        );
+        self.objects
+            .iter()
+            .enumerate()
+            .filter_map(|(i, object)| {
+                let data = &object.mesh.data;
+                data.intersect_ray_triangle(model_origin, model_direction)
+                    .map(|(t, triangle)| (t, i, triangle))
+            })
+            .fold(
+                None,
+                |closest: Option<(f32, usize, usize)>, hit| match closest {
+                    Some(closest) if closest.0 <= hit.0 => Some(closest),
+                    _ => Some(hit),
+                },
+            )
+            .map(|(t, object, triangle)| {
+                let point = self
+                    .model_transform
+                    .transform_point(model_origin + model_direction * t);
+                (point, object, triangle)
+            })
+    }
+
+    /*
+     * Outlines one triangle, given as an object index and an index into that object's
+     * MeshData::triangles like `pick_triangle` returns. None clears the selection.
+     */
+    pub fn select_triangle(&mut self, selection: Option<(usize, usize)>) {
+        self.selected_triangle = selection.filter(|&(object, triangle)| {
+            self.objects
+                .get(object)
+                .map_or(false, |o| triangle < o.mesh.data.triangles().len())
+        });
+        self.rebuild_isolated();
+        self.rebuild_overlays();
+    }
+
+    // triangle indices stop meaning anything once the geometry is replaced
+    fn clear_selection(&mut self) {
+        self.selected_triangle = None;
+        self.isolated = None;
+    }
+
+    // draws only the selected triangle, for a close look at a bad face
+    pub fn set_isolate_selection(&mut self, isolate: bool) {
+        self.isolate_selection = isolate;
+        self.rebuild_isolated();
+    }
+
+    fn rebuild_isolated(&mut self) {
+        let selection = self.selected_triangle.and_then(|(object, triangle)| {
+            let source = self.objects.get(object)?;
+            Some((
+                source,
+                triangle,
+                *source.mesh.data.triangles().get(triangle)?,
+            ))
+        });
+        self.isolated = match (self.isolate_selection, selection) {
+            (true, Some((source, triangle, corners))) => {
+                let data = MeshData {
+                    vertices: corners
+                        .iter()
+                        .map(|&index| source.mesh.data.vertices[index as usize])
+                        .collect(),
+                    indices: vec![0, 1, 2],
+                    material: source.mesh.data.material,
+                    ..Default::default()
+                };
+                let name = format!("{} (triangle {})", source.name, triangle);
+                let mesh = Mesh::new(
+                    &self.device,
+                    &self.queue,
+                    &self.morph_bind_group_layout,
+                    data,
+                );
+                Some(SceneObject::new(&name, mesh))
+            }
+            _ => None,
         };
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &DeviceDescriptor {
-                label: Some("Device"),
-                features: Features::default(),
-                limits: Limits::default(),
+    }
+
+    /*
+     * Nearest first by the centre of their bounds while opaque sorting is on, so the depth
+     * test throws away as much of what's behind as it can. It costs a sort every frame,
+     * which only pays off with a lot of overdraw.
+     */
+    pub fn set_opaque_sorting(&mut self, enabled: bool) {
+        // toggling back and forth with the GPU timer on compares the two
+        if let Some(timings) = self.gpu_timer.as_ref().and_then(GpuTimer::last) {
+            log::info!(
+                "main pass took {:.3} ms with opaque sorting {}",
+                timings.main_ms,
+                if self.opaque_sorting { "on" } else { "off" }
+            );
+        }
+        self.opaque_sorting = enabled;
+    }
+
+    // the order objects get drawn in, see `set_opaque_sorting`
+    pub(super) fn draw_order(&self) -> Vec<&SceneObject> {
+        let mut objects: Vec<&SceneObject> = self.drawn_objects().iter().collect();
+        if self.opaque_sorting {
+            let world = Matrix4::from_translation(self.pivot_offset) * self.model_transform;
+            let eye = self.camera.eye;
+            // objects without bounds have nothing to draw and go last
+            objects.sort_by_cached_key(|object| {
+                let distance = object.bounds.map_or(f32::INFINITY, |bounds| {
+                    (world.transform_point(bounds.center()) - eye).magnitude2()
+                });
+                mesh::sort_key(distance)
+            });
+        }
+        objects
+    }
+
+    // the objects the frame draws, just the isolated triangle while there is one
+    pub(super) fn drawn_objects(&self) -> &[SceneObject] {
+        match &self.isolated {
+            Some(object) => slice::from_ref(object),
+            None => &self.objects,
+        }
+    }
+
+    /*
+     * Places the whole scene without touching the geometry, the same way the pivot does.
+     * Clip planes, slices and shadows stay in the untransformed space.
+     */
+    pub fn set_model_transform(&mut self, transform: Matrix4<f32>) {
+        self.model_transform = transform;
+        self.rebuild_gizmo();
+        self.write_uniforms();
+    }
+
+    // the current view, for saving next to the model with ViewPreset::save
+    pub fn view_preset(&self) -> ViewPreset {
+        let c = self.clear_color;
+        ViewPreset {
+            model_transform: self.model_transform.into(),
+            camera_state: CameraState::of(&self.camera),
+            display_mode: self.display_mode,
+            clear_color: [c.r, c.g, c.b, c.a],
+        }
+    }
+
+    pub fn apply_preset(&mut self, preset: Preset) {
+        match preset {
+            Preset::TechnicalDrawing => {
+                // looking along +Y with Z up, like the grid
+                self.camera.up = Vector3::unit_z();
+                self.camera.eye = self.camera.target - Vector3::unit_y();
+                // through the setter, so feature edge lines and depth only pipelines go too
+                self.set_display_mode(DisplayMode::Default);
+                self.wireframe_visible = true;
+                self.grid_visible = false;
+                self.rebuild_overlays();
+                if let Some(bounds) = self.scene_bounds() {
+                    self.pivot_offset = Vector3::new(0.0, 0.0, 0.0);
+                    self.camera.frame_orthographic(&bounds, self.aspect_ratio());
+                }
+                self.write_uniforms();
+            }
+        }
+    }
+
+    pub fn render_setup(&self) -> RenderSetup {
+        let c = self.clear_color;
+        RenderSetup {
+            camera_state: CameraState::of(&self.camera),
+            ortho_height: self.camera.ortho_height,
+            light_direction: self.light_direction.into(),
+            shadows: Some(self.shadow.resolution).filter(|_| self.shadow.enabled),
+            ground_shadow: self.drawn_ground_shadow().map(|ground| ground.opacity),
+            clear_color: [c.r, c.g, c.b, c.a],
+            display_mode: self.display_mode,
+            normal_space: self.normal_space,
+            color_space: self.color_space,
+            hdr_samples: self.hdr.as_ref().map(|hdr| hdr.sample_count),
+            exposure: self.hdr.as_ref().map_or(1.0, |hdr| hdr.exposure),
+        }
+    }
+
+    // like `apply_view_preset` the pivot is reset, the camera was saved relative to its own
+    pub fn apply_render_setup(&mut self, setup: &RenderSetup) -> Result<(), String> {
+        let current_samples = self.hdr.as_ref().map(|hdr| hdr.sample_count);
+        if setup.hdr_samples != current_samples {
+            self.set_hdr(setup.hdr_samples)?;
+        }
+        if let Some(hdr) = &mut self.hdr {
+            hdr.exposure = setup.exposure;
+        }
+        setup.camera_state.apply(&mut self.camera);
+        self.camera.ortho_height = setup.ortho_height;
+        self.pivot_offset = Vector3::new(0.0, 0.0, 0.0);
+        self.light_direction = Vector3::from(setup.light_direction).normalize();
+        match setup.shadows {
+            Some(resolution) => self.set_shadows(true, resolution),
+            None => self.set_shadows(false, self.shadow.resolution),
+        }
+        match setup.ground_shadow {
+            Some(opacity) => self.set_ground_shadow(true, opacity),
+            None if self.ground_shadow.is_some() => self.set_ground_shadow(false, 0.0),
+            None => {}
+        }
+        let [r, g, b, a] = setup.clear_color;
+        self.clear_color = Color { r, g, b, a };
+        self.set_display_mode(setup.display_mode);
+        self.set_normal_space(setup.normal_space);
+        self.set_color_space(setup.color_space);
+        Ok(())
+    }
+
+    // the global render configuration as JSON, read back with `import_setup`
+    pub fn export_setup(&self, path: &Path) -> io::Result<()> {
+        self.render_setup().save(path)
+    }
+
+    pub fn import_setup(&mut self, path: &Path) -> io::Result<()> {
+        let setup = RenderSetup::load(path)?;
+        self.apply_render_setup(&setup)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+
+    // the pivot is reset since the preset's camera was saved relative to its own
+    pub fn apply_view_preset(&mut self, preset: &ViewPreset) {
+        self.model_transform = Matrix4::from(preset.model_transform);
+        self.rebuild_gizmo();
+        preset.camera_state.apply(&mut self.camera);
+        self.pivot_offset = Vector3::new(0.0, 0.0, 0.0);
+        let [r, g, b, a] = preset.clear_color;
+        self.clear_color = Color { r, g, b, a };
+        self.set_display_mode(preset.display_mode);
+        self.write_uniforms();
+    }
+
+    /*
+     * Shifts the scene so `point` sits at the camera target, which is what the view
+     * turns around. The geometry itself isn't touched: clip planes, shadows and exports
+     * stay in the same space as before.
+     */
+    pub fn set_pivot(&mut self, point: Point3<f32>) {
+        self.pivot_offset = self.camera.target - point;
+        self.write_uniforms();
+    }
+
+    fn aspect_ratio(&self) -> f32 {
+        let (width, height) = self.target_size.unwrap_or_else(|| self.frame_size());
+        width as f32 / height as f32
+    }
+
+    // what frames render at, the output resolution when there is one, otherwise the window's
+    pub fn frame_size(&self) -> (u32, u32) {
+        match &self.output {
+            Some(output) => output.size,
+            None => (self.surface_config.width, self.surface_config.height),
+        }
+    }
+
+    /*
+     * Renders at a fixed `resolution` whatever the window's size, scaled into the window
+     * with its aspect ratio kept and black bars around it. Captures, SVG export and picking
+     * go by it too, None goes back to rendering at the window's size. Everything sized to
+     * the frame, the depth and HDR targets and the accumulation history, is rebuilt.
+     */
+    pub fn set_output_resolution(&mut self, resolution: Option<(u32, u32)>) -> Result<(), String> {
+        if let Some((0, _)) | Some((_, 0)) = resolution {
+            return Err(format!("output resolution {:?} is empty", resolution));
+        }
+        let format = self.surface_config.format;
+        self.output = resolution.map(|size| OutputTarget::new(&self.device, format, size));
+        let hdr_samples = self.hdr.as_ref().map(|hdr| hdr.sample_count);
+        self.set_hdr(hdr_samples)?;
+        self.rebuild_post_targets();
+        self.rebuild_up_indicator();
+        Ok(())
+    }
+
+    pub(super) fn drawn_grid(&self) -> Option<&InfiniteGrid> {
+        self.grid
+            .as_ref()
+            .filter(|_| self.grid_visible && !self.objects.is_empty())
+    }
+
+    pub(super) fn drawn_ground_shadow(&self) -> Option<&GroundShadow> {
+        self.ground_shadow
+            .as_ref()
+            .filter(|ground| ground.enabled && !self.objects.is_empty())
+    }
+
+    fn light_view_proj(&self) -> Matrix4<f32> {
+        match self.scene_bounds() {
+            Some(bounds) => ShadowMap::light_view_proj(self.light_direction, &bounds),
+            None => Matrix4::identity(),
+        }
+    }
+
+    // encoding for the surface happens in the tonemap pass when rendering to HDR
+    fn output_encoding(&self) -> (u32, u32) {
+        match self.hdr {
+            Some(_) => (ColorSpace::Linear.shader_id(), 0),
+            None => (
+                self.color_space.shader_id(),
+                self.surface_config.format.describe().srgb as u32,
+            ),
+        }
+    }
+
+    fn uniforms(&self) -> Uniforms {
+        let (color_space, surface_srgb) = self.output_encoding();
+        let (slice, slice_half_width) = Slice::as_uniform(self.slice.as_ref());
+        let [override_base_color, override_emissive, override_material] = self
+            .material_override
+            .map_or([[0.0; 4]; 3], |material| material.as_uniform());
+        let (near, far) = self.camera.clip_range();
+        let mx_total = self.jittered_view_projection();
+        let mx_view = self.camera.view_matrix() * self.model_transform;
+        let mut clip_planes = [[0.0; 4]; MAX_CLIP_PLANES];
+        for (dst, plane) in clip_planes.iter_mut().zip(self.clip_planes.iter()) {
+            *dst = Plane::as_vec4(plane.as_ref());
+        }
+        Uniforms {
+            transform: mx_total.into(),
+            view: mx_view.into(),
+            clip_planes,
+            display_mode: self.display_mode.shader_id(),
+            time: self.elapsed,
+            color_space,
+            surface_srgb,
+            light_view_proj: self.light_view_proj().into(),
+            light_direction: self.light_direction.extend(0.0).into(),
+            shadow_params: [
+                if self.shadows_drawn() { 1.0 } else { 0.0 },
+                1.0 / self.shadow.resolution as f32,
+                SHADOW_BIAS,
+                0.0,
+            ],
+            color_ramp: self.color_ramp.shader_id(),
+            slice_half_width,
+            normal_space: self.normal_space.shader_id(),
+            vertex_ao: self.vertex_ao_visible as u32,
+            slice,
+            backface_color: self
+                .backface_color
+                .map_or([0.0; 4], |c| [c.r as f32, c.g as f32, c.b as f32, 1.0]),
+            flat_color: match self.display_mode {
+                DisplayMode::FlatColor(color) => color,
+                _ => [0.0; 4],
             },
-            None,
-        ))
-        .unwrap();
-        surface.configure(&device, &surface_config);
+            alpha_to_coverage: (self.sample_count() > 1) as u32,
+            material_override: self.material_override.is_some() as u32,
+            _padding: [0; 2],
+            override_base_color,
+            override_emissive,
+            override_material,
+            depth_range: [
+                near,
+                far,
+                self.camera.ortho_height.is_some() as u32 as f32,
+                self.camera.depth_correction as u32 as f32,
+            ],
+        }
+    }
 
-        Self {
-            instance,
-            surface,
-            surface_config,
-            adapter,
-            device,
-            queue,
-            active_pipeline: None,
-            vertex_buffer: None,
-            index_buffer: None,
-            index_count: 0,
-            bind_group: None,
+    fn write_uniforms(&self) {
+        if let Some(uniform_buf) = &self.uniform_buffer {
+            let uniforms = self.uniforms();
+            self.queue
+                .write_buffer(uniform_buf, 0, bytemuck::bytes_of(&uniforms));
+            self.shadow
+                .write_uniforms(&self.queue, uniforms.light_view_proj.into());
+        }
+        if let (Some(ground), Some(bounds)) = (self.drawn_ground_shadow(), self.scene_bounds()) {
+            ground.write_uniforms(
+                &self.queue,
+                self.jittered_view_projection(),
+                self.light_direction,
+                &bounds,
+            );
+        }
+        if let (Some(grid), Some(bounds)) = (self.drawn_grid(), self.scene_bounds()) {
+            // the eye and the view distance in the grid's untransformed space
+            let world = Matrix4::from_translation(self.pivot_offset) * self.model_transform;
+            let inverse = world.invert().unwrap_or_else(Matrix4::identity);
+            let eye = inverse.transform_point(self.camera.eye);
+            let target = inverse.transform_point(self.camera.target);
+            let scale = (eye - target).magnitude()
+                / (self.camera.eye - self.camera.target)
+                    .magnitude()
+                    .max(f32::EPSILON);
+            grid.write_uniforms(
+                &self.queue,
+                self.jittered_view_projection(),
+                eye,
+                self.camera.view_distance() * scale,
+                bounds.min.z,
+                &self.line_style,
+            );
+        }
+        if let Some(background) = &self.background {
+            background.write_uniforms(&self.queue, self.aspect_ratio());
+        }
+        if let Some(hdr) = &self.hdr {
+            hdr.write_uniforms(
+                &self.queue,
+                self.color_space.shader_id(),
+                self.surface_config.format.describe().srgb,
+            );
+        }
+    }
+
+    fn create_bind_group(&self, layout: &BindGroupLayout, uniform_buf: &Buffer) -> BindGroup {
+        self.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buf.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&self.matcap.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&self.shadow.view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::Sampler(&self.shadow.sampler),
+                },
+            ],
+        })
+    }
+
+    fn rebuild_bind_group(&mut self) {
+        if let (Some(layout), Some(uniform_buf)) = (&self.bind_group_layout, &self.uniform_buffer) {
+            self.bind_group = Some(self.create_bind_group(layout, uniform_buf));
         }
     }
 
-    pub fn init_cube(&mut self) {
+    pub fn add_cube(&mut self) {
         let cube_positions: &[f32] = &[
             /*
              *        +-[v6]----------+ <-- [v7]
@@ -111,119 +2566,242 @@ impl Renderer {
             -0.5, 0.5, 0.5, // v6
             0.5, 0.5, 0.5, // v7
         ];
-        let index_data = &[
+        let index_data: &[u32] = &[
             4, 5, 1, 4, 1, 0, 5, 7, 3, 5, 3, 1, 7, 6, 2, 7, 2, 3, 6, 4, 0, 6, 0, 2, 6, 7, 5, 6, 5,
-            4, 0, 1, 3, 0, 3, 4,
+            4, 0, 1, 3, 0, 3, 2,
         ];
-        let vertex_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(cube_positions),
-            usage: BufferUsages::VERTEX,
-        });
-
-        let index_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(index_data),
-            usage: BufferUsages::INDEX,
-        });
+        // the cube is shared-vertex, so the best normal we can give each corner is the
+        // direction away from the centre
+        let vertices: Vec<Vertex> = cube_positions
+            .chunks_exact(3)
+            .map(|p| {
+                let normal = cgmath::Vector3::new(p[0], p[1], p[2]).normalize();
+                Vertex {
+                    position: [p[0], p[1], p[2]],
+                    normal: normal.into(),
+                    // planar projection, the cube has no authored UVs
+                    uv: [p[0] + 0.5, 0.5 - p[1]],
+                }
+            })
+            .collect();
+        self.add_object(
+            "Cube",
+            MeshData {
+                vertices,
+                indices: index_data.to_vec(),
+                ..Default::default()
+            },
+        );
+    }
 
+    pub fn init_pipeline(&mut self) {
         let bind_group_layout = self
             .device
             .create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("Vertex Bind Group Layout"),
-                entries: &[BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::VERTEX,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: BufferSize::new(64),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(mem::size_of::<Uniforms>() as u64),
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler {
+                            filtering: true,
+                            comparison: false,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Depth,
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler {
+                            filtering: true,
+                            comparison: true,
+                        },
+                        count: None,
+                    },
+                ],
             });
         let pipeline_layout = self
             .device
             .create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Pipeline Layout"),
-                bind_group_layouts: &[&bind_group_layout],
+                bind_group_layouts: &[
+                    &bind_group_layout,
+                    &self.morph_bind_group_layout,
+                    &self.custom_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
-        let mx_total = Renderer::generate_matrix(
-            self.surface_config.width as f32 / self.surface_config.height as f32,
-        );
-        let mx_ref: &[f32; 16] = mx_total.as_ref();
-        let uniform_buf = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Uniform Buffer"),
-                contents: bytemuck::cast_slice(mx_ref),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
-        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: uniform_buf.as_entire_binding(),
-            }],
+        let uniform_buf = self.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Uniform Buffer"),
+            contents: bytemuck::bytes_of(&self.uniforms()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let bind_group = self.create_bind_group(&bind_group_layout, &uniform_buf);
         let shader = self.device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("Shader"),
             source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shader.wgsl"))),
         });
-        let vertex_size = mem::size_of::<[f32; 3]>();
-        let vertex_buffers = [VertexBufferLayout {
-            array_stride: vertex_size as BufferAddress,
-            step_mode: VertexStepMode::Vertex,
-            attributes: &[VertexAttribute {
-                format: VertexFormat::Float32x3,
-                offset: 0,
-                shader_location: 0,
-            }],
-        }];
+        self.bind_group = Some(bind_group);
+        self.uniform_buffer = Some(uniform_buf);
+        self.bind_group_layout = Some(bind_group_layout);
+        self.pipeline_layout = Some(pipeline_layout);
+        self.shader = Some(shader);
+        self.rebuild_pipeline();
+    }
+
+    fn rebuild_pipeline(&mut self) {
+        let topologies: HashSet<Topology> = iter::once(Topology::TriangleList)
+            .chain(self.objects.iter().map(|object| object.mesh.data.topology))
+            .collect();
+        self.pipelines = topologies
+            .into_iter()
+            .filter_map(|topology| {
+                Some((
+                    topology,
+                    self.create_pipeline(topology, false, ColorWrites::ALL)?,
+                ))
+            })
+            .collect();
+        // wgpu rejects alpha to coverage on single sampled targets, those just discard
+        self.coverage_pipelines = if self.sample_count() > 1 {
+            let topologies: HashSet<Topology> = self
+                .objects
+                .iter()
+                .filter(|object| object.alpha_cutoff().is_some())
+                .map(|object| object.mesh.data.topology)
+                .collect();
+            topologies
+                .into_iter()
+                .filter_map(|topology| {
+                    Some((
+                        topology,
+                        self.create_pipeline(topology, true, ColorWrites::ALL)?,
+                    ))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        self.background_pipeline = Some(Background::create_pipeline(
+            &self.device,
+            &self.background_bind_group_layout,
+            self.target_format(),
+            self.sample_count(),
+        ));
+        let (format, samples) = (self.target_format(), self.sample_count());
+        self.line_pipeline = self
+            .bind_group_layout
+            .as_ref()
+            .map(|layout| lines::create_pipeline(&self.device, layout, format, samples, false));
+        self.gizmo_pipeline = self
+            .bind_group_layout
+            .as_ref()
+            .map(|layout| lines::create_pipeline(&self.device, layout, format, samples, true));
+        let (target_format, sample_count) = (self.target_format(), self.sample_count());
+        if let Some(ground) = &mut self.ground_shadow {
+            ground.rebuild_pipeline(&self.device, target_format, sample_count);
+        }
+        if let Some(grid) = &mut self.grid {
+            grid.rebuild_pipeline(&self.device, target_format, sample_count);
+        }
+        self.rebuild_depth_only_pipelines();
+    }
+
+    fn create_pipeline(
+        &self,
+        topology: Topology,
+        alpha_to_coverage: bool,
+        color_writes: ColorWrites,
+    ) -> Option<RenderPipeline> {
+        let (pipeline_layout, shader) = match (&self.pipeline_layout, &self.shader) {
+            (Some(pipeline_layout), Some(shader)) => (pipeline_layout, shader),
+            _ => return None,
+        };
+        let vertex_buffers = [Vertex::layout(), heatmap::scalar_layout(), ao::ao_layout()];
         let pipeline = self
             .device
             .create_render_pipeline(&RenderPipelineDescriptor {
                 label: Some("Render Pipeline"),
-                layout: Some(&pipeline_layout),
+                layout: Some(pipeline_layout),
                 vertex: VertexState {
-                    module: &shader,
+                    module: shader,
                     entry_point: "vs_main",
                     buffers: &vertex_buffers,
                 },
                 primitive: PrimitiveState {
-                    topology: PrimitiveTopology::TriangleStrip,
-                    strip_index_format: Some(IndexFormat::Uint32),
+                    topology: topology.primitive(),
+                    strip_index_format: topology.strip_index_format(),
                     front_face: FrontFace::Ccw,
-                    cull_mode: Some(Face::Back),
+                    cull_mode: self.cull_mode,
                     clamp_depth: false,
                     polygon_mode: PolygonMode::Fill,
                     conservative: false,
                 },
-                depth_stencil: None,
+                depth_stencil: Some(DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: CompareFunction::Less,
+                    stencil: StencilState::default(),
+                    bias: self.depth_bias,
+                }),
                 multisample: MultisampleState {
-                    count: 1,
+                    count: self.sample_count(),
                     mask: !0,
-                    alpha_to_coverage_enabled: false,
+                    alpha_to_coverage_enabled: alpha_to_coverage,
                 },
                 fragment: Some(FragmentState {
-                    module: &shader,
+                    module: shader,
                     entry_point: "fs_main",
-                    targets: &[self.surface_config.format.into()],
+                    targets: &[ColorTargetState {
+                        format: self.target_format(),
+                        blend: None,
+                        write_mask: color_writes,
+                    }],
                 }),
             });
-        self.active_pipeline = Some(pipeline);
-        self.vertex_buffer = Some(vertex_buffer);
-        self.index_buffer = Some(index_buffer);
-        self.index_count = index_data.len();
-        self.bind_group = Some(bind_group);
+        Some(pipeline)
     }
 
-    pub fn draw_cube(renderer: &Renderer) {
-        // render here
+    pub fn draw(renderer: &Renderer) {
+        // before acquiring, so a full queue doesn't also hold on to a surface texture
+        renderer.frame_limiter.wait(&renderer.device);
         let frame = match renderer.surface.get_current_texture() {
             Ok(frame) => frame,
+            // the surface is still fine, just busy, so try again next frame
+            Err(SurfaceError::Timeout) => {
+                renderer.skip_timed_out_frame();
+                return;
+            }
             Err(_) => {
                 renderer
                     .surface
@@ -237,51 +2815,258 @@ impl Renderer {
         let view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = renderer
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.1,
-                            b: 0.6,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
-            rpass.push_debug_group("preparing data for drawing...");
-            rpass.set_pipeline(renderer.active_pipeline.as_ref().unwrap());
-            rpass.set_bind_group(0, renderer.bind_group.as_ref().unwrap(), &[]);
-            rpass.set_index_buffer(
-                renderer.index_buffer.as_ref().unwrap().slice(..),
-                wgpu::IndexFormat::Uint32,
-            );
-            rpass.set_vertex_buffer(0, renderer.vertex_buffer.as_ref().unwrap().slice(..));
-            rpass.pop_debug_group();
-            rpass.insert_debug_marker("drawing");
-            rpass.draw_indexed(0..renderer.index_count as u32, 0, 0..1);
+        let timer = renderer.gpu_timer.as_ref();
+        let depth = &renderer.depth_texture.view;
+        let buffers = match &renderer.output {
+            Some(output) => {
+                let mut buffers = renderer.encode_frame(&output.color.view, depth, timer);
+                let window = (
+                    renderer.surface_config.width,
+                    renderer.surface_config.height,
+                );
+                buffers.push(output.blit(&renderer.device, &view, window));
+                buffers
+            }
+            None => renderer.encode_frame(&view, depth, timer),
+        };
+        renderer.queue.submit(buffers);
+        renderer.frame_limiter.submitted(&renderer.queue);
+        if let Some(timer) = timer {
+            timer.read(&renderer.device);
         }
-        renderer.queue.submit(Some(encoder.finish()));
         frame.present();
+        renderer.timeouts_in_a_row.set(0);
+    }
+
+    fn skip_timed_out_frame(&self) {
+        self.skipped_frames.set(self.skipped_frames.get() + 1);
+        let in_a_row = self.timeouts_in_a_row.get() + 1;
+        self.timeouts_in_a_row.set(in_a_row);
+        if in_a_row == self.timeout_warning_threshold {
+            log::warn!(
+                "acquiring the surface texture timed out {} frames in a row, \
+                 a different present mode (see set_vsync) may help",
+                in_a_row
+            );
+        }
     }
 
-    fn generate_matrix(aspect_ratio: f32) -> cgmath::Matrix4<f32> {
-        let mx_projection = cgmath::perspective(cgmath::Deg(45f32), aspect_ratio, 1.0, 10.0);
-        let mx_view = cgmath::Matrix4::look_at_rh(
-            cgmath::Point3::new(1.5f32, -5.0, 3.0),
-            cgmath::Point3::new(0f32, 0.0, 0.0),
-            cgmath::Vector3::unit_z(),
+    /*
+     * Renders one frame into an offscreen copy of the surface and reads it back, blocking
+     * until the GPU is done. The pixels are what the window would show, already encoded.
+     */
+    pub fn capture_frame(&self) -> image::RgbaImage {
+        let (width, height) = self.frame_size();
+        let target = Texture::render_target(
+            &self.device,
+            "Capture Texture",
+            self.surface_config.format,
+            width,
+            height,
+            1,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
         );
-        let mx_correction = OPENGL_TO_WGPU_MATRIX;
-        mx_correction * mx_projection * mx_view
+        self.queue
+            .submit(self.encode_frame(&target.view, &self.depth_texture.view, None));
+        capture::read_texture(
+            &self.device,
+            &self.queue,
+            &target.texture,
+            self.surface_config.format,
+            width,
+            height,
+        )
+    }
+
+    /*
+     * Draws the frame with the current camera and scene into a view the caller owns, for
+     * compositing the viewer into a bigger wgpu application. `target` has to be in the
+     * surface format, `depth` in DEPTH_FORMAT with `sample_count` samples, both `size`
+     * big. Without a depth view one is made just for the call. With HDR on the scene goes
+     * through the HDR target, which is `frame_size` big, so `size` has to be too.
+     */
+    pub fn render_into(
+        &mut self,
+        target: &TextureView,
+        size: (u32, u32),
+        depth: Option<&TextureView>,
+    ) -> Result<(), String> {
+        let frame_size = self.frame_size();
+        if self.hdr.is_some() && size != frame_size {
+            return Err(format!(
+                "with HDR on the target has to be {:?} like the frame, not {:?}",
+                frame_size, size
+            ));
+        }
+        let owned_depth;
+        let depth = match depth {
+            Some(depth) => depth,
+            None => {
+                owned_depth = Texture::depth(&self.device, size.0, size.1, self.sample_count());
+                &owned_depth.view
+            }
+        };
+        self.target_size = Some(size);
+        self.rebuild_post_targets();
+        self.write_uniforms();
+        self.queue.submit(self.encode_frame(target, depth, None));
+        self.target_size = None;
+        self.rebuild_post_targets();
+        self.write_uniforms();
+        Ok(())
+    }
+
+    // every object's vertices and indices as uploaded, read back with BufferDump::read
+    pub fn dump_buffers(&self, path: &Path) -> io::Result<()> {
+        dump::write(path, &self.objects, self.origin)
+    }
+
+    // the scene's edges as vector line art from the current camera, see svg::render
+    pub fn export_svg(&self, path: &Path, remove_hidden: bool) -> io::Result<()> {
+        let world = Matrix4::from_translation(self.pivot_offset) * self.model_transform;
+        let viewport = self.frame_size();
+        let svg = svg::render(&self.objects, world, &self.camera, viewport, remove_hidden);
+        fs::write(path, svg)
+    }
+
+    // for snapshot tests, `tolerance` is the largest per-channel difference still accepted
+    pub fn compare_to_reference(
+        &self,
+        reference: &Path,
+        tolerance: u8,
+    ) -> image::ImageResult<DiffReport> {
+        let reference = image::open(reference)?.into_rgba8();
+        Ok(capture::diff(&self.capture_frame(), &reference, tolerance))
+    }
+
+    // everything a frame draws, into `view` which has to be in the surface format
+    fn encode_frame(
+        &self,
+        view: &TextureView,
+        depth: &TextureView,
+        timer: Option<&GpuTimer>,
+    ) -> Vec<CommandBuffer> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Frame Encoder"),
+            });
+        if let Some(timer) = timer {
+            timer.write(&mut encoder, 0);
+        }
+        // with effects to run the frame is finished off screen, they write into `view`
+        let chain = self.post_chain();
+        let post = self.post_targets.as_ref().filter(|_| !chain.is_empty());
+        let frame_view = post.map_or(view, PostTargets::frame);
+        if self.shadows_drawn() {
+            self.shadow.render(&mut encoder, self.drawn_objects());
+        }
+        if let Some(ground) = self.drawn_ground_shadow() {
+            ground.render_mask(&mut encoder, self.drawn_objects());
+        }
+        if let Some(timer) = timer {
+            timer.write(&mut encoder, 1);
+        }
+        // with HDR the passes draw into the float target, the surface only gets the tonemap
+        let target = match &self.hdr {
+            Some(hdr) => hdr.attachment(),
+            None => (frame_view, None),
+        };
+        let mut buffers = Vec::new();
+        let scene: &dyn FramePass = if self.drawn_objects().len() > OBJECTS_PER_BATCH {
+            {
+                let mut rpass = pass::begin(
+                    &mut encoder,
+                    BackgroundPass.label(),
+                    target,
+                    depth,
+                    Some(self.clear_color),
+                );
+                BackgroundPass.draw(self, &mut rpass);
+            }
+            buffers.push(encoder.finish());
+            buffers.extend(self.encode_object_batches(target, depth));
+            encoder = self
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Frame Encoder"),
+                });
+            &LinePass
+        } else {
+            &ScenePass
+        };
+        let passes = iter::once(scene).chain(self.passes.iter().map(|pass| pass.as_ref()));
+        for (i, pass) in passes.enumerate() {
+            // only the very first pass of the frame clears
+            let clear = if i == 0 && buffers.is_empty() {
+                Some(self.clear_color)
+            } else {
+                None
+            };
+            let mut rpass = pass::begin(&mut encoder, pass.label(), target, depth, clear);
+            pass.draw(self, &mut rpass);
+        }
+        if let Some(timer) = timer {
+            timer.write(&mut encoder, 2);
+        }
+        if let Some(hdr) = &self.hdr {
+            if let Some(auto) = &self.auto_exposure {
+                auto.adapt(&mut encoder, hdr);
+            }
+            match self.taa_active() {
+                Some(taa) => {
+                    if !taa.converged() {
+                        taa.accumulate(&mut encoder);
+                    }
+                    hdr.tonemap_from(&mut encoder, frame_view, &taa.tonemap_bind_group);
+                }
+                None => hdr.tonemap(&mut encoder, frame_view),
+            }
+        }
+        if let Some(targets) = post {
+            let ctx = PostContext {
+                device: &self.device,
+                queue: &self.queue,
+                format: self.surface_config.format,
+                size: targets.size,
+            };
+            targets.run(&chain, &mut encoder, view, &ctx);
+        }
+        if let Some(timer) = timer {
+            timer.write(&mut encoder, 3);
+            timer.resolve(&mut encoder);
+        }
+        buffers.push(encoder.finish());
+        buffers
+    }
+
+    /*
+     * Records the objects on the rayon pool, a command buffer per batch, returned in object
+     * order. The workers only get the device and resources that are Sync, never the renderer.
+     */
+    fn encode_object_batches(
+        &self,
+        target: (&TextureView, Option<&TextureView>),
+        depth: &TextureView,
+    ) -> Vec<CommandBuffer> {
+        let batch = ObjectBatch::new(self);
+        let device = &self.device;
+        let objects = self.draw_order();
+        let threads = rayon::current_num_threads().max(1);
+        let size = ((objects.len() + threads - 1) / threads).max(OBJECTS_PER_BATCH);
+        objects
+            .par_chunks(size)
+            .map(|objects| {
+                let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Object Batch Encoder"),
+                });
+                {
+                    let mut rpass =
+                        pass::begin(&mut encoder, "Object Batch Pass", target, depth, None);
+                    batch.draw(objects.iter().copied(), &mut rpass);
+                }
+                encoder.finish()
+            })
+            .collect()
     }
 }
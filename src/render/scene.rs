@@ -0,0 +1,89 @@
+use cgmath::Vector3;
+
+use super::{Aabb, CameraDef, EdgeDiagnostics, Lines, Mesh, MeshData, Skeleton};
+
+pub struct SceneObject {
+    pub name: String,
+    pub mesh: Mesh,
+    pub bounds: Option<Aabb>,
+    // the geometry as loaded, kept while a filter like smoothing is applied
+    pub original: Option<MeshData>,
+    // found when the object is added and again whenever a replacement mesh changes the
+    // vertex count, smoothing moves vertices but keeps the topology
+    pub edges: EdgeDiagnostics,
+    // higher draws in front of coplanar faces of lower ones, see Renderer::set_layer_priority
+    pub layer_priority: i32,
+    // per vertex, from Renderer::bake_vertex_ao
+    pub ao: Option<Vec<f32>>,
+    // bumped by every `replace_mesh`, so copies of the vertices can tell they're stale
+    pub revision: u32,
+}
+
+// everything Renderer::clear_scene took down, kept on the GPU to bring back in place
+pub struct StashedScene {
+    pub objects: Vec<SceneObject>,
+    pub lines: Vec<Lines>,
+    pub gltf_cameras: Vec<CameraDef>,
+    pub skeletons: Vec<Skeleton>,
+    pub origin: Vector3<f64>,
+}
+
+// what stays on the GPU while a model is uploaded, see Renderer::check_memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeptScenes {
+    // added to the scene, the stashed one stays too
+    Both,
+    // the scene gets stashed by Renderer::clear_scene, replacing what was stashed before
+    Current,
+    // everything is dropped first, like switching between a glTF file's scenes
+    None,
+}
+
+// one of the scenes a glTF file holds, see Renderer::gltf_scenes
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneInfo {
+    pub index: usize,
+    pub name: Option<String>,
+}
+
+impl SceneObject {
+    pub fn new(name: &str, mesh: Mesh) -> Self {
+        let bounds = mesh.data.bounds();
+        let edges = EdgeDiagnostics::new(&mesh.data);
+        Self {
+            name: name.to_owned(),
+            mesh,
+            bounds,
+            original: None,
+            edges,
+            layer_priority: 0,
+            ao: None,
+            revision: 0,
+        }
+    }
+
+    pub fn replace_mesh(&mut self, mesh: Mesh) {
+        let topology_changed = mesh.data.vertices.len() != self.mesh.data.vertices.len();
+        self.bounds = mesh.data.bounds();
+        self.mesh = mesh;
+        self.revision = self.revision.wrapping_add(1);
+        if topology_changed {
+            self.edges = EdgeDiagnostics::new(&self.mesh.data);
+        }
+    }
+
+    // see MaterialFactors::alpha_cutoff
+    pub fn alpha_cutoff(&self) -> Option<f32> {
+        self.mesh
+            .data
+            .material
+            .and_then(|material| material.alpha_cutoff)
+    }
+}
+
+pub fn combined_bounds(objects: &[SceneObject]) -> Option<Aabb> {
+    objects
+        .iter()
+        .filter_map(|object| object.bounds)
+        .reduce(|a, b| a.union(&b))
+}
@@ -0,0 +1,204 @@
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+use std::{borrow::Cow, mem};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use super::{
+    camera::OPENGL_TO_WGPU_MATRIX, texture::DEPTH_FORMAT, Aabb, SceneObject, Topology, Vertex,
+};
+
+/*
+ * Depth-only render of the scene from the primary directional light, sampled with
+ * PCF in the main fragment shader. The light's orthographic frustum is fitted to
+ * the scene's bounding sphere so the whole scene can cast and receive.
+ */
+pub struct ShadowMap {
+    pub enabled: bool,
+    pub resolution: u32,
+    pub view: TextureView,
+    pub sampler: Sampler,
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    strip_pipeline: RenderPipeline,
+}
+
+impl ShadowMap {
+    pub fn new(device: &Device, morph_layout: &BindGroupLayout, resolution: u32) -> Self {
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            contents: bytemuck::bytes_of(&<[[f32; 4]; 4]>::from(Matrix4::<f32>::identity())),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Shadow Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(mem::size_of::<[[f32; 4]; 4]>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Shadow Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, morph_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shadow.wgsl"))),
+        });
+        let pipeline =
+            ShadowMap::create_pipeline(device, &pipeline_layout, &shader, Topology::TriangleList);
+        let strip_pipeline =
+            ShadowMap::create_pipeline(device, &pipeline_layout, &shader, Topology::TriangleStrip);
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        Self {
+            enabled: false,
+            resolution,
+            view: ShadowMap::create_view(device, resolution),
+            sampler,
+            uniform_buffer,
+            bind_group,
+            pipeline,
+            strip_pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        topology: Topology,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_shadow",
+                buffers: &[Vertex::layout()],
+            },
+            primitive: PrimitiveState {
+                topology: topology.primitive(),
+                strip_index_format: topology.strip_index_format(),
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                clamp_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                // keeps lit surfaces from shadowing themselves (acne)
+                bias: DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState::default(),
+            fragment: None,
+        })
+    }
+
+    fn create_view(device: &Device, resolution: u32) -> TextureView {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+        texture.create_view(&TextureViewDescriptor::default())
+    }
+
+    // returns true when the shadow map texture was recreated
+    pub fn resize(&mut self, device: &Device, resolution: u32) -> bool {
+        if resolution == self.resolution {
+            return false;
+        }
+        self.resolution = resolution;
+        self.view = ShadowMap::create_view(device, resolution);
+        true
+    }
+
+    pub fn light_view_proj(direction: Vector3<f32>, bounds: &Aabb) -> Matrix4<f32> {
+        let center = bounds.center();
+        let radius = bounds.radius().max(f32::EPSILON);
+        let direction = direction.normalize();
+        let up = if direction.cross(Vector3::unit_z()).magnitude2() < 1e-4 {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_z()
+        };
+        let eye = center - direction * radius * 2.0;
+        let mx_view = Matrix4::look_at_rh(eye, center, up);
+        let mx_projection = cgmath::ortho(-radius, radius, -radius, radius, radius, radius * 3.0);
+        OPENGL_TO_WGPU_MATRIX * mx_projection * mx_view
+    }
+
+    pub fn write_uniforms(&self, queue: &Queue, light_view_proj: Matrix4<f32>) {
+        let matrix: [[f32; 4]; 4] = light_view_proj.into();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&matrix));
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, objects: &[SceneObject]) {
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        let casters = objects
+            .iter()
+            .filter(|object| object.mesh.data.topology.has_triangles());
+        for object in casters {
+            rpass.set_pipeline(match object.mesh.data.topology {
+                Topology::TriangleStrip => &self.strip_pipeline,
+                _ => &self.pipeline,
+            });
+            rpass.set_bind_group(1, &object.mesh.morph.bind_group, &[]);
+            rpass.set_index_buffer(object.mesh.index_buffer.slice(..), object.mesh.index_format);
+            rpass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+            rpass.draw_indexed(0..object.mesh.index_count(), 0, 0..1);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use cgmath::{EuclideanSpace, Matrix3, Point3, Vector3};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Joint {
+    pub position: Point3<f32>,
+    // index into Skeleton::joints, None for roots
+    pub parent: Option<usize>,
+}
+
+/*
+ * A rig's joints at the pose the mesh was bound in, so they line up with the unskinned
+ * geometry the viewer draws. Only used for display, see Renderer::show_skeleton.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+    pub name: Option<String>,
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    // parent and child pairs, one per bone
+    pub fn bones(&self) -> impl Iterator<Item = (Point3<f32>, Point3<f32>)> + '_ {
+        self.joints.iter().filter_map(move |joint| {
+            joint
+                .parent
+                .and_then(|parent| self.joints.get(parent))
+                .map(|parent| (parent.position, joint.position))
+        })
+    }
+
+    pub fn change_basis(&mut self, basis: Matrix3<f32>) {
+        for joint in self.joints.iter_mut() {
+            joint.position = Point3::from_vec(basis * joint.position.to_vec());
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vector3<f32>) {
+        for joint in self.joints.iter_mut() {
+            joint.position += offset;
+        }
+    }
+}
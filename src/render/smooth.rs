@@ -0,0 +1,71 @@
+use cgmath::{Vector3, Zero};
+use std::collections::HashMap;
+
+use super::MeshData;
+
+// vertices split along uv or normal seams share a position, weld them so the
+// smoothing doesn't tear the surface apart at the seams
+pub(super) fn weld(data: &MeshData) -> (Vec<usize>, Vec<Vector3<f32>>) {
+    let mut groups: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut positions = Vec::new();
+    let group_of = data
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let [x, y, z] = vertex.position;
+            let key = [x.to_bits(), y.to_bits(), z.to_bits()];
+            *groups.entry(key).or_insert_with(|| {
+                positions.push(Vector3::from(vertex.position));
+                positions.len() - 1
+            })
+        })
+        .collect();
+    (group_of, positions)
+}
+
+fn adjacency(data: &MeshData, group_of: &[usize], group_count: usize) -> Vec<Vec<usize>> {
+    let mut neighbours = vec![Vec::new(); group_count];
+    for tri in data.triangles() {
+        for i in 0..3 {
+            let a = group_of[tri[i] as usize];
+            let b = group_of[tri[(i + 1) % 3] as usize];
+            if a != b {
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+            }
+        }
+    }
+    for list in neighbours.iter_mut() {
+        list.sort_unstable();
+        list.dedup();
+    }
+    neighbours
+}
+
+// moves every vertex `lambda` of the way toward the average of its neighbours per iteration
+pub fn laplacian(data: &MeshData, iterations: u32, lambda: f32) -> MeshData {
+    let (group_of, mut positions) = weld(data);
+    let neighbours = adjacency(data, &group_of, positions.len());
+    for _ in 0..iterations {
+        positions = positions
+            .iter()
+            .zip(&neighbours)
+            .map(|(&p, adjacent)| {
+                if adjacent.is_empty() {
+                    return p;
+                }
+                let sum = adjacent
+                    .iter()
+                    .fold(Vector3::zero(), |sum, &n| sum + positions[n]);
+                let average = sum / adjacent.len() as f32;
+                p + (average - p) * lambda
+            })
+            .collect();
+    }
+    let mut smoothed = data.clone();
+    for (vertex, &group) in smoothed.vertices.iter_mut().zip(&group_of) {
+        vertex.position = positions[group].into();
+    }
+    smoothed.compute_normals();
+    smoothed
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+use wgpu::{Backend, TextureFormat};
+
+use super::{DisplayMode, GpuTimings};
+
+pub struct Stats {
+    pub adapter: String,
+    pub backend: Backend,
+    pub surface_format: TextureFormat,
+    pub resolution: (u32, u32),
+    pub sample_count: u32,
+    pub frame_time_ms: f32,
+    pub triangles: usize,
+    // in scene units through the model transform, see Renderer::surface_area
+    pub surface_area: f32,
+    // None unless every mesh is closed, see Renderer::volume
+    pub volume: Option<f32>,
+    pub draw_calls: u32,
+    pub display_mode: DisplayMode,
+    // None when the adapter can't do timestamp queries
+    pub gpu_timings: Option<GpuTimings>,
+    // frames dropped because the surface texture timed out
+    pub skipped_frames: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fps = if self.frame_time_ms > 0.0 {
+            1000.0 / self.frame_time_ms
+        } else {
+            0.0
+        };
+        writeln!(f, "adapter:      {} ({:?})", self.adapter, self.backend)?;
+        writeln!(f, "surface:      {:?}", self.surface_format)?;
+        writeln!(
+            f,
+            "resolution:   {}x{} @ {}x",
+            self.resolution.0, self.resolution.1, self.sample_count
+        )?;
+        writeln!(
+            f,
+            "frame time:   {:.2}ms ({:.0} fps)",
+            self.frame_time_ms, fps
+        )?;
+        if let Some(gpu) = self.gpu_timings {
+            writeln!(
+                f,
+                "gpu passes:   shadow {:.2}ms, main {:.2}ms, post {:.2}ms",
+                gpu.shadow_ms, gpu.main_ms, gpu.post_ms
+            )?;
+        }
+        writeln!(f, "triangles:    {}", self.triangles)?;
+        writeln!(f, "surface area: {:.4}", self.surface_area)?;
+        match self.volume {
+            Some(volume) => writeln!(f, "volume:       {:.4}", volume)?,
+            None => writeln!(f, "volume:       - (not closed)")?,
+        }
+        writeln!(f, "draw calls:   {}", self.draw_calls)?;
+        if self.skipped_frames > 0 {
+            writeln!(f, "skipped:      {} frames", self.skipped_frames)?;
+        }
+        write!(f, "display mode: {:?}", self.display_mode)
+    }
+}
@@ -0,0 +1,134 @@
+use cgmath::{Vector3, Zero};
+use std::{collections::HashMap, f32::consts::PI};
+
+use super::{smooth, MeshData, Topology, Vertex};
+
+// Loop's weight for each of a vertex's `n` neighbours
+fn beta(n: usize) -> f32 {
+    let n = n as f32;
+    let c = 3.0 / 8.0 + (2.0 * PI / n).cos() / 4.0;
+    (5.0 / 8.0 - c * c) / n
+}
+
+/*
+ * One level of Loop subdivision: every triangle becomes four through its edge midpoints,
+ * then the old vertices move towards their neighbours and the new ones settle between
+ * the four corners around their edge. Open borders and non-manifold edges use the curve
+ * rule instead, so outlines stay put rather than shrinking. Like smoothing it works on
+ * welded positions so seams don't tear, with uvs and morph deltas interpolated.
+ */
+fn subdivide_once(data: &MeshData) -> MeshData {
+    let (group_of, positions) = smooth::weld(data);
+    let triangles = data.triangles();
+    // the corners opposite each edge, one per triangle using it
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for tri in &triangles {
+        for i in 0..3 {
+            let a = group_of[tri[i] as usize];
+            let b = group_of[tri[(i + 1) % 3] as usize];
+            let c = group_of[tri[(i + 2) % 3] as usize];
+            edges.entry((a.min(b), a.max(b))).or_default().push(c);
+        }
+    }
+
+    // old vertices, with the neighbours along a border taking over where there is one
+    let mut neighbours = vec![Vec::new(); positions.len()];
+    let mut border_neighbours = vec![Vec::new(); positions.len()];
+    for (&(a, b), opposite) in &edges {
+        if a == b {
+            continue;
+        }
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+        if opposite.len() != 2 {
+            border_neighbours[a].push(b);
+            border_neighbours[b].push(a);
+        }
+    }
+    let moved: Vec<Vector3<f32>> = positions
+        .iter()
+        .enumerate()
+        .map(|(group, &p)| match border_neighbours[group].as_slice() {
+            [] if neighbours[group].is_empty() => p,
+            [] => {
+                let adjacent = &neighbours[group];
+                let beta = beta(adjacent.len());
+                let sum = adjacent
+                    .iter()
+                    .fold(Vector3::zero(), |sum, &n| sum + positions[n]);
+                p * (1.0 - adjacent.len() as f32 * beta) + sum * beta
+            }
+            &[a, b] => p * 0.75 + (positions[a] + positions[b]) * 0.125,
+            // a corner where borders meet, or a non-manifold one, stays where it is
+            _ => p,
+        })
+        .collect();
+    let edge_point = |a: usize, b: usize| {
+        let (pa, pb) = (positions[a], positions[b]);
+        match edges[&(a.min(b), a.max(b))].as_slice() {
+            &[c, d] if a != b => (pa + pb) * 0.375 + (positions[c] + positions[d]) * 0.125,
+            _ => (pa + pb) * 0.5,
+        }
+    };
+
+    let mut vertices: Vec<Vertex> = data
+        .vertices
+        .iter()
+        .zip(&group_of)
+        .map(|(vertex, &group)| Vertex {
+            position: moved[group].into(),
+            ..*vertex
+        })
+        .collect();
+    let mut morph_targets = data.morph_targets.clone();
+    // keyed on the vertices rather than the welded positions, so seams keep their uvs apart
+    let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut midpoint = |a: u32, b: u32| {
+        *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+            let (va, vb) = (data.vertices[a as usize], data.vertices[b as usize]);
+            let position = edge_point(group_of[a as usize], group_of[b as usize]);
+            vertices.push(Vertex {
+                position: position.into(),
+                normal: va.normal,
+                uv: [(va.uv[0] + vb.uv[0]) / 2.0, (va.uv[1] + vb.uv[1]) / 2.0],
+            });
+            for (target, deltas) in morph_targets.iter_mut().zip(&data.morph_targets) {
+                let (da, db) = (deltas[a as usize], deltas[b as usize]);
+                target.push([
+                    (da[0] + db[0]) / 2.0,
+                    (da[1] + db[1]) / 2.0,
+                    (da[2] + db[2]) / 2.0,
+                ]);
+            }
+            vertices.len() as u32 - 1
+        })
+    };
+    let mut indices = Vec::with_capacity(triangles.len() * 12);
+    for &[a, b, c] in &triangles {
+        let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+        indices.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+    }
+
+    let mut subdivided = MeshData {
+        vertices,
+        indices,
+        topology: Topology::TriangleList,
+        morph_targets,
+        morph_weights: data.morph_weights.clone(),
+        material: data.material,
+    };
+    subdivided.compute_normals();
+    subdivided
+}
+
+// each iteration quadruples the triangles, lines and points come back as they were
+pub fn loop_subdivide(data: &MeshData, iterations: u32) -> MeshData {
+    let mut subdivided = data.clone();
+    if !data.topology.has_triangles() {
+        return subdivided;
+    }
+    for _ in 0..iterations {
+        subdivided = subdivide_once(&subdivided);
+    }
+    subdivided
+}
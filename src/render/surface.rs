@@ -0,0 +1,53 @@
+use wgpu::*;
+
+// tried after the adapter's preferred format
+const FALLBACK_FORMATS: [TextureFormat; 4] = [
+    TextureFormat::Bgra8UnormSrgb,
+    TextureFormat::Rgba8UnormSrgb,
+    TextureFormat::Bgra8Unorm,
+    TextureFormat::Rgba8Unorm,
+];
+
+/*
+ * wgpu can't list the formats a surface supports, and the preferred one isn't
+ * guaranteed to work with every usage. Candidates are tried inside a validation
+ * error scope, sRGB ones first, until configure goes through.
+ */
+pub async fn configure(
+    surface: &Surface,
+    adapter: &Adapter,
+    device: &Device,
+    usage: TextureUsages,
+    width: u32,
+    height: u32,
+) -> Option<SurfaceConfiguration> {
+    let mut candidates: Vec<TextureFormat> =
+        surface.get_preferred_format(adapter).into_iter().collect();
+    for &format in FALLBACK_FORMATS.iter() {
+        if !candidates.contains(&format) {
+            candidates.push(format);
+        }
+    }
+    // stable, so the preferred format still leads within each group
+    candidates.sort_by_key(|format| !format.describe().srgb);
+    for format in candidates {
+        let config = SurfaceConfiguration {
+            usage,
+            format,
+            // a minimised window reports a zero size, which configure rejects
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: PresentMode::Fifo,
+        };
+        device.push_error_scope(ErrorFilter::Validation);
+        surface.configure(device, &config);
+        match device.pop_error_scope().await {
+            None => {
+                log::info!("surface format {:?}", format);
+                return Some(config);
+            }
+            Some(err) => log::warn!("surface format {:?} rejected: {}", format, err),
+        }
+    }
+    None
+}
@@ -0,0 +1,154 @@
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Rad, Transform, Vector3, Vector4};
+use std::{collections::HashMap, fmt::Write};
+
+use super::{bvh::Bvh, face_normal, scene, smooth, Camera, SceneObject};
+
+// edges between faces turning further than this are drawn as creases
+const CREASE_ANGLE: Deg<f32> = Deg(30.0);
+// pieces each edge is cut into when checking what's hidden
+const HIDDEN_LINE_SAMPLES: u32 = 16;
+// fraction of the scene's bounding radius occlusion rays start off the edge, so the
+// faces meeting at the edge don't hide it
+const HIDDEN_LINE_BIAS: f32 = 1e-3;
+
+// where the camera looks from at `point`, towards the eye for perspective views
+fn towards_viewer(camera: &Camera, point: Point3<f32>) -> Vector3<f32> {
+    match camera.ortho_height {
+        Some(_) => (camera.eye - camera.target).normalize(),
+        None => (camera.eye - point).normalize(),
+    }
+}
+
+/*
+ * Line art of the scene as the camera sees it: boundary and non-manifold edges, creases
+ * sharper than CREASE_ANGLE, and silhouettes where the surface turns away from the camera.
+ * `world` places the meshes like the transform uniform does. With `remove_hidden` every
+ * edge is cut into pieces and the ones with geometry between them and the camera left
+ * out, by casting rays rather than reading back depth so it doesn't depend on resolution.
+ */
+pub fn render(
+    objects: &[SceneObject],
+    world: Matrix4<f32>,
+    camera: &Camera,
+    viewport: (u32, u32),
+    remove_hidden: bool,
+) -> String {
+    let (width, height) = (viewport.0.max(1) as f32, viewport.1.max(1) as f32);
+    let view_proj = camera.view_projection_matrix(width / height);
+    let mut segments = Vec::new();
+    let mut triangles = Vec::new();
+    for object in objects {
+        let data = &object.mesh.data;
+        let positions: Vec<Point3<f32>> = data
+            .vertices
+            .iter()
+            .map(|vertex| world.transform_point(Point3::from(vertex.position)))
+            .collect();
+        // on welded positions like EdgeDiagnostics, so uv seams don't count as borders
+        let (group_of, _) = smooth::weld(data);
+        let mut edges: HashMap<(usize, usize), ([u32; 2], Vec<Vector3<f32>>)> = HashMap::new();
+        for tri in data.triangles() {
+            let corner = |i: usize| positions[tri[i] as usize];
+            let (a, b, c) = (corner(0), corner(1), corner(2));
+            triangles.push([a, b, c]);
+            let normal = face_normal(a.into(), b.into(), c.into());
+            for i in 0..3 {
+                let (a, b) = (tri[i], tri[(i + 1) % 3]);
+                let (ga, gb) = (group_of[a as usize], group_of[b as usize]);
+                if ga == gb {
+                    continue;
+                }
+                edges
+                    .entry((ga.min(gb), ga.max(gb)))
+                    .or_insert(([a, b], Vec::new()))
+                    .1
+                    .push(normal);
+            }
+        }
+        let crease = Rad::from(CREASE_ANGLE).0.cos();
+        for ([a, b], normals) in edges.values() {
+            let (a, b) = (positions[*a as usize], positions[*b as usize]);
+            let keep = match normals.as_slice() {
+                [n0, n1] => {
+                    let (n0, n1) = (n0.normalize(), n1.normalize());
+                    let view = towards_viewer(camera, a + (b - a) / 2.0);
+                    n0.dot(n1) < crease || (n0.dot(view) > 0.0) != (n1.dot(view) > 0.0)
+                }
+                _ => true,
+            };
+            if keep {
+                segments.push((a, b));
+            }
+        }
+    }
+
+    let bias =
+        scene::combined_bounds(objects).map_or(0.0, |bounds| bounds.radius()) * HIDDEN_LINE_BIAS;
+    let bvh = if remove_hidden {
+        Some(Bvh::new(triangles))
+    } else {
+        None
+    };
+    let visible = |point: Point3<f32>| match &bvh {
+        Some(bvh) => {
+            let direction = towards_viewer(camera, point);
+            let distance = match camera.ortho_height {
+                Some(_) => f32::INFINITY,
+                None => (camera.eye - point).magnitude() - bias,
+            };
+            !bvh.occluded(point + direction * bias, direction, distance)
+        }
+        None => true,
+    };
+    let project = |point: Point3<f32>| {
+        let clip = view_proj * Vector4::new(point.x, point.y, point.z, 1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        Some(((x + 1.0) / 2.0 * width, (1.0 - y) / 2.0 * height))
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width, height
+    );
+    svg.push_str(r#"<g fill="none" stroke="black" stroke-width="1" stroke-linecap="round">"#);
+    svg.push('\n');
+    let mut line = |from: Point3<f32>, to: Point3<f32>| {
+        if let (Some((x1, y1)), Some((x2, y2))) = (project(from), project(to)) {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#,
+                x1, y1, x2, y2
+            );
+        }
+    };
+    for (a, b) in segments {
+        if bvh.is_none() {
+            line(a, b);
+            continue;
+        }
+        // runs of visible pieces become one line each
+        let at = |i: u32| a + (b - a) * (i as f32 / HIDDEN_LINE_SAMPLES as f32);
+        let mut run_start = None;
+        for i in 0..HIDDEN_LINE_SAMPLES {
+            let shown = visible(at(i) + (at(i + 1) - at(i)) / 2.0);
+            match (shown, run_start) {
+                (true, None) => run_start = Some(i),
+                (false, Some(start)) => {
+                    line(at(start), at(i));
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = run_start {
+            line(at(start), b);
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
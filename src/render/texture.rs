@@ -0,0 +1,112 @@
+use std::num::NonZeroU32;
+use wgpu::*;
+
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+// filtered colour sampler, `address_mode` decides what uvs outside [0, 1] read
+pub fn sampler(device: &Device, address_mode: AddressMode) -> Sampler {
+    device.create_sampler(&SamplerDescriptor {
+        label: Some("Sampler"),
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: TextureView,
+}
+
+impl Texture {
+    pub fn from_rgba(
+        device: &Device,
+        queue: &Queue,
+        label: &str,
+        image: &image::RgbaImage,
+        format: TextureFormat,
+    ) -> Self {
+        let (width, height) = image.dimensions();
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            image.as_raw(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * width),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            size,
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+
+    // has to match the sample count of the colour target it's used with
+    pub fn depth(device: &Device, width: u32, height: u32, sample_count: u32) -> Self {
+        // multisampled depth can't be bound for sampling
+        let usage = if sample_count > 1 {
+            TextureUsages::RENDER_ATTACHMENT
+        } else {
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING
+        };
+        Self::render_target(
+            device,
+            "Depth Texture",
+            DEPTH_FORMAT,
+            width,
+            height,
+            sample_count,
+            usage,
+        )
+    }
+
+    pub fn render_target(
+        device: &Device,
+        label: &str,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        usage: TextureUsages,
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+}
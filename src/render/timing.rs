@@ -0,0 +1,110 @@
+use std::{cell::Cell, mem};
+use wgpu::*;
+
+// timestamps written per frame: start, after the shadow pass, after the main pass, at the end
+const TIMESTAMP_COUNT: u32 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GpuTimings {
+    pub shadow_ms: f32,
+    pub main_ms: f32,
+    // tonemap, TAA, auto exposure and the post chain, whichever of them run
+    pub post_ms: f32,
+}
+
+/*
+ * Per-pass GPU durations from timestamp queries. Only exists when the adapter supports
+ * TIMESTAMP_QUERY. Reading the results back waits for the GPU every frame, so
+ * measuring is off until asked for, e.g. while the stats are shown.
+ */
+pub struct GpuTimer {
+    pub enabled: bool,
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    size: u64,
+    // nanoseconds per timestamp tick
+    period: f32,
+    last: Cell<Option<GpuTimings>>,
+}
+
+impl GpuTimer {
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let size = (TIMESTAMP_COUNT as usize * mem::size_of::<u64>()) as u64;
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            enabled: false,
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            size,
+            period: queue.get_timestamp_period(),
+            last: Cell::new(None),
+        })
+    }
+
+    // index 0 is the start of the frame, see TIMESTAMP_COUNT
+    pub fn write(&self, encoder: &mut CommandEncoder, index: u32) {
+        if self.enabled {
+            encoder.write_timestamp(&self.query_set, index);
+        }
+    }
+
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        if self.enabled {
+            encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &self.resolve_buffer,
+                0,
+                &self.readback_buffer,
+                0,
+                self.size,
+            );
+        }
+    }
+
+    // call after submitting, blocks until the frame's commands have finished
+    pub fn read(&self, device: &Device) {
+        if !self.enabled {
+            return;
+        }
+        let slice = self.readback_buffer.slice(..);
+        let mapping = slice.map_async(MapMode::Read);
+        device.poll(Maintain::Wait);
+        if pollster::block_on(mapping).is_err() {
+            return;
+        }
+        let ticks: Vec<u64> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        self.readback_buffer.unmap();
+        let ms = |from: u64, to: u64| to.saturating_sub(from) as f32 * self.period / 1_000_000.0;
+        self.last.set(Some(GpuTimings {
+            shadow_ms: ms(ticks[0], ticks[1]),
+            main_ms: ms(ticks[1], ticks[2]),
+            post_ms: ms(ticks[2], ticks[3]),
+        }));
+    }
+
+    pub fn last(&self) -> Option<GpuTimings> {
+        self.last.get()
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+// Forsyth's tuning, for a cache model rather than any particular GPU
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+// FIFO size ACMR is measured with, about what desktop GPUs behave like
+pub(super) const ACMR_CACHE_SIZE: usize = 16;
+
+/*
+ * Average cache miss ratio, how many vertices get transformed per triangle with a FIFO
+ * post-transform cache of `cache_size` entries. 3 is the worst, around 0.5 about the best
+ * a regular mesh can do.
+ */
+pub(super) fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    let mut misses = 0;
+    for index in indices {
+        if !cache.contains(index) {
+            misses += 1;
+            cache.push_back(*index);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / triangle_count as f32
+}
+
+// vertices just used score high, as do ones with few triangles left to keep them from lingering
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cached = match cache_position {
+        // the triangle just added, it's ambiguous which of them hit
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    cached + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/*
+ * Tom Forsyth's linear-speed vertex cache optimisation: triangles get emitted greedily,
+ * always the one whose vertices score highest under a simulated LRU cache. Takes and
+ * returns a triangle list, the triangles themselves and their winding are unchanged.
+ */
+pub(super) fn optimize(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let mut remaining = vec![0; vertex_count];
+    for &index in indices {
+        remaining[index as usize] += 1;
+    }
+    // the triangles using each vertex, vertex v's are at offsets[v]..offsets[v + 1]
+    let mut offsets = vec![0; vertex_count + 1];
+    for v in 0..vertex_count {
+        offsets[v + 1] = offsets[v] + remaining[v];
+    }
+    let mut fill = offsets.clone();
+    let mut vertex_triangles = vec![0; indices.len()];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &index in corners {
+            vertex_triangles[fill[index as usize]] = triangle;
+            fill[index as usize] += 1;
+        }
+    }
+
+    let mut scores: Vec<f32> = remaining
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+    let mut added = vec![false; triangle_count];
+    let mut cache: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    let mut next_unadded = 0;
+    let mut best = None;
+    while output.len() < triangle_count * 3 {
+        let triangle = match best.take() {
+            Some(triangle) => triangle,
+            // nothing in the cache has triangles left, carry on from the first untouched one
+            None => {
+                while added[next_unadded] {
+                    next_unadded += 1;
+                }
+                next_unadded
+            }
+        };
+        added[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(corners);
+        for &index in corners {
+            remaining[index as usize] -= 1;
+        }
+
+        let corners: Vec<usize> = corners.iter().map(|&index| index as usize).collect();
+        cache.retain(|v| !corners.contains(v));
+        for (position, v) in corners.into_iter().enumerate() {
+            cache.insert(position, v);
+        }
+        for v in cache.drain(CACHE_SIZE.min(cache.len())..) {
+            scores[v] = vertex_score(None, remaining[v]);
+        }
+        for (position, &v) in cache.iter().enumerate() {
+            scores[v] = vertex_score(Some(position), remaining[v]);
+        }
+
+        let mut best_score = f32::NEG_INFINITY;
+        for &v in &cache {
+            for &candidate in &vertex_triangles[offsets[v]..offsets[v + 1]] {
+                if added[candidate] {
+                    continue;
+                }
+                let score: f32 = indices[candidate * 3..candidate * 3 + 3]
+                    .iter()
+                    .map(|&index| scores[index as usize])
+                    .sum();
+                if score > best_score {
+                    best_score = score;
+                    best = Some(candidate);
+                }
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a `size` by `size` grid of quads with its triangles listed in a scattered order
+    fn scattered_grid(size: u32) -> (Vec<u32>, usize) {
+        let row = size + 1;
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let corner = y * row + x;
+                triangles.push([corner, corner + 1, corner + row + 1]);
+                triangles.push([corner, corner + row + 1, corner + row]);
+            }
+        }
+        // 97 shares no factor with the power of two triangle count, so every one is visited
+        let count = triangles.len();
+        let indices = (0..count)
+            .flat_map(|i| triangles[i * 97 % count].to_vec())
+            .collect();
+        (indices, (row * row) as usize)
+    }
+
+    // each triangle rotated to start at its smallest index, then sorted
+    fn triangle_set(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let first = (0..3).min_by_key(|&i| t[i]).unwrap();
+                [t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn acmr_counts_misses_per_triangle() {
+        assert_eq!(acmr(&[], ACMR_CACHE_SIZE), 0.0);
+        assert_eq!(acmr(&[0, 1, 2], ACMR_CACHE_SIZE), 3.0);
+        // the second triangle shares an edge, only its third vertex misses
+        assert_eq!(acmr(&[0, 1, 2, 2, 1, 3], ACMR_CACHE_SIZE), 2.0);
+        // a cache too small to keep the shared edge misses on everything
+        assert_eq!(acmr(&[0, 1, 2, 3, 4, 5, 0, 1, 2], 3), 3.0);
+    }
+
+    #[test]
+    fn optimize_lowers_acmr() {
+        let (indices, vertex_count) = scattered_grid(16);
+        let before = acmr(&indices, ACMR_CACHE_SIZE);
+        let optimized = optimize(&indices, vertex_count);
+        let after = acmr(&optimized, ACMR_CACHE_SIZE);
+        assert!(before > 2.0, "scattered grid ACMR {}", before);
+        assert!(after < 1.5, "optimized ACMR {}", after);
+    }
+
+    #[test]
+    fn optimize_keeps_the_triangles_and_their_winding() {
+        let (indices, vertex_count) = scattered_grid(4);
+        let optimized = optimize(&indices, vertex_count);
+        assert_eq!(triangle_set(&optimized), triangle_set(&indices));
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{smooth, MeshData};
+
+/*
+ * Which of `data.triangles()` need flipping for every triangle to agree with its neighbours
+ * on winding. Neighbours are found through edges on welded positions shared by exactly two
+ * triangles, non-manifold edges don't say anything about orientation. Each connected
+ * component keeps whichever winding most of its triangles already had.
+ */
+pub(super) fn flips(data: &MeshData) -> Vec<bool> {
+    let (group_of, _) = smooth::weld(data);
+    let triangles: Vec<[usize; 3]> = data
+        .triangles()
+        .iter()
+        .map(|tri| {
+            [
+                group_of[tri[0] as usize],
+                group_of[tri[1] as usize],
+                group_of[tri[2] as usize],
+            ]
+        })
+        .collect();
+    // every triangle touching an edge, and whether it runs the edge from low to high
+    let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        for i in 0..3 {
+            let (a, b) = (tri[i], tri[(i + 1) % 3]);
+            if a != b {
+                edges
+                    .entry((a.min(b), a.max(b)))
+                    .or_default()
+                    .push((t, a < b));
+            }
+        }
+    }
+    let mut neighbours = vec![Vec::new(); triangles.len()];
+    for faces in edges.values() {
+        if let [(s, s_forward), (t, t_forward)] = faces[..] {
+            // wound the same way they run a shared edge in opposite directions
+            let agree = s_forward != t_forward;
+            neighbours[s].push((t, agree));
+            neighbours[t].push((s, agree));
+        }
+    }
+
+    let mut flipped = vec![false; triangles.len()];
+    let mut visited = vec![false; triangles.len()];
+    let mut queue = VecDeque::new();
+    for seed in 0..triangles.len() {
+        if visited[seed] {
+            continue;
+        }
+        visited[seed] = true;
+        queue.push_back(seed);
+        let mut component = Vec::new();
+        while let Some(t) = queue.pop_front() {
+            component.push(t);
+            for &(n, agree) in &neighbours[t] {
+                if !visited[n] {
+                    visited[n] = true;
+                    flipped[n] = flipped[t] ^ !agree;
+                    queue.push_back(n);
+                }
+            }
+        }
+        // the seed's winding was a guess, go with the majority instead
+        let flip_count = component.iter().filter(|&&t| flipped[t]).count();
+        if flip_count * 2 > component.len() {
+            for t in component {
+                flipped[t] = !flipped[t];
+            }
+        }
+    }
+    flipped
+}
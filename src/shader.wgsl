@@ -1,25 +1,428 @@
 struct VertexOutput {
     [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] view_normal: vec3<f32>;
+    [[location(2)]] world_normal: vec3<f32>;
+    [[location(3)]] uv: vec2<f32>;
+    [[location(4)]] scalar: f32;
+    [[location(5)]] ao: f32;
 };
 
 [[block]]
 struct Locals {
     transform: mat4x4<f32>;
+    view: mat4x4<f32>;
+    // xyz: normal, w: distance. the side the normal points to is discarded
+    clip_planes: array<vec4<f32>, 4>;
+    display_mode: u32;
+    // seconds of simulation time, see Renderer::update
+    time: f32;
+    color_space: u32;
+    // 1 when the surface encodes to sRGB on write
+    surface_srgb: u32;
+    light_view_proj: mat4x4<f32>;
+    // xyz: direction the light travels in
+    light_direction: vec4<f32>;
+    // x: enabled, y: shadow map texel size, z: depth bias
+    shadow_params: vec4<f32>;
+    color_ramp: u32;
+    // negative while slicing is off
+    slice_half_width: f32;
+    normal_space: u32;
+    // 1 to shade with the baked per-vertex occlusion
+    vertex_ao: u32;
+    // xyz: slab normal, w: centre of the slab along it
+    slice: vec4<f32>;
+    // rgb: flat colour for back faces, a: 1 while it's used
+    backface_color: vec4<f32>;
+    // linear rgba for DISPLAY_MODE_FLAT_COLOR
+    flat_color: vec4<f32>;
+    // 1 while alpha cutoff objects get drawn with alpha to coverage, it needs multisampling
+    alpha_to_coverage: u32;
+    // 1 while the override_ factors replace every object's, see Renderer::set_material_override
+    material_override: u32;
+    override_base_color: vec4<f32>;
+    override_emissive: vec4<f32>;
+    override_material: vec4<f32>;
+    // x: near, y: far, z: 1 for orthographic, w: 1 when depth is 0..1 rather than -1..1
+    depth_range: vec4<f32>;
 };
 [[group(0), binding(0)]]
 var<uniform> r_locals: Locals;
+[[group(0), binding(1)]]
+var t_matcap: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_sampler: sampler;
+[[group(0), binding(3)]]
+var t_shadow: texture_depth_2d;
+[[group(0), binding(4)]]
+var s_shadow: sampler_comparison;
+
+[[block]]
+struct Morph {
+    // packed 4 to a vec4 to satisfy uniform array stride rules
+    weights: array<vec4<f32>, 2>;
+    target_count: u32;
+    vertex_count: u32;
+    // scaled by w so it's a constant amount of depth, see Renderer::set_layer_priority
+    depth_offset: f32;
+    // MaterialFactors, for DISPLAY_MODE_CHANNEL
+    base_color: vec4<f32>;
+    emissive: vec4<f32>;
+    // x: metallic, y: roughness, z: alpha cutoff, negative without one
+    material: vec4<f32>;
+};
+[[block]]
+struct MorphDeltas {
+    // target-major: delta for vertex v of target t is at t * vertex_count + v
+    deltas: array<vec4<f32>>;
+};
+[[group(1), binding(0)]]
+var<uniform> r_morph: Morph;
+[[group(1), binding(1)]]
+var<storage, read> r_morph_deltas: MorphDeltas;
+
+// must match DisplayMode::shader_id
+let DISPLAY_MODE_DEFAULT: u32 = 0u;
+let DISPLAY_MODE_MATCAP: u32 = 1u;
+let DISPLAY_MODE_UV_CHECKER: u32 = 2u;
+let DISPLAY_MODE_CURVATURE: u32 = 3u;
+let DISPLAY_MODE_HEATMAP: u32 = 4u;
+let DISPLAY_MODE_NORMALS: u32 = 5u;
+let DISPLAY_MODE_FLAT_DERIVED: u32 = 6u;
+let DISPLAY_MODE_FLAT_COLOR: u32 = 7u;
+let DISPLAY_MODE_DEPTH: u32 = 8u;
+// and up, plus MaterialChannel::shader_id
+let DISPLAY_MODE_CHANNEL: u32 = 9u;
+let CHANNEL_ALBEDO: u32 = 0u;
+let CHANNEL_METALLIC: u32 = 1u;
+let CHANNEL_ROUGHNESS: u32 = 2u;
+let CHANNEL_NORMAL: u32 = 3u;
+let CHANNEL_AO: u32 = 4u;
+let CHANNEL_EMISSIVE: u32 = 5u;
+
+// must match NormalSpace::shader_id
+let NORMAL_SPACE_WORLD: u32 = 0u;
+let NORMAL_SPACE_VIEW: u32 = 1u;
+
+// must match ColorRamp::shader_id
+let COLOR_RAMP_VIRIDIS: u32 = 0u;
+let COLOR_RAMP_JET: u32 = 1u;
+
+let UV_CHECKER_TILES: f32 = 8.0;
+// how much normal change per pixel maps to the hot end of the ramp
+let CURVATURE_GAIN: f32 = 8.0;
+let FLAT_ALBEDO: f32 = 0.8;
+
+// must match ColorSpace::shader_id
+let COLOR_SPACE_LINEAR: u32 = 0u;
+let COLOR_SPACE_SRGB: u32 = 1u;
+let COLOR_SPACE_REC709: u32 = 2u;
 
 [[stage(vertex)]]
 fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
     [[location(0)]] position: vec3<f32>,
+    [[location(1)]] normal: vec3<f32>,
+    [[location(2)]] uv: vec2<f32>,
+    // normalized per-vertex data for the heatmap, 0 when none was set
+    [[location(3)]] scalar: f32,
+    // 1 until baked, see Renderer::bake_vertex_ao
+    [[location(4)]] ao: f32,
 ) -> VertexOutput {
+    var morphed = position;
+    for (var i: u32 = 0u; i < r_morph.target_count; i = i + 1u) {
+        let weight = r_morph.weights[i / 4u][i % 4u];
+        let delta = r_morph_deltas.deltas[i * r_morph.vertex_count + vertex_index];
+        morphed = morphed + weight * delta.xyz;
+    }
     var out: VertexOutput;
-    out.position = r_locals.transform * vec4<f32>(position, 1.0);
+    out.position = r_locals.transform * vec4<f32>(morphed, 1.0);
+    out.position.z = out.position.z - r_morph.depth_offset * out.position.w;
+    out.world_position = morphed;
+    out.view_normal = (r_locals.view * vec4<f32>(normal, 0.0)).xyz;
+    out.world_normal = normal;
+    out.uv = uv;
+    out.scalar = scalar;
+    out.ao = ao;
     return out;
 }
 
+// stretching shows up as non-square cells, the tint gives away flipped or rotated islands
+fn uv_checker(uv: vec2<f32>) -> vec3<f32> {
+    let cell = floor(uv * UV_CHECKER_TILES);
+    let parity = abs(cell.x + cell.y) % 2.0;
+    let shade = mix(0.25, 0.9, parity);
+    let tint = vec3<f32>(fract(uv), 1.0) * 0.3 + vec3<f32>(0.7, 0.7, 0.7);
+    return shade * tint;
+}
+
+fn srgb_encode(c: f32) -> f32 {
+    if (c <= 0.0031308) {
+        return c * 12.92;
+    }
+    return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+fn srgb_decode(c: f32) -> f32 {
+    if (c <= 0.04045) {
+        return c / 12.92;
+    }
+    return pow((c + 0.055) / 1.055, 2.4);
+}
+
+fn rec709_encode(c: f32) -> f32 {
+    if (c < 0.018) {
+        return c * 4.5;
+    }
+    return 1.099 * pow(c, 0.45) - 0.099;
+}
+
+fn encode_channel(c: f32) -> f32 {
+    var encoded = c;
+    if (r_locals.color_space == COLOR_SPACE_SRGB) {
+        encoded = srgb_encode(c);
+    }
+    if (r_locals.color_space == COLOR_SPACE_REC709) {
+        encoded = rec709_encode(c);
+    }
+    // an sRGB surface encodes again on write, undo that so the stored value is ours
+    if (r_locals.surface_srgb == 1u) {
+        encoded = srgb_decode(encoded);
+    }
+    return encoded;
+}
+
+// linear shading result to what gets written to the surface
+fn encode_output(c: vec3<f32>) -> vec3<f32> {
+    // no upper clamp, an HDR target keeps values above 1 for the tonemap pass
+    let clamped = max(c, vec3<f32>(0.0, 0.0, 0.0));
+    return vec3<f32>(
+        encode_channel(clamped.x),
+        encode_channel(clamped.y),
+        encode_channel(clamped.z)
+    );
+}
+
+// polynomial fit of matplotlib's viridis
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273, 0.0054073, 0.3340998);
+    let c1 = vec3<f32>(0.1050930, 1.4046135, 1.3845902);
+    let c2 = vec3<f32>(-0.3308618, 0.2148476, 0.0950952);
+    let c3 = vec3<f32>(-4.6342305, -5.7991010, -19.3324410);
+    let c4 = vec3<f32>(6.2282699, 14.1799334, 56.6905526);
+    let c5 = vec3<f32>(4.7763850, -13.7451454, -65.3530326);
+    let c6 = vec3<f32>(-5.4354559, 4.6458526, 26.3124352);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn jet(t: f32) -> vec3<f32> {
+    let r = clamp(1.5 - abs(4.0 * t - 3.0), 0.0, 1.0);
+    let g = clamp(1.5 - abs(4.0 * t - 2.0), 0.0, 1.0);
+    let b = clamp(1.5 - abs(4.0 * t - 1.0), 0.0, 1.0);
+    return vec3<f32>(r, g, b);
+}
+
+fn heatmap(scalar: f32) -> vec3<f32> {
+    let t = clamp(scalar, 0.0, 1.0);
+    if (r_locals.color_ramp == COLOR_RAMP_JET) {
+        return jet(t);
+    }
+    return viridis(t);
+}
+
+// blue for flat, through green, to red for sharply curved
+fn curvature_ramp(k: f32) -> vec3<f32> {
+    let t = clamp(k, 0.0, 1.0);
+    let cold = mix(vec3<f32>(0.1, 0.2, 0.8), vec3<f32>(0.1, 0.8, 0.2), clamp(t * 2.0, 0.0, 1.0));
+    return mix(cold, vec3<f32>(0.9, 0.1, 0.1), clamp(t * 2.0 - 1.0, 0.0, 1.0));
+}
+
+// fraction of the light reaching this fragment, 3x3 PCF over the shadow map
+fn shadow_factor(world_position: vec3<f32>, world_normal: vec3<f32>) -> f32 {
+    if (r_locals.shadow_params.x == 0.0) {
+        return 1.0;
+    }
+    // faces turned away from the light are in their own shadow
+    if (dot(normalize(world_normal), -r_locals.light_direction.xyz) <= 0.0) {
+        return 0.0;
+    }
+    let light_clip = r_locals.light_view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+    let texel = r_locals.shadow_params.y;
+    let depth = ndc.z - r_locals.shadow_params.z;
+    var lit = 0.0;
+    for (var x: i32 = -1; x <= 1; x = x + 1) {
+        for (var y: i32 = -1; y <= 1; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompare(t_shadow, s_shadow, uv + offset, depth);
+        }
+    }
+    return lit / 9.0;
+}
+
+// the object's material factors, or the override's
+fn base_color() -> vec4<f32> {
+    if (r_locals.material_override == 1u) {
+        return r_locals.override_base_color;
+    }
+    return r_morph.base_color;
+}
+
+fn emissive() -> vec3<f32> {
+    if (r_locals.material_override == 1u) {
+        return r_locals.override_emissive.rgb;
+    }
+    return r_morph.emissive.rgb;
+}
+
+// x: metallic, y: roughness, z: alpha cutoff, which the override never has
+fn material_params() -> vec4<f32> {
+    if (r_locals.material_override == 1u) {
+        return vec4<f32>(r_locals.override_material.xy, -1.0, 0.0);
+    }
+    return r_morph.material;
+}
+
+// the override's base colour under a diffuse light and a highlight that widens with roughness
+fn clay(view_normal: vec3<f32>) -> vec3<f32> {
+    let params = material_params();
+    let roughness = clamp(params.y, 0.05, 1.0);
+    let n = normalize(view_normal);
+    let l = normalize((r_locals.view * vec4<f32>(-r_locals.light_direction.xyz, 0.0)).xyz);
+    let h = normalize(l + vec3<f32>(0.0, 0.0, 1.0));
+    let diffuse = max(dot(n, l), 0.0);
+    let shininess = 2.0 / (roughness * roughness * roughness * roughness) - 2.0;
+    let specular = pow(max(dot(n, h), 0.0), shininess) * (1.0 - roughness) * 0.5;
+    let albedo = mix(base_color().rgb, vec3<f32>(0.0, 0.0, 0.0), params.x * 0.5);
+    return albedo * (0.3 + 0.7 * diffuse) + vec3<f32>(specular, specular, specular);
+}
+
+// from the fragment's depth back to its distance in front of the camera, over the clip range
+fn linear_depth(z: f32) -> f32 {
+    let near = r_locals.depth_range.x;
+    let far = r_locals.depth_range.y;
+    var depth = z;
+    if (r_locals.depth_range.w == 0.0) {
+        depth = z * 0.5 + 0.5;
+    }
+    var distance = near * far / (far - depth * (far - near));
+    if (r_locals.depth_range.z == 1.0) {
+        distance = near + depth * (far - near);
+    }
+    return clamp((distance - near) / max(far - near, 0.0001), 0.0, 1.0);
+}
+
+// without textures every channel is its factor, or what a missing map reads as
+fn material_channel(channel: u32) -> vec3<f32> {
+    let params = material_params();
+    let metallic = params.x;
+    let roughness = params.y;
+    if (channel == CHANNEL_METALLIC) {
+        return vec3<f32>(metallic, metallic, metallic);
+    }
+    if (channel == CHANNEL_ROUGHNESS) {
+        return vec3<f32>(roughness, roughness, roughness);
+    }
+    if (channel == CHANNEL_NORMAL) {
+        return vec3<f32>(0.5, 0.5, 1.0);
+    }
+    if (channel == CHANNEL_AO) {
+        return vec3<f32>(1.0, 1.0, 1.0);
+    }
+    if (channel == CHANNEL_EMISSIVE) {
+        return emissive();
+    }
+    return base_color().rgb;
+}
+
 [[stage(fragment)]]
-fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    // return vec4<f32>(1.0, 0.0, 0.0, 1.0);
-    return vec4<f32>(normalize(in.position.xyz), 1.0);
-}
\ No newline at end of file
+fn fs_main(
+    in: VertexOutput,
+    [[builtin(front_facing)]] front_facing: bool,
+) -> [[location(0)]] vec4<f32> {
+    // derivatives have to be taken before any fragment in the quad can discard
+    let n = normalize(in.world_normal);
+    let curvature = (length(dpdx(n)) + length(dpdy(n))) * CURVATURE_GAIN;
+    // framebuffer y points down, so this order faces the camera on front faces
+    let face_normal = normalize(cross(dpdy(in.world_position), dpdx(in.world_position)));
+    let alpha = base_color().a;
+    let alpha_width = max(fwidth(alpha), 0.0001);
+    for (var i: i32 = 0; i < 4; i = i + 1) {
+        let plane = r_locals.clip_planes[i];
+        if (dot(plane.xyz, in.world_position) > plane.w) {
+            discard;
+        }
+    }
+    if (r_locals.slice_half_width >= 0.0) {
+        let offset = dot(r_locals.slice.xyz, in.world_position) - r_locals.slice.w;
+        if (abs(offset) > r_locals.slice_half_width) {
+            discard;
+        }
+    }
+    // alpha mode MASK, see MaterialFactors::alpha_cutoff
+    let cutoff = material_params().z;
+    var coverage = 1.0;
+    if (cutoff >= 0.0) {
+        if (r_locals.alpha_to_coverage == 1u) {
+            // sharpened to about a pixel so it's the cutoff edge that gets antialiased
+            coverage = clamp((alpha - cutoff) / alpha_width + 0.5, 0.0, 1.0);
+        } else {
+            if (alpha < cutoff) {
+                discard;
+            }
+        }
+    }
+    if (!front_facing && r_locals.backface_color.a > 0.0) {
+        return vec4<f32>(encode_output(r_locals.backface_color.rgb), 1.0);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_FLAT_COLOR) {
+        return vec4<f32>(encode_output(r_locals.flat_color.rgb), r_locals.flat_color.a);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_DEPTH) {
+        let shade = 1.0 - linear_depth(in.position.z);
+        return vec4<f32>(encode_output(vec3<f32>(shade, shade, shade)), 1.0);
+    }
+    if (r_locals.display_mode >= DISPLAY_MODE_CHANNEL) {
+        let channel = material_channel(r_locals.display_mode - DISPLAY_MODE_CHANNEL);
+        return vec4<f32>(encode_output(channel), 1.0);
+    }
+    // color = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    var color = vec4<f32>(normalize(in.position.xyz), 1.0);
+    if (r_locals.display_mode == DISPLAY_MODE_DEFAULT && r_locals.material_override == 1u) {
+        color = vec4<f32>(clay(in.view_normal), base_color().a);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_MATCAP) {
+        let n = normalize(in.view_normal);
+        let uv = vec2<f32>(n.x, -n.y) * 0.5 + vec2<f32>(0.5, 0.5);
+        color = textureSample(t_matcap, s_sampler, uv);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_UV_CHECKER) {
+        color = vec4<f32>(uv_checker(in.uv), 1.0);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_CURVATURE) {
+        color = vec4<f32>(curvature_ramp(curvature), 1.0);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_HEATMAP) {
+        color = vec4<f32>(heatmap(in.scalar), 1.0);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_NORMALS) {
+        var normal = normalize(in.world_normal);
+        if (r_locals.normal_space == NORMAL_SPACE_VIEW) {
+            normal = normalize(in.view_normal);
+        }
+        color = vec4<f32>(normal * 0.5 + vec3<f32>(0.5, 0.5, 0.5), 1.0);
+    }
+    if (r_locals.display_mode == DISPLAY_MODE_FLAT_DERIVED) {
+        let diffuse = max(dot(face_normal, -normalize(r_locals.light_direction.xyz)), 0.0);
+        let shade = FLAT_ALBEDO * (0.25 + 0.75 * diffuse);
+        color = vec4<f32>(shade, shade, shade, 1.0);
+    }
+    let lit = shadow_factor(in.world_position, in.world_normal);
+    let ao = select(1.0, in.ao, r_locals.vertex_ao == 1u);
+    return vec4<f32>(encode_output(color.rgb * (0.35 + 0.65 * lit) * ao), color.a * coverage);
+}
@@ -0,0 +1,36 @@
+[[block]]
+struct Light {
+    view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> r_light: Light;
+
+// same layout as the morph group in shader.wgsl
+[[block]]
+struct Morph {
+    weights: array<vec4<f32>, 2>;
+    target_count: u32;
+    vertex_count: u32;
+};
+[[block]]
+struct MorphDeltas {
+    deltas: array<vec4<f32>>;
+};
+[[group(1), binding(0)]]
+var<uniform> r_morph: Morph;
+[[group(1), binding(1)]]
+var<storage, read> r_morph_deltas: MorphDeltas;
+
+[[stage(vertex)]]
+fn vs_shadow(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    [[location(0)]] position: vec3<f32>,
+) -> [[builtin(position)]] vec4<f32> {
+    var morphed = position;
+    for (var i: u32 = 0u; i < r_morph.target_count; i = i + 1u) {
+        let weight = r_morph.weights[i / 4u][i % 4u];
+        let delta = r_morph_deltas.deltas[i * r_morph.vertex_count + vertex_index];
+        morphed = morphed + weight * delta.xyz;
+    }
+    return r_light.view_proj * vec4<f32>(morphed, 1.0);
+}
@@ -0,0 +1,72 @@
+[[block]]
+struct Tonemap {
+    color_space: u32;
+    // 1 when the surface encodes to sRGB on write
+    surface_srgb: u32;
+    exposure: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> r_tonemap: Tonemap;
+[[group(0), binding(1)]]
+var t_hdr: texture_2d<f32>;
+
+// must match ColorSpace::shader_id, same as in shader.wgsl
+let COLOR_SPACE_SRGB: u32 = 1u;
+let COLOR_SPACE_REC709: u32 = 2u;
+
+// one triangle covering the screen, no vertex buffer needed
+[[stage(vertex)]]
+fn vs_tonemap([[builtin(vertex_index)]] vertex_index: u32) -> [[builtin(position)]] vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - vec2<f32>(1.0, 1.0), 0.0, 1.0);
+}
+
+// the transfer curves below are copies of the ones in shader.wgsl
+fn srgb_encode(c: f32) -> f32 {
+    if (c <= 0.0031308) {
+        return c * 12.92;
+    }
+    return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+fn srgb_decode(c: f32) -> f32 {
+    if (c <= 0.04045) {
+        return c / 12.92;
+    }
+    return pow((c + 0.055) / 1.055, 2.4);
+}
+
+fn rec709_encode(c: f32) -> f32 {
+    if (c < 0.018) {
+        return c * 4.5;
+    }
+    return 1.099 * pow(c, 0.45) - 0.099;
+}
+
+fn encode_channel(c: f32) -> f32 {
+    var encoded = c;
+    if (r_tonemap.color_space == COLOR_SPACE_SRGB) {
+        encoded = srgb_encode(c);
+    }
+    if (r_tonemap.color_space == COLOR_SPACE_REC709) {
+        encoded = rec709_encode(c);
+    }
+    if (r_tonemap.surface_srgb == 1u) {
+        encoded = srgb_decode(encoded);
+    }
+    return encoded;
+}
+
+[[stage(fragment)]]
+fn fs_tonemap([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let hdr = textureLoad(t_hdr, vec2<i32>(position.xy), 0);
+    // reinhard, maps [0, inf) into [0, 1)
+    let exposed = max(hdr.rgb, vec3<f32>(0.0, 0.0, 0.0)) * r_tonemap.exposure;
+    let mapped = exposed / (exposed + vec3<f32>(1.0, 1.0, 1.0));
+    return vec4<f32>(
+        encode_channel(mapped.x),
+        encode_channel(mapped.y),
+        encode_channel(mapped.z),
+        hdr.a
+    );
+}